@@ -0,0 +1,150 @@
+//! The per-file progress bars below the summary line of a batch.
+//!
+//! Every file keeps one bar for all of its attempts. Batches can show a
+//! "Queued" bar for each file up front so the bars follow the tasks file; with
+//! `--max-visible-bars` only that many running files get a bar and finished
+//! ones move into a short "recently completed" list instead.
+
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Finished files listed below the running ones with `--max-visible-bars`.
+const RECENT_LINES: usize = 5;
+
+pub(crate) struct Bars {
+    multi: MultiProgress,
+    /// `--max-visible-bars`: running files shown at once (`None` shows every file)
+    max_visible: Option<usize>,
+    /// The "recently completed" section, below the running files
+    recent_pb: Option<ProgressBar>,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Bar of every file, keyed by URL
+    files: HashMap<String, ProgressBar>,
+    /// Files drawn right now (with `max_visible`)
+    visible: Vec<String>,
+    /// Files started while every visible slot was taken, oldest first
+    waiting: VecDeque<String>,
+    /// Final messages of the last finished files, newest last
+    recent: VecDeque<String>,
+}
+
+impl Bars {
+    pub(crate) fn new(multi: MultiProgress, max_visible: Option<usize>) -> Self {
+        let recent_pb = max_visible.map(|_| {
+            let pb = multi.add(ProgressBar::new(0));
+            pb.set_style(ProgressStyle::default_bar().template("{msg}").unwrap());
+            pb
+        });
+        Self { multi, max_visible, recent_pb, inner: Mutex::new(Inner::default()) }
+    }
+
+    /// Show a "Queued" bar for each file, in order; `sizes` has the known sizes by URL.
+    /// Does nothing with `--max-visible-bars`, where the summary line counts queued files.
+    pub(crate) fn queue(&self, files: impl IntoIterator<Item = (String, String)>, sizes: &HashMap<String, u64>) {
+        if self.max_visible.is_some() {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        for (url, name) in files {
+            if inner.files.contains_key(&url) {
+                continue;
+            }
+            let size = sizes.get(&url).map(|size| HumanBytes(*size).to_string()).unwrap_or_default();
+            let pb = self.multi.add(ProgressBar::new(0));
+            pb.set_style(ProgressStyle::default_bar()
+                .template("{spinner:.dim} [--:--:--] [{bar:40.cyan/blue}] {msg}")
+                .unwrap());
+            // Lined up with the sizes of the download bars, like a skipped file
+            pb.set_message(format!("{:>25} {:>17} Queued      {}", size, "", name));
+            inner.files.insert(url, pb);
+        }
+    }
+
+    /// The bar of `url`, reset to `len`: the one [`queue`](Self::queue) or an earlier
+    /// attempt set up, or a new one (hidden until a slot frees up with `--max-visible-bars`).
+    pub(crate) fn file_bar(&self, url: &str, len: u64) -> ProgressBar {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(pb) = inner.files.get(url) {
+            pb.reset();
+            pb.set_length(len);
+            return pb.clone();
+        }
+        let pb = match self.max_visible {
+            None => self.multi.add(ProgressBar::new(len)),
+            Some(max) if inner.visible.len() < max => {
+                inner.visible.push(url.to_string());
+                self.show(ProgressBar::new(len))
+            }
+            Some(_) => {
+                inner.waiting.push_back(url.to_string());
+                ProgressBar::with_draw_target(Some(len), ProgressDrawTarget::hidden())
+            }
+        };
+        inner.files.insert(url.to_string(), pb.clone());
+        pb
+    }
+
+    /// The bar of `url`, if it has one.
+    pub(crate) fn get(&self, url: &str) -> Option<ProgressBar> {
+        self.inner.lock().unwrap().files.get(url).cloned()
+    }
+
+    /// With `--max-visible-bars`, move finished files into the recently completed
+    /// list and give their slots to waiting ones. Called by the summary monitor.
+    pub(crate) fn tidy(&self) {
+        let (Some(max), Some(recent_pb)) = (self.max_visible, &self.recent_pb) else {
+            return;
+        };
+        let mut inner = self.inner.lock().unwrap();
+        let Inner { files, visible, waiting, recent } = &mut *inner;
+        let mut finished = |url: &String, shown: bool| {
+            let done = files.get(url).is_some_and(|pb| pb.is_finished());
+            if done {
+                let pb = files.remove(url).unwrap();
+                if shown {
+                    self.multi.remove(&pb);
+                }
+                recent.push_back(pb.message());
+                if recent.len() > RECENT_LINES {
+                    recent.pop_front();
+                }
+            }
+            !done
+        };
+        visible.retain(|url| finished(url, true));
+        waiting.retain(|url| finished(url, false));
+        while visible.len() < max {
+            let Some(url) = waiting.pop_front() else {
+                break;
+            };
+            if let Some(pb) = files.get(&url) {
+                self.show(pb.clone());
+                visible.push(url);
+            }
+        }
+
+        let hidden = waiting.len();
+        let mut lines = Vec::new();
+        if hidden > 0 {
+            lines.push(format!("  ... {} more running", hidden));
+        }
+        if !recent.is_empty() {
+            lines.push("Recently completed:".to_string());
+            lines.extend(recent.iter().map(|message| format!("  {}", message.trim_start())));
+        }
+        recent_pb.set_message(lines.join("\n"));
+    }
+
+    /// Draw `pb` above the recently completed section.
+    fn show(&self, pb: ProgressBar) -> ProgressBar {
+        match &self.recent_pb {
+            Some(recent_pb) => self.multi.insert_before(recent_pb, pb),
+            None => self.multi.add(pb),
+        }
+    }
+}
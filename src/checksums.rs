@@ -0,0 +1,300 @@
+//! External checksum sources for tasks without an inline hash.
+//!
+//! A checksum file (`--checksum-file`, a local path or URL) may use the GNU
+//! format of `sha256sum` / `md5sum`:
+//!
+//! ```text
+//! 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  model.safetensors
+//! ```
+//!
+//! or the BSD format (`sha256sum --tag`):
+//!
+//! ```text
+//! SHA256 (model.safetensors) = 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//! ```
+//!
+//! Entries are matched to tasks by their path in the output directory, then by
+//! file name. Sidecar files listed in the same batch (`file.bin.sha256` next to
+//! `file.bin`) are fetched and used the same way; for the remaining HTTP(S) tasks
+//! the server is asked for `<url>.sha256`, `.sha1` and `.md5`.
+
+use anyhow::{bail, Context, Result};
+use futures::{stream, StreamExt};
+use reqwest::{Client, StatusCode};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::downloader::{item_relative_path, DownloadOptions};
+use crate::hashing::{HashAlgorithm, VerifyMode};
+use crate::providers::{auth_headers_for, DownloadItem};
+
+/// Sidecar extensions recognised next to a file of the batch.
+const SIDECAR_EXTENSIONS: &[(&str, HashAlgorithm)] = &[
+    (".sha256", HashAlgorithm::Sha256),
+    (".sha1", HashAlgorithm::Sha1),
+    (".md5", HashAlgorithm::Md5),
+];
+
+/// Sidecar lookups in flight at once when probing the server.
+const PROBE_CONCURRENCY: usize = 8;
+
+/// Expected hashes by file path, each formatted as a task hash (`algo:hex`).
+#[derive(Debug, Default)]
+pub struct Checksums {
+    by_path: HashMap<String, String>,
+    by_name: HashMap<String, String>,
+}
+
+impl Checksums {
+    /// Parse a checksum file. `hint` (usually the file name, e.g. `MD5SUMS`) picks
+    /// the algorithm of GNU-style lines; otherwise it is guessed from the digest length.
+    pub fn parse(content: &str, hint: &str) -> Self {
+        let hint = hint.to_lowercase();
+        let mut sums = Self::default();
+        for line in content.lines() {
+            if let Some((name, hash)) = parse_line(line, &hint) {
+                sums.insert(&name, hash);
+            }
+        }
+        sums
+    }
+
+    fn insert(&mut self, name: &str, hash: String) {
+        let path = name.trim_start_matches("./").replace('\\', "/");
+        let file_name = path.rsplit('/').next().unwrap_or(&path).to_string();
+        self.by_name.entry(file_name).or_insert_with(|| hash.clone());
+        self.by_path.insert(path, hash);
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_path.is_empty()
+    }
+
+    /// Expected hash of `relative_path`, falling back to an entry with the same file name.
+    pub fn lookup(&self, relative_path: &Path) -> Option<&String> {
+        let path = relative_path.to_string_lossy().replace('\\', "/");
+        self.by_path.get(&path).or_else(|| {
+            let file_name = relative_path.file_name()?.to_string_lossy();
+            self.by_name.get(file_name.as_ref())
+        })
+    }
+
+    /// Give every item without a hash the one listed for its file. Returns how many were filled.
+    pub fn apply(&self, items: &mut [DownloadItem]) -> usize {
+        let mut filled = 0;
+        for item in items.iter_mut().filter(|i| i.hash.is_none()) {
+            let Ok(relative) = item_relative_path(item) else {
+                continue;
+            };
+            if let Some(hash) = self.lookup(&relative) {
+                item.hash = Some(hash.clone());
+                filled += 1;
+            }
+        }
+        filled
+    }
+}
+
+/// One entry of a checksum file as `(name, "algo:hex")`; comments and blank lines give `None`.
+fn parse_line(line: &str, hint: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    // BSD: `SHA256 (name) = hex`
+    if let Some((head, digest)) = line.rsplit_once(") = ") {
+        let (algo, name) = head.split_once(" (")?;
+        let algo = HashAlgorithm::from_prefix(algo)?;
+        return is_hex(digest).then(|| (name.to_string(), format!("{}:{}", prefix(algo), digest.to_lowercase())));
+    }
+
+    // GNU: `hex  name`, `hex *name` for binary mode, or a bare `hex` in a sidecar file
+    let (digest, name) = match line.split_once(char::is_whitespace) {
+        Some((digest, name)) => (digest, name.trim_start().trim_start_matches('*')),
+        None => (line, ""),
+    };
+    if !is_hex(digest) {
+        return None;
+    }
+    let algo = algorithm_for(digest.len(), hint)?;
+    Some((name.to_string(), format!("{}:{}", prefix(algo), digest.to_lowercase())))
+}
+
+fn is_hex(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Algorithm of a GNU-style digest: named by the file (`MD5SUMS`, `b3sums.txt`), else by length.
+fn algorithm_for(len: usize, hint: &str) -> Option<HashAlgorithm> {
+    let named = [
+        ("blake3", HashAlgorithm::Blake3),
+        ("b3sum", HashAlgorithm::Blake3),
+        ("sha256", HashAlgorithm::Sha256),
+        ("sha1", HashAlgorithm::Sha1),
+        ("md5", HashAlgorithm::Md5),
+    ];
+    if let Some((_, algo)) = named.iter().find(|(name, _)| hint.contains(name)) {
+        return Some(*algo);
+    }
+    match len {
+        32 => Some(HashAlgorithm::Md5),
+        40 => Some(HashAlgorithm::Sha1),
+        64 => Some(HashAlgorithm::Sha256),
+        _ => None,
+    }
+}
+
+fn prefix(algo: HashAlgorithm) -> &'static str {
+    match algo {
+        HashAlgorithm::Sha256 => "sha256",
+        HashAlgorithm::Sha1 => "sha1",
+        HashAlgorithm::Md5 => "md5",
+        HashAlgorithm::Blake3 => "blake3",
+    }
+}
+
+async fn fetch_text(url: &str, options: &DownloadOptions) -> Result<String> {
+    let client = options.client_builder().build().context("Failed to build HTTP client")?;
+    let resp = client
+        .get(url)
+        .headers(auth_headers_for(url, options.token.as_deref()))
+        .send()
+        .await
+        .context(format!("Failed to fetch {}", url))?;
+    if !resp.status().is_success() {
+        bail!("Failed to fetch {}: HTTP {}", url, resp.status());
+    }
+    resp.text().await.context(format!("Failed to read {}", url))
+}
+
+/// Load a checksum file from a local path or an `http(s)://` URL.
+pub async fn load_checksum_file(source: &str, options: &DownloadOptions) -> Result<Checksums> {
+    let content = if source.contains("://") {
+        fetch_text(source, options).await?
+    } else {
+        tokio::fs::read_to_string(source)
+            .await
+            .context(format!("Failed to read checksum file: {}", source))?
+    };
+    let hint = source.rsplit('/').next().unwrap_or(source);
+    Ok(Checksums::parse(&content, hint))
+}
+
+/// Fetch the sidecar of `url` with extension `ext`, placed before any query string.
+/// Returns `None` if the server has no such file.
+async fn fetch_sidecar(client: &Client, url: &str, ext: &str, options: &DownloadOptions) -> Result<Option<String>> {
+    let sidecar = match url.split_once('?') {
+        Some((base, query)) => format!("{}{}?{}", base, ext, query),
+        None => format!("{}{}", url, ext),
+    };
+    let resp = options
+        .network
+        .send(client, client.get(&sidecar).headers(auth_headers_for(&sidecar, options.token.as_deref())))
+        .await
+        .context(format!("Failed to fetch {}", sidecar))?;
+    match resp.status() {
+        StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => return Ok(None),
+        status if !status.is_success() => bail!("Failed to fetch {}: HTTP {}", sidecar, status),
+        _ => {}
+    }
+    resp.text().await.map(Some).context(format!("Failed to read {}", sidecar))
+}
+
+/// Look for `<url>.sha256`, `.sha1` then `.md5` on the server and return the first hash found.
+async fn probe_sidecars(client: &Client, url: &str, options: &DownloadOptions) -> Result<Option<String>> {
+    for (ext, algo) in SIDECAR_EXTENSIONS {
+        let Some(content) = fetch_sidecar(client, url, ext, options).await? else {
+            continue;
+        };
+        if let Some((_, hash)) = content.lines().find_map(|line| parse_line(line, prefix(*algo))) {
+            return Ok(Some(hash));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `url` can have a sidecar on its server: an HTTP(S) file that is not a checksum file itself.
+fn probes_sidecar(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    (url.starts_with("http://") || url.starts_with("https://"))
+        && !SIDECAR_EXTENSIONS.iter().any(|(ext, _)| path.ends_with(ext))
+}
+
+/// Fill hashes from sidecar files: first those in the same batch (`file.bin.sha256` next to
+/// `file.bin`), then by probing the server for `<url>.sha256` / `.sha1` / `.md5`, where a 404
+/// means the file has none. Sidecars that cannot be fetched are skipped. Returns how many items
+/// were filled.
+pub async fn apply_sidecars(items: &mut [DownloadItem], options: &DownloadOptions) -> usize {
+    let missing: HashMap<String, usize> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, i)| i.hash.is_none())
+        .map(|(idx, i)| (i.url.clone(), idx))
+        .collect();
+
+    let mut found = vec![];
+    for item in items.iter() {
+        for (ext, algo) in SIDECAR_EXTENSIONS {
+            let Some(target) = item.url.strip_suffix(ext).and_then(|base| missing.get(base)) else {
+                continue;
+            };
+            found.push((*target, item.url.clone(), *algo));
+        }
+    }
+
+    let mut filled = 0;
+    for (target, sidecar, algo) in found {
+        if items[target].hash.is_some() {
+            continue;
+        }
+        let hash = match fetch_text(&sidecar, options).await {
+            Ok(content) => content.lines().find_map(|line| parse_line(line, prefix(algo))),
+            Err(e) => {
+                eprintln!("Skipping checksum {}: {:#}", sidecar, e);
+                continue;
+            }
+        };
+        if let Some((_, hash)) = hash {
+            items[target].hash = Some(hash);
+            filled += 1;
+        }
+    }
+
+    // No point asking the server for hashes that will not be checked
+    if matches!(options.verify_mode, VerifyMode::Off) {
+        return filled;
+    }
+    let Ok(client) = options.client_builder().build() else {
+        return filled;
+    };
+    let probes: Vec<usize> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, i)| i.hash.is_none() && probes_sidecar(&i.url))
+        .map(|(idx, _)| idx)
+        .collect();
+    let found: Vec<_> = stream::iter(probes)
+        .map(|idx| {
+            let (client, url) = (&client, items[idx].url.clone());
+            async move { (idx, probe_sidecars(client, &url, options).await) }
+        })
+        .buffered(PROBE_CONCURRENCY)
+        .collect()
+        .await;
+    for (idx, result) in found {
+        match result {
+            Ok(Some(hash)) => {
+                items[idx].hash = Some(hash);
+                filled += 1;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Skipping checksum of {}: {:#}", items[idx].url, e),
+        }
+    }
+    filled
+}
@@ -1,8 +1,191 @@
-use clap::ValueEnum;
+use clap::{Subcommand, ValueEnum};
+use reqwest::header::{HeaderName, HeaderValue};
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(ValueEnum, Clone, Debug)]
-pub enum VerifyMode {
+use rdl_core::jobs::JobStatus;
+use rdl_core::schedule::Schedule;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Show the effective settings and the config file in use
+    Config,
+    /// Show recorded downloads from the job database, newest first
+    History {
+        /// Only show jobs with this status
+        #[arg(long, value_enum)]
+        status: Option<JobStatus>,
+        /// Maximum number of jobs to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// Download every failed job again
+    Retry,
+    /// List the named daemon sessions (--session) and whether they are running
+    Sessions,
+    /// Manage rdl as a long-running service [Unix only]
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonCommand,
+    },
+    /// Stop one file of the running daemon, by URL or file name [Unix only]
+    Cancel {
+        /// URL, path below the download dir, or file name
+        target: String,
+        /// Also delete its .part / .part.json
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Delete .part / .part.json files no unfinished task needs anymore and report the space freed
+    Clean {
+        /// Only delete partial files not written to for this long, e.g. 7d
+        #[arg(long = "older-than", value_parser = parse_duration)]
+        older_than: Option<Duration>,
+        /// Also delete partial data of unfinished tasks (they start over when run again)
+        #[arg(long)]
+        abandoned: bool,
+    },
+    /// Drop a task from the tasks file and the job database and delete its partial data
+    Remove {
+        /// URL, path below the download dir, or file name
+        target: String,
+    },
+    /// List the branches and tags of a provider repository (usable with --branch)
+    Revisions {
+        /// Repository, e.g. Qwen/Qwen2.5-7B-Instruct
+        model: String,
+    },
+    /// Re-hash downloaded files against their expected hashes and report OK / MISMATCH / MISSING
+    Verify {
+        /// Take files and hashes from a provider repository (org/model) instead of the tasks file
+        #[arg(long)]
+        model: Option<String>,
+        /// Download mismatched and missing files again
+        #[arg(long)]
+        requeue: bool,
+    },
+    /// Keep running and download new lines of a tasks file, or every list file dropped into a folder
+    Watch {
+        /// Tasks file or drop folder
+        target: PathBuf,
+    },
+    /// Watch the clipboard and add every copied http(s) URL to the tasks file
+    Clip {
+        /// Only capture URLs matching this glob (repeatable), e.g. "*huggingface.co/*"
+        #[arg(long = "match", value_parser = parse_glob)]
+        patterns: Vec<glob::Pattern>,
+    },
+    /// Download a directory tree served as Apache/nginx auto-index pages, keeping its structure
+    Mirror {
+        /// URL of the top directory listing
+        url: String,
+        /// Follow subdirectories this many levels deep (0: only the files of the top directory)
+        #[arg(long, default_value_t = 0)]
+        depth: usize,
+    },
+    /// Search provider repositories by name, most downloaded first
+    Search {
+        query: String,
+        /// Maximum number of results to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DaemonCommand {
+    /// Write a systemd user unit (Type=notify) that runs `rdl watch` on the tasks file
+    InstallService {
+        /// Replace an existing unit file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// `--progress`: live progress bars, a plain summary line every `--summary-interval`
+/// (what the daemon log wants instead of redrawn bars), or newline-delimited JSON events.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressMode {
+    Bars,
+    Summary,
+    Json,
+}
+
+/// `--split`: a fixed number of segments per file, or `auto` to tune it by throughput.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Split {
     Auto,
-    On,
-    Off,
+    Fixed(usize),
+}
+
+/// Parse `--split`: `auto` or a positive number.
+pub fn parse_split(value: &str) -> Result<Split, String> {
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(Split::Auto);
+    }
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(Split::Fixed(n)),
+        _ => Err(format!("invalid split count (expected a positive number or `auto`): {}", value)),
+    }
+}
+
+/// Parse a `Key: Value` request header.
+pub fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, val) = value
+        .split_once(':')
+        .ok_or_else(|| format!("invalid header (expected `Key: Value`): {}", value))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|e| format!("invalid header name {:?}: {}", name.trim(), e))?;
+    let val = HeaderValue::from_str(val.trim())
+        .map_err(|e| format!("invalid header value for {}: {}", name, e))?;
+    Ok((name, val))
+}
+
+/// Parse a `--resolve` entry (`host:address` or `host:port:address`).
+pub fn parse_resolve(value: &str) -> Result<(String, std::net::SocketAddr), String> {
+    rdl_core::dns::parse_resolve(value).map_err(|e| e.to_string())
+}
+
+/// Parse a glob pattern for `--include` / `--exclude`.
+pub fn parse_glob(value: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(value).map_err(|e| format!("invalid glob pattern {:?}: {}", value, e))
+}
+
+pub fn parse_schedule(value: &str) -> Result<Schedule, String> {
+    value.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
+/// Check the placeholders of an `--out` template.
+pub fn parse_output_template(value: &str) -> Result<String, String> {
+    rdl_core::providers::check_output_template(value)
+        .map(|_| value.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Parse a human duration such as `500ms`, `30s`, `5m`, `1h`, `7d`; a bare number means seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split_at = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split_at);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {}", value))?;
+    let secs = match unit.trim() {
+        "" | "s" => number,
+        "ms" => number / 1000.0,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        "d" => number * 86400.0,
+        other => return Err(format!("unknown duration unit: {}", other)),
+    };
+    Ok(Duration::from_secs_f64(secs))
 }
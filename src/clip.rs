@@ -0,0 +1,70 @@
+//! `rdl clip`: capture http(s) URLs copied to the clipboard into the tasks file,
+//! for grabbing a series of links while browsing. Pair it with `rdl watch` on the
+//! same file (or just run `rdl` afterwards) to download them.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use rdl_core::tasks::{parse_tasks, InputFormat};
+
+/// How often the clipboard is read
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub fn run(tasks_file: &Path, patterns: &[glob::Pattern]) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new().context("Failed to open the clipboard")?;
+    // URLs already in the tasks file are not added twice
+    let content = std::fs::read_to_string(tasks_file).unwrap_or_default();
+    let mut known: HashSet<String> = parse_tasks(&content, InputFormat::Rdl)
+        .with_context(|| format!("Failed to parse {:?}", tasks_file))?
+        .into_iter()
+        .map(|item| item.url)
+        .collect();
+    // Whatever was copied before starting is not captured
+    let mut last = clipboard.get_text().unwrap_or_default();
+    eprintln!("Capturing copied URLs into {:?} (Ctrl+C to stop)", tasks_file);
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        // Non-text contents (images, files) read as an error
+        let Ok(text) = clipboard.get_text() else {
+            continue;
+        };
+        if text == last {
+            continue;
+        }
+        let urls: Vec<String> = extract_urls(&text)
+            .filter(|url| patterns.is_empty() || patterns.iter().any(|p| p.matches(url)))
+            .filter(|url| known.insert(url.clone()))
+            .collect();
+        last = text;
+        if urls.is_empty() {
+            continue;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(tasks_file)
+            .with_context(|| format!("Failed to open {:?}", tasks_file))?;
+        let unterminated = std::fs::read(tasks_file).is_ok_and(|c| c.last().is_some_and(|b| *b != b'\n'));
+        if unterminated {
+            writeln!(file)?;
+        }
+        for url in &urls {
+            writeln!(file, "{}", url)?;
+            println!("Added {}", url);
+        }
+    }
+}
+
+/// The http(s) URLs in copied text, without the quotes or brackets around them.
+fn extract_urls(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| matches!(c, '"' | '\'' | '<' | '>' | '(' | ')' | '[' | ']' | ',')))
+        .filter(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .filter(|word| reqwest::Url::parse(word).is_ok())
+        .map(str::to_string)
+}
@@ -1,73 +1,329 @@
 use anyhow::{Context, Result, bail};
-use indicatif::HumanBytes;
-use std::collections::HashMap;
+use chrono::{DateTime, Local};
+use indicatif::{HumanBytes, HumanDuration};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::fs;
-use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::sync::Semaphore;
-use num_cpus;
 
-use crate::downloader::Downloader;
-use crate::state::DownloadState;
-use crate::providers::{self, DownloadItem};
-use crate::cli::VerifyMode;
+use rdl_core::checksums::{apply_sidecars, load_checksum_file};
+use rdl_core::control::{spawn_signal_handler, Interrupted, PauseToken};
+use rdl_core::downloader::{
+    apply_layout, item_relative_path, probe_remote, DownloadOptions, Downloaded, Downloader, IfExists, Layout,
+};
+use rdl_core::encoding::ContentEncoding;
+use rdl_core::hashing::{
+    calculate_hash, parse_expected_hash, write_checksum_manifest, ChecksumManifest, VerifyMode, SHA256SUMS,
+};
+use rdl_core::jobs::{JobStatus, JobStore};
+use rdl_core::metalink::format_metalink;
+use rdl_core::mirror::{crawl, MirrorOptions};
+use rdl_core::state::{part_path, partial_base, state_path, DownloadState};
+use rdl_core::providers::{self, DownloadItem, FetchOptions, RepoType, RevisionKind};
+use rdl_core::throttle::{HostThrottle, Throttled};
+use rdl_core::torrent::expand_torrents;
+use rdl_core::tasks::{format_task_line, parse_rdl_lines, parse_task_line, read_tasks_file, read_tasks_file_as, InputFormat};
+use rdl_core::utils::{
+    ascii_filename, backoff_delay, parse_content_disposition, sanitize_filename, sanitize_relative_path, unique_filename,
+};
+use crate::cli::OutputFormat;
+use crate::hooks::Hooks;
+use crate::progress::JsonProgress;
+#[cfg(unix)]
+use crate::ipc::{IpcRequest, IpcResponse};
 
-pub async fn get_total_size(items: &[DownloadItem]) -> HashMap<String, u64> {
-    let client = reqwest::Client::builder()
-        .user_agent("rdl/0.1.0")
+/// Most size probes in flight at once, in total and per host.
+const PROBE_CONCURRENCY: usize = 32;
+const PROBE_CONCURRENCY_PER_HOST: usize = 8;
+/// Retries of a probe that failed with a network or server error; its size then stays unknown.
+const PROBE_RETRIES: u32 = 3;
+
+/// HEAD every item without a known size and return the sizes by URL; see
+/// [`probe_remote`] for servers that refuse HEAD. At most [`PROBE_CONCURRENCY`]
+/// probes run at once and failed ones are retried with backoff. Items that
+/// are not provider files and have no explicit name are named after the
+/// `Content-Disposition` of the response (see [`settle_names`]). Objects in S3 or
+/// GCS without a hash take the MD5 the store reports.
+pub async fn probe_items(items: &mut [DownloadItem], options: &DownloadOptions) -> HashMap<String, u64> {
+    let client = options
+        .client_builder()
         .connect_timeout(std::time::Duration::from_secs(5))
+        .redirect(options.network.send_policy())
         .build()
         .unwrap_or_else(|_| reqwest::Client::new());
 
     let mut handles = vec![];
+    let mut map = HashMap::new();
+    // A dataset of thousands of files must not become thousands of simultaneous requests;
+    // a host answering 429 gets half as many for a while
+    let probes = Arc::new(Semaphore::new(PROBE_CONCURRENCY));
+    let throttle = Arc::new(HostThrottle::new(PROBE_CONCURRENCY_PER_HOST));
 
-    for item in items {
+    for (index, item) in items.iter().enumerate() {
+        // Sizes known from the provider listing or the tasks file need no HEAD request
+        if let Some(size) = item.size.filter(|s| *s > 0) {
+            map.insert(item.url.clone(), size);
+            continue;
+        }
         let client = client.clone();
         let url = item.url.clone();
+        let wants_name = item.name.is_none() && providers::relative_path_for(&url).is_none();
+        let token = options.token.clone();
+        let connect_timeout = options.connect_timeout;
+        let read_timeout = options.read_timeout;
+        let (retries, retry_wait) = (options.retries.min(PROBE_RETRIES), options.retry_wait);
+        let network = options.network.clone();
+        let auto_decompress = options.auto_decompress;
+        let probes = probes.clone();
+        let throttle = throttle.clone();
         handles.push(tokio::spawn(async move {
-            if let Ok(resp) = client.head(&url).send().await {
-                (url, resp.content_length().unwrap_or(0))
-            } else {
-                (url, 0)
+            // Torrent sizes are known once the engine has the metadata
+            if rdl_core::torrent::is_torrent_source(&url) {
+                return (index, url, 0, None, None);
+            }
+            let _probe = probes.acquire_owned().await.expect("probe semaphore is never closed");
+            // FTP has no HEAD; SIZE tells the size, names come from the URL
+            if rdl_core::ftp::is_ftp_url(&url) {
+                let size = rdl_core::ftp::remote_size(&url, connect_timeout).await.ok().flatten();
+                return (index, url, size.unwrap_or(0), None, None);
             }
+            let auth_headers = providers::download_headers_for(&url, token.as_deref(), &network).await;
+            let mut attempt = 0;
+            let remote = loop {
+                let host = throttle.acquire(&url).await;
+                let result = probe_remote(&client, &network, &url, &auth_headers, read_timeout).await;
+                drop(host);
+                match result {
+                    Ok(remote) => break remote,
+                    Err(_) if attempt >= retries => return (index, url, 0, None, None),
+                    Err(e) => {
+                        let backoff = backoff_delay(retry_wait, attempt);
+                        let wait = match e.downcast_ref::<Throttled>() {
+                            Some(throttled) => throttle.throttled(&url, throttled, backoff),
+                            None => backoff,
+                        };
+                        tokio::time::sleep(wait).await;
+                        attempt += 1;
+                    }
+                }
+            };
+            let hash = rdl_core::object_store::reported_hash(&url, &remote.headers);
+            let name = remote
+                .headers
+                .get(reqwest::header::CONTENT_DISPOSITION)
+                .filter(|_| wants_name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_content_disposition);
+            // The length of a body the server compresses is not the size of the decoded file
+            let size = match ContentEncoding::from_headers(&remote.headers) {
+                Some(_) if auto_decompress => 0,
+                _ => remote.size.unwrap_or(0),
+            };
+            (index, url, size, name, hash)
         }));
     }
 
-    let mut map = HashMap::new();
+    let mut names = BTreeMap::new();
     for handle in handles {
-        if let Ok((url, size)) = handle.await {
+        if let Ok((index, url, size, name, hash)) = handle.await {
             if size > 0 {
                 map.insert(url, size);
             }
+            if items[index].hash.is_none() {
+                items[index].hash = hash;
+            }
+            if let Some(name) = name {
+                names.insert(index, name);
+            }
         }
     }
+
+    settle_names(items, names, options);
     map
 }
 
-pub async fn run_downloads(input: PathBuf, output: PathBuf, concurrency: Option<usize>, rate_limit: Option<u32>, split: usize, daemon: bool, verify_mode: VerifyMode) -> Result<()> {
-    if !output.exists() {
-        fs::create_dir_all(&output).await.context("Failed to create output directory")?;
+/// Name every item that has no explicit name yet: after its `Content-Disposition`
+/// file name (`disposition`, by index) if any, then through the `--out` template.
+/// Names that clash within the batch get a `_1`, `_2`, ... suffix: two files saved
+/// to one path would share a `.part` file. Of the paths fixed by the task or the
+/// URL, the first item keeps its path and later ones are renamed.
+fn settle_names(items: &mut [DownloadItem], mut disposition: BTreeMap<usize, String>, options: &DownloadOptions) {
+    let renamed: BTreeSet<usize> = (0..items.len())
+        .filter(|i| items[*i].name.is_none())
+        .filter(|i| disposition.contains_key(i) || options.output_template.is_some())
+        .collect();
+    let mut taken: HashSet<PathBuf> = HashSet::new();
+    let mut clashing = vec![];
+    for (index, item) in items.iter().enumerate().filter(|(index, _)| !renamed.contains(index)) {
+        if let Ok(path) = item_relative_path(item) {
+            if !taken.insert(path) {
+                clashing.push(index);
+            }
+        }
     }
 
-    let file = fs::File::open(&input).await.context(format!("Failed to open input file: {:?}", input))?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
-    let mut items: Vec<DownloadItem> = vec![];
-    while let Some(line) = lines.next_line().await? {
-        let raw = line.trim();
-        if raw.is_empty() {
+    for index in renamed {
+        let item = &mut items[index];
+        let path = match disposition.remove(&index) {
+            Some(name) => PathBuf::from(sanitize_filename(&name)),
+            None => match item_relative_path(item) {
+                Ok(path) => path,
+                Err(_) => continue,
+            },
+        };
+        let name = match &options.output_template {
+            Some(template) => providers::render_output_template(template, &item.url, &path),
+            None => path.to_string_lossy().to_string(),
+        };
+        let name = unique_filename(&name, |n| {
+            sanitize_relative_path(n).is_some_and(|p| taken.contains(&p))
+        });
+        if let Some(path) = sanitize_relative_path(&name) {
+            taken.insert(path);
+        }
+        item.name = Some(name);
+    }
+
+    for index in clashing {
+        let item = &mut items[index];
+        let Ok(path) = item_relative_path(item) else {
             continue;
+        };
+        let name = unique_filename(&path.to_string_lossy().replace('\\', "/"), |n| {
+            sanitize_relative_path(n).is_some_and(|p| taken.contains(&p))
+        });
+        eprintln!("{} has the same path as another file of the batch; saving it as {}", item.url, name);
+        if let Some(path) = sanitize_relative_path(&name) {
+            taken.insert(path);
         }
-        let mut parts = raw.splitn(2, '|');
-        let url = parts.next().unwrap_or_default().trim().to_string();
-        let hash = parts.next().map(|h| h.trim().to_string()).filter(|s| !s.is_empty());
-        if !url.is_empty() {
-            items.push(DownloadItem { url, hash });
+        item.name = Some(name);
+    }
+}
+
+/// Merge the items of the batch with the same URL, which would otherwise download
+/// into the same `.part` file at once. The first one is kept and takes the mirrors
+/// of the others, and any hash, name or size only they have.
+fn dedupe_items(items: Vec<DownloadItem>) -> Vec<DownloadItem> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut unique: Vec<DownloadItem> = Vec::with_capacity(items.len());
+    let mut duplicates = 0;
+    for item in items {
+        let Some(&position) = positions.get(&item.url) else {
+            positions.insert(item.url.clone(), unique.len());
+            unique.push(item);
+            continue;
+        };
+        duplicates += 1;
+        let kept = &mut unique[position];
+        for mirror in item.mirrors {
+            if !kept.mirrors.contains(&mirror) {
+                kept.mirrors.push(mirror);
+            }
+        }
+        kept.hash = kept.hash.take().or(item.hash);
+        kept.name = kept.name.take().or(item.name);
+        kept.size = kept.size.or(item.size);
+        kept.priority = kept.priority.max(item.priority);
+    }
+    if duplicates > 0 {
+        eprintln!("Ignoring {} duplicate URL(s) in the batch", duplicates);
+    }
+    unique
+}
+
+/// Start times of the items whose schedule lies ahead, recorded in the job database
+/// so `rdl list` shows them as scheduled.
+fn schedule_items(items: &[DownloadItem], options: &DownloadOptions, jobs: Option<&JobStore>) -> HashMap<String, DateTime<Local>> {
+    let now = Local::now();
+    let mut start_times = HashMap::new();
+    for item in items {
+        let Some(at) = item.schedule.as_ref().and_then(|s| s.next_start(now)).filter(|at| *at > now) else {
+            continue;
+        };
+        if let (Some(jobs), Ok(path)) = (jobs, item_relative_path(item)) {
+            if let Err(e) = jobs.set_scheduled(&item.url, &options.output_dir.join(path), at) {
+                eprintln!("Failed to record schedule of {}: {:#}", item.url, e);
+            }
+        }
+        start_times.insert(item.url.clone(), at);
+    }
+    if let Some(first) = start_times.values().min() {
+        eprintln!(
+            "Holding {} download(s) until their start time, the first at {}",
+            start_times.len(),
+            first.format("%Y-%m-%d %H:%M")
+        );
+    }
+    start_times
+}
+
+/// Wait until `at`, giving up once the batch is shutting down. The clock is checked
+/// every second, so a suspended machine still starts on time after waking up.
+async fn wait_until(at: DateTime<Local>, token: &PauseToken) -> Result<(), Interrupted> {
+    loop {
+        if token.is_shutting_down() {
+            return Err(Interrupted);
+        }
+        match (at - Local::now()).to_std() {
+            Ok(left) if !left.is_zero() => tokio::time::sleep(left.min(Duration::from_secs(1))).await,
+            _ => return Ok(()),
+        }
+    }
+}
+
+pub async fn run_downloads(input: PathBuf, format: InputFormat, options: DownloadOptions, daemon: bool) -> Result<()> {
+    let items = read_tasks_file_as(&input, format).await?;
+    run_items(items, options, daemon).await
+}
+
+/// `rdl mirror`: list the directory tree below `url` and download it as one batch.
+pub async fn mirror(url: &str, mirror_options: &MirrorOptions, options: DownloadOptions, daemon: bool) -> Result<()> {
+    let client = options.client_builder().build().context("Failed to build HTTP client")?;
+    eprintln!("Listing {} ...", url);
+    let items = crawl(&client, url, mirror_options).await?;
+    if items.is_empty() {
+        bail!("No files found below {}", url);
+    }
+    eprintln!("Found {} file(s) to mirror", items.len());
+    run_items(items, options, daemon).await
+}
+
+/// Merge duplicates, lay out and order the items of a batch.
+fn arrange_items(items: Vec<DownloadItem>, options: &DownloadOptions) -> Vec<DownloadItem> {
+    let mut items = dedupe_items(items);
+    // Before the job database is asked, which knows the files by their laid out path
+    for item in &mut items {
+        apply_layout(item, options.layout);
+        if item.schedule.is_none() {
+            item.schedule = options.schedule.clone();
         }
     }
-    if matches!(verify_mode, VerifyMode::On) {
+    // Files queue for a download slot in this order; the sort keeps the order of equal priorities
+    items.sort_by_key(|item| std::cmp::Reverse(item.priority.unwrap_or(0)));
+    items
+}
+
+/// Probe sizes and settle the names and hashes of the items of a batch, checking
+/// them against the size limits, the free disk space and `--verify-mode on`.
+async fn prepare_items(items: &mut Vec<DownloadItem>, options: &DownloadOptions) -> Result<HashMap<String, u64>> {
+    // Probing also settles file names, so hashes are matched afterwards
+    eprintln!("Calculating total size...");
+    let mut size_map = probe_items(items, options).await;
+    skip_large_files(items, &mut size_map, options);
+    if items.is_empty() {
+        bail!("Every file is larger than --max-file-size");
+    }
+    check_total_size(items, &size_map, options)?;
+    apply_ascii_names(items, options);
+    rename_existing(items, options);
+    check_disk_space(items, &size_map, options).await?;
+    attach_checksums(items, options).await?;
+
+    if matches!(options.verify_mode, VerifyMode::On) {
         // Require hash for every item
         let missing: Vec<String> = items
             .iter()
@@ -78,12 +334,57 @@ pub async fn run_downloads(input: PathBuf, output: PathBuf, concurrency: Option<
             bail!("校验模式为 on，但以下条目缺少 hash: {:?}", missing);
         }
     }
+    Ok(size_map)
+}
 
+pub async fn run_items(items: Vec<DownloadItem>, options: DownloadOptions, daemon: bool) -> Result<()> {
+    if options.dry_run {
+        // Nothing is written: no output directory, no job database, no fetched .torrent files
+        let mut items = arrange_items(items, &options);
+        let size_map = prepare_items(&mut items, &options).await?;
+        let jobs = options.db_path.as_deref().and_then(|path| JobStore::open_read_only(path).ok());
+        print_plan(&items, &size_map, &options, jobs.as_ref());
+        return Ok(());
+    }
+
+    let output = &options.output_dir;
+    if !output.exists() {
+        fs::create_dir_all(output).await.context("Failed to create output directory")?;
+    }
+    let mut items = arrange_items(expand_torrents(items, &options).await?, &options);
+
+    // The job database remembers the batch, so a restarted run skips what already finished
+    let jobs = options.db_path.as_deref().and_then(|path| JobStore::open(path).ok());
+    let mut completed = vec![];
+    if let Some(jobs) = &jobs {
+        (items, completed) = jobs.resume(items, output)?;
+        if !completed.is_empty() {
+            eprintln!("Skipping {} file(s) completed by a previous run", completed.len());
+        }
+    }
+    let previously_completed = completed.len();
+    if items.is_empty() {
+        eprintln!("Nothing left to download.");
+        write_manifest(&options, &completed).await?;
+        write_metalink(&options, &completed)?;
+        let report = BatchReport { skipped: previously_completed, ..Default::default() };
+        write_failed_tasks(output, &report, &HashMap::new())?;
+        return write_report(&options, &report);
+    }
+
+    let size_map = prepare_items(&mut items, &options).await?;
     let total_files = items.len();
+    let verify_mode = options.verify_mode.clone();
 
-    // Pre-calculate total size
-    println!("Calculating total size...");
-    let size_map = get_total_size(&items).await;
+    for item in &mut items {
+        item.size = item.size.or_else(|| size_map.get(&item.url).copied());
+    }
+    if let Some(jobs) = &jobs {
+        if let Err(e) = jobs.enqueue(&items, output) {
+            eprintln!("Failed to record batch queue: {:#}", e);
+        }
+    }
+    let start_times = schedule_items(&items, &options, jobs.as_ref());
     let expected_hashes: HashMap<String, String> = if matches!(verify_mode, VerifyMode::Off) {
         HashMap::new()
     } else {
@@ -93,232 +394,1343 @@ pub async fn run_downloads(input: PathBuf, output: PathBuf, concurrency: Option<
             .collect()
     };
 
-    let downloader = Arc::new(Downloader::new(output.clone(), rate_limit, split, total_files, size_map, expected_hashes, verify_mode.clone()));
-    let concurrency = concurrency.unwrap_or_else(num_cpus::get);
+    let concurrency = options.concurrency.unwrap_or_else(num_cpus::get);
+    let finish_options = options.clone();
+    let hooks = Hooks::new(&options);
+    let json_progress = JsonProgress::open(&options).await?;
+    let total_bytes: u64 = size_map.values().sum();
+    // The manifest covers the files of the batch that succeeded, and those finished by an earlier run
+    let queued = items.clone();
+    let downloader = Arc::new(Downloader::new(options, total_files, size_map, expected_hashes));
+    // Every file of a batch gets its bar right away, in the order of the tasks
+    if total_files > 1 {
+        downloader.queue(&items);
+    }
+    let progress_stream = json_progress.map(|p| tokio::spawn(p.run(downloader.subscribe(), total_files, total_bytes)));
+
+    // The daemon accepts pause/resume over its control socket
+    #[cfg(unix)]
+    let mut control_server = None;
+    #[cfg(unix)]
+    if daemon {
+        let downloader = downloader.clone();
+        // `rdl cancel` may name a file by path instead of URL
+        let batch: Vec<(String, PathBuf)> = items
+            .iter()
+            .filter_map(|i| item_relative_path(i).ok().map(|path| (i.url.clone(), path)))
+            .collect();
+        let task_response = |found: bool, action: &str, url: &str| {
+            if found {
+                IpcResponse::ok(format!("{} {}", action, url))
+            } else {
+                IpcResponse::error(format!("Not downloading: {}", url))
+            }
+        };
+        control_server = Some(crate::ipc::spawn_server(Arc::new(move |request: IpcRequest| match request {
+            IpcRequest::Pause => {
+                downloader.pause_token().pause();
+                IpcResponse::ok("Paused downloads")
+            }
+            IpcRequest::Resume => {
+                downloader.pause_token().resume();
+                IpcResponse::ok("Resumed downloads")
+            }
+            IpcRequest::PauseTask { url } => task_response(downloader.pause_task(&url), "Paused", &url),
+            IpcRequest::ResumeTask { url } => task_response(downloader.resume_task(&url), "Resumed", &url),
+            IpcRequest::CancelTask { url, purge } => {
+                let url = batch
+                    .iter()
+                    .find(|(u, path)| names_task(u, path, &url))
+                    .map_or(url, |(u, _)| u.clone());
+                let action = if purge { "Cancelled and purged" } else { "Cancelled" };
+                task_response(downloader.cancel_task(&url, purge), action, &url)
+            }
+            IpcRequest::Status => IpcResponse {
+                paused: downloader.pause_token().is_paused(),
+                paused_tasks: downloader.paused_tasks(),
+                bytes_per_sec: Some(downloader.bytes_per_sec()),
+                eta_secs: downloader.eta().map(|eta| eta.as_secs()),
+                ..IpcResponse::ok(downloader.summary())
+            },
+        }))?);
+    }
+    // SIGTERM/Ctrl+C stop the batch after every part state has been saved
+    let signal_handler = spawn_signal_handler(downloader.pause_token());
+    #[cfg(unix)]
+    let service_status = crate::systemd::spawn_status(downloader.clone());
+    let hook_events = hooks.as_ref().map(|h| tokio::spawn(h.clone().watch(downloader.subscribe(), items.clone())));
     let semaphore = Arc::new(Semaphore::new(concurrency));
     let mut handles = vec![];
+    let task_lines: HashMap<String, String> = items.iter().map(|i| (i.url.clone(), format_task_line(i))).collect();
+    let started = Instant::now();
 
     for item in items {
         let downloader_clone = downloader.clone();
         let semaphore_clone = semaphore.clone();
         let download_item = item.clone();
         let url_for_log = download_item.url.clone();
+        let start_at = start_times.get(&item.url).copied();
 
         let handle = tokio::spawn(async move {
+            // Scheduled downloads only take a slot once they are due
+            if let Some(at) = start_at {
+                if wait_until(at, &downloader_clone.pause_token()).await.is_err() {
+                    return None;
+                }
+            }
             let _permit = semaphore_clone.acquire().await.unwrap();
-            if let Err(e) = downloader_clone.download_file(download_item).await {
-                eprintln!("Failed to download {}: {}", url_for_log, e);
+            let result = downloader_clone.download_file(download_item).await;
+            if let Err(e) = &result {
+                if !e.is::<Interrupted>() {
+                    eprintln!("Failed to download {}: {}", url_for_log, e);
+                }
             }
+            Some((url_for_log, result))
         });
         handles.push(handle);
     }
 
+    // The outcome of every file comes from its task; events may be missed by a lagging receiver
+    let mut report = BatchReport::default();
+    let mut succeeded = HashSet::new();
+    let mut joined = Ok(());
     for handle in handles {
-        handle.await?;
+        match handle.await {
+            Ok(Some((url, result))) => {
+                if result.is_ok() {
+                    succeeded.insert(url.clone());
+                }
+                report.record(url, result);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                joined = Err(e);
+                break;
+            }
+        }
+    }
+    report.finish(started.elapsed());
+    completed.extend(queued.into_iter().filter(|i| succeeded.contains(&i.url)));
+    // `rdl watch` runs one batch after another; each installs its own handler
+    signal_handler.abort();
+    #[cfg(unix)]
+    if let Some(status) = service_status {
+        status.abort();
+        crate::systemd::notify(&format!("STATUS=Finished batch: {}", downloader.summary()));
     }
 
-    // Clean up PID file if we are the daemon
+    // The control socket belongs to this batch; the PID file is removed once the daemon exits
     #[cfg(unix)]
-    if daemon {
-        crate::daemon::cleanup_pid_file();
+    if let Some(server) = control_server {
+        server.abort();
+        crate::ipc::cleanup_socket();
+    }
+    // A panicked task fails the batch only once the handlers above are gone
+    joined?;
+
+    if downloader.pause_token().is_shutting_down() {
+        downloader.abandon_progress();
+        return Err(Interrupted.into());
+    }
+
+    // Every terminal event was sent before its task ended; only a lagging receiver can miss one
+    if let Some(watch) = hook_events {
+        let _ = tokio::time::timeout(Duration::from_secs(1), watch).await;
+    }
+    if let Some(stream) = progress_stream {
+        let _ = tokio::time::timeout(Duration::from_secs(1), stream).await;
+    }
+    report.skipped += previously_completed;
+    print_batch_report(&report);
+    if let Some(hooks) = &hooks {
+        hooks.batch_finished(report.succeeded + report.skipped, report.failures.len());
+        hooks.finish().await;
+    }
+    write_manifest(&finish_options, &completed).await?;
+    write_metalink(&finish_options, &completed)?;
+    write_report(&finish_options, &report)?;
+    write_failed_tasks(&finish_options.output_dir, &report, &task_lines)?;
+    if report.failed > 0 {
+        bail!("{} of {} file(s) failed to download", report.failed, total_files);
+    }
+    Ok(())
+}
+
+/// `--dry-run`: what a batch would do with each file, judged from the disk and the
+/// job database like the downloader would, without touching either.
+fn print_plan(items: &[DownloadItem], size_map: &HashMap<String, u64>, options: &DownloadOptions, jobs: Option<&JobStore>) {
+    let mut to_download = 0u64;
+    let mut counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+    println!("{:<10} {:>12}  Path", "Action", "Size");
+    println!("{:-<10} {:->12}  {:-<40}", "", "", "");
+    for item in items {
+        let Ok(relative) = item_relative_path(item) else {
+            continue;
+        };
+        let path = options.output_dir.join(&relative);
+        let size = size_map.get(&item.url).copied().filter(|s| *s > 0);
+        let existing = std::fs::metadata(&path).ok().map(|m| m.len());
+        let partial = std::fs::read_to_string(state_path(&options.partial_base(&path)))
+            .ok()
+            .and_then(|content| DownloadState::from_json(&content).ok())
+            .map(|state| state.downloaded_bytes());
+        let elsewhere = jobs
+            .filter(|_| options.dedup && existing.is_none())
+            .and_then(|jobs| jobs.find_completed(&item.url).ok().flatten())
+            .filter(|job| job.path != path && job.path.exists());
+
+        let (action, done, note) = if let Some(job) = elsewhere {
+            ("skip", size, format!(" (already at {})", job.path.display()))
+        } else if let Some(len) = existing {
+            match options.if_exists {
+                IfExists::Skip | IfExists::Rename => ("skip", size, " (exists)".to_string()),
+                IfExists::Overwrite => ("overwrite", Some(0), String::new()),
+                IfExists::Resume => ("resume", Some(len), format!(" (from {})", HumanBytes(len))),
+                IfExists::CheckSize if size.is_some_and(|s| s != len) => {
+                    ("overwrite", Some(0), format!(" (size {} differs)", HumanBytes(len)))
+                }
+                IfExists::CheckSize => ("skip", size, " (size matches)".to_string()),
+                IfExists::CheckHash => ("check", size, " (hash, downloaded again on mismatch)".to_string()),
+            }
+        } else if let Some(len) = partial.filter(|len| *len > 0) {
+            ("resume", Some(len), format!(" (from {})", HumanBytes(len)))
+        } else {
+            ("download", Some(0), String::new())
+        };
+        if let (Some(size), Some(done)) = (size, done) {
+            to_download += size.saturating_sub(done);
+        }
+        *counts.entry(action).or_default() += 1;
+        let size = size.map_or_else(|| "?".to_string(), |s| HumanBytes(s).to_string());
+        println!("{:<10} {:>12}  {}{}", action, size, relative.display(), note);
+    }
+
+    let unknown = items.iter().filter(|i| size_map.get(&i.url).is_none_or(|s| *s == 0)).count();
+    let counts: Vec<String> = counts.iter().map(|(action, n)| format!("{} {}", n, action)).collect();
+    println!();
+    println!(
+        "Dry run: {} file(s) into {:?}: {}; {} to download{}",
+        items.len(),
+        options.output_dir,
+        counts.join(", "),
+        HumanBytes(to_download),
+        if unknown > 0 { format!(" plus {} file(s) of unknown size", unknown) } else { String::new() }
+    );
+    if let Ok(free) = fs2::available_space(existing_dir(&options.output_dir)) {
+        println!("Free space: {}", HumanBytes(free));
+    }
+}
+
+/// Tasks that failed in the last batch of a download dir, in tasks-file format.
+pub const FAILED_TASKS: &str = "failed.txt";
+
+/// Write the failed tasks of a batch to `failed.txt` in the download dir, so they can be
+/// retried on their own; a batch without failures removes the list of an earlier run.
+fn write_failed_tasks(output_dir: &Path, report: &BatchReport, task_lines: &HashMap<String, String>) -> Result<()> {
+    let path = output_dir.join(FAILED_TASKS);
+    if report.failures.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+        return Ok(());
+    }
+    let content: String = report
+        .failures
+        .iter()
+        .map(|f| format!("{}\n", task_lines.get(&f.url).unwrap_or(&f.url)))
+        .collect();
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+    eprintln!(
+        "Wrote {} failed task(s) to {:?}; retry them with: rdl -t {:?} --download-dir {:?}",
+        report.failures.len(),
+        path,
+        path,
+        output_dir
+    );
+    Ok(())
+}
+
+/// Write the `--export-metalink` file for the files of a batch, if one was requested.
+fn write_metalink(options: &DownloadOptions, items: &[DownloadItem]) -> Result<()> {
+    let Some(path) = &options.export_metalink else {
+        return Ok(());
+    };
+    std::fs::write(path, format_metalink(items, options.hash_algo))
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    eprintln!("Wrote metalink for {} file(s) to {:?}", items.len(), path);
+    Ok(())
+}
+
+/// Write the `--report` JSON, if one was requested.
+fn write_report(options: &DownloadOptions, report: &BatchReport) -> Result<()> {
+    let Some(path) = &options.report else {
+        return Ok(());
+    };
+    let file = File::create(path).with_context(|| format!("Failed to create report {:?}", path))?;
+    serde_json::to_writer_pretty(file, report)?;
+    eprintln!("Wrote report to {:?}", path);
+    Ok(())
+}
+
+/// Write the `--checksums` manifest for the files of a batch, if one was requested.
+async fn write_manifest(options: &DownloadOptions, items: &[DownloadItem]) -> Result<()> {
+    let Some(kind) = options.checksum_manifest else {
+        return Ok(());
+    };
+    let mut files: Vec<PathBuf> = items.iter().filter_map(|i| item_relative_path(i).ok()).collect();
+    files.sort();
+    files.dedup();
+    eprintln!("Writing SHA256 checksums...");
+    let count = write_checksum_manifest(&options.output_dir, &files, kind).await?;
+    match kind {
+        ChecksumManifest::Files => eprintln!("Wrote .sha256 files for {} file(s)", count),
+        _ => eprintln!("Wrote {} entries to {:?}", count, options.output_dir.join(SHA256SUMS)),
+    }
+    Ok(())
+}
+
+/// `--max-file-size`: leave out the items known to be larger than the limit.
+/// Items of unknown size are kept.
+fn skip_large_files(items: &mut Vec<DownloadItem>, size_map: &mut HashMap<String, u64>, options: &DownloadOptions) {
+    let Some(limit) = options.max_file_size else {
+        return;
+    };
+    items.retain(|item| match size_map.get(&item.url) {
+        Some(size) if *size > limit => {
+            eprintln!(
+                "Skipping {}: {} is larger than --max-file-size {}",
+                item.url,
+                HumanBytes(*size),
+                HumanBytes(limit)
+            );
+            false
+        }
+        _ => true,
+    });
+    let remaining: HashSet<&str> = items.iter().map(|item| item.url.as_str()).collect();
+    size_map.retain(|url, _| remaining.contains(url.as_str()));
+}
+
+/// `--max-total-size`: refuse to start a batch whose known size exceeds the budget,
+/// unless it is confirmed on a terminal or `--force` is given (then only warn).
+fn check_total_size(items: &[DownloadItem], size_map: &HashMap<String, u64>, options: &DownloadOptions) -> Result<()> {
+    let Some(limit) = options.max_total_size else {
+        return Ok(());
+    };
+    let total: u64 = items.iter().filter_map(|item| size_map.get(&item.url)).sum();
+    if total <= limit {
+        return Ok(());
+    }
+
+    let unknown = items.iter().filter(|i| size_map.get(&i.url).is_none_or(|s| *s == 0)).count();
+    let mut message = format!(
+        "The batch is {} ({} file(s)), more than --max-total-size {}",
+        HumanBytes(total),
+        items.len(),
+        HumanBytes(limit)
+    );
+    if unknown > 0 {
+        message.push_str(&format!(" ({} file(s) of unknown size not counted)", unknown));
+    }
+    if options.dry_run {
+        eprintln!("Warning: {}", message);
+        return Ok(());
+    }
+    if options.force {
+        eprintln!("Warning: {}; continuing because of --force", message);
+        return Ok(());
+    }
+    use std::io::{BufRead, IsTerminal, Write};
+    if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        eprint!("{}. Download it anyway? [y/N] ", message);
+        let _ = std::io::stderr().flush();
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+        }
+        bail!("Cancelled");
     }
+    bail!("{}; raise the limit or use --force to start anyway", message)
+}
 
+/// `dir`, or while it does not exist yet (`--dry-run`) the closest existing directory
+/// above it, on whose filesystem it will be created.
+fn existing_dir(dir: &Path) -> &Path {
+    dir.ancestors().find(|p| p.is_dir()).unwrap_or(Path::new("."))
+}
+
+/// Refuse to start (or only warn with `--force`) when the bytes still missing from
+/// `items` exceed the free space of the output directory. Finished files and the
+/// saved progress of partial ones count as already on disk.
+async fn check_disk_space(items: &[DownloadItem], size_map: &HashMap<String, u64>, options: &DownloadOptions) -> Result<()> {
+    let output = &options.output_dir;
+    let mut needed = 0u64;
+    let mut unknown = 0;
+    for item in items {
+        let Some(size) = size_map.get(&item.url).filter(|s| **s > 0) else {
+            unknown += 1;
+            continue;
+        };
+        let path = output.join(item_relative_path(item)?);
+        let downloaded = if path.exists() {
+            match options.if_exists {
+                IfExists::Overwrite => 0,
+                _ => fs::metadata(&path).await.map_or(0, |m| m.len()),
+            }
+        } else {
+            match fs::read_to_string(state_path(&options.partial_base(&path))).await {
+                Ok(content) => DownloadState::from_json(&content).map_or(0, |s| s.downloaded_bytes()),
+                Err(_) => 0,
+            }
+        };
+        needed += size.saturating_sub(downloaded);
+    }
+
+    let available = match fs2::available_space(existing_dir(output)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Could not check free disk space in {:?}: {}", output, e);
+            return Ok(());
+        }
+    };
+    if needed <= available {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "Not enough disk space in {:?}: the batch needs {} more but only {} is free",
+        output,
+        HumanBytes(needed),
+        HumanBytes(available)
+    );
+    if unknown > 0 {
+        message.push_str(&format!(" ({} file(s) of unknown size not counted)", unknown));
+    }
+    if options.dry_run {
+        eprintln!("Warning: {}", message);
+        Ok(())
+    } else if options.force {
+        eprintln!("Warning: {}; continuing because of --force", message);
+        Ok(())
+    } else {
+        bail!("{}; free some space or use --force to start anyway", message)
+    }
+}
+
+/// `--ascii-names`: pin every item to an ASCII-only version of its path. The name
+/// is kept in the task line, so a resumed batch finds the same files.
+fn apply_ascii_names(items: &mut [DownloadItem], options: &DownloadOptions) {
+    if !options.ascii_names {
+        return;
+    }
+    for item in items.iter_mut() {
+        if let Ok(path) = item_relative_path(item) {
+            let components: Vec<String> = path
+                .iter()
+                .map(|c| ascii_filename(&c.to_string_lossy()))
+                .collect();
+            item.name = Some(components.join("/"));
+        }
+    }
+}
+
+/// `--if-exists rename`: give every item whose file is already on disk the next
+/// free `name_1.ext` name, kept in the task line like any other explicit name.
+fn rename_existing(items: &mut [DownloadItem], options: &DownloadOptions) {
+    if options.if_exists != IfExists::Rename {
+        return;
+    }
+    let output = &options.output_dir;
+    let mut taken: HashSet<PathBuf> = items.iter().filter_map(|i| item_relative_path(i).ok()).collect();
+    for item in items.iter_mut() {
+        let Ok(path) = item_relative_path(item) else {
+            continue;
+        };
+        if !output.join(&path).exists() {
+            continue;
+        }
+        let name = unique_filename(&path.to_string_lossy().replace('\\', "/"), |n| {
+            sanitize_relative_path(n).is_some_and(|p| taken.contains(&p) || output.join(&p).exists())
+        });
+        if let Some(path) = sanitize_relative_path(&name) {
+            taken.insert(path);
+        }
+        item.name = Some(name);
+    }
+}
+
+/// Fill missing task hashes from `--checksum-file` and from sidecar checksum files in the batch or on the server.
+async fn attach_checksums(items: &mut [DownloadItem], options: &DownloadOptions) -> Result<()> {
+    if !items.iter().any(|i| i.hash.is_none()) {
+        return Ok(());
+    }
+    if let Some(source) = &options.checksum_file {
+        let sums = load_checksum_file(source, options).await?;
+        let filled = sums.apply(items);
+        eprintln!("Checksum file {}: {} entries, matched {} file(s)", source, sums.len(), filled);
+    }
+    let filled = apply_sidecars(items, options).await;
+    if filled > 0 {
+        eprintln!("Found checksum files for {} file(s)", filled);
+    }
     Ok(())
 }
-pub async fn run_single_download(
+
+/// What a batch did, printed when it ends and written to `--report` as JSON.
+#[derive(Serialize, Default, Debug)]
+struct BatchReport {
+    succeeded: usize,
+    failed: usize,
+    /// Already on disk: finished by a previous run, `--if-exists` or downloaded elsewhere
+    skipped: usize,
+    /// Size of the files downloaded by this run
+    bytes: u64,
+    elapsed_secs: f64,
+    bytes_per_sec: f64,
+    failures: Vec<FailedDownload>,
+}
+
+#[derive(Serialize, Debug)]
+struct FailedDownload {
     url: String,
-    output: PathBuf,
-    concurrency: Option<usize>,
-    rate_limit: Option<u32>,
-    split: usize,
-    verify_mode: VerifyMode,
-) -> Result<()> {
-    if !output.exists() {
-        fs::create_dir_all(&output).await.context("Failed to create output directory")?;
+    error: String,
+}
+
+impl BatchReport {
+    /// Count the outcome of one file of the batch.
+    fn record(&mut self, url: String, result: Result<Downloaded>) {
+        match result {
+            Ok(done) if done.skipped => self.skipped += 1,
+            Ok(done) => {
+                self.succeeded += 1;
+                self.bytes += done.bytes;
+            }
+            Err(e) => {
+                self.failed += 1;
+                self.failures.push(FailedDownload { url, error: format!("{:#}", e) });
+            }
+        }
     }
 
-    let items = vec![DownloadItem { url: url.clone(), hash: None }];
-    
+    fn finish(&mut self, elapsed: Duration) {
+        self.elapsed_secs = elapsed.as_secs_f64();
+        if self.elapsed_secs > 0.0 {
+            self.bytes_per_sec = self.bytes as f64 / self.elapsed_secs;
+        }
+    }
+}
+
+fn print_batch_report(report: &BatchReport) {
+    eprintln!(
+        "Batch finished: {} succeeded, {} failed, {} skipped",
+        report.succeeded, report.failed, report.skipped
+    );
+    eprintln!(
+        "Downloaded {} in {} ({}/s)",
+        HumanBytes(report.bytes),
+        HumanDuration(Duration::from_secs_f64(report.elapsed_secs)),
+        HumanBytes(report.bytes_per_sec as u64)
+    );
+    for failure in &report.failures {
+        eprintln!("  {}: {}", failure.url, failure.error);
+    }
+}
+
+pub async fn run_single_download(url: String, options: DownloadOptions) -> Result<()> {
+    let output = &options.output_dir;
+    if !output.exists() && !options.dry_run {
+        fs::create_dir_all(output).await.context("Failed to create output directory")?;
+    }
+
+    let mut items = vec![DownloadItem { url: url.clone(), ..Default::default() }];
+    apply_layout(&mut items[0], options.layout);
+
     // Pre-calculate total size
-    println!("Calculating size...");
-    let size_map = get_total_size(&items).await;
-    let expected_hashes = HashMap::new(); // Single URL download via CLI doesn't support hash verification yet
-
-    let downloader = Arc::new(Downloader::new(
-        output.clone(), 
-        rate_limit, 
-        split, 
-        1, 
-        size_map, 
-        expected_hashes, 
-        verify_mode
-    ));
+    eprintln!("Calculating size...");
+    let mut size_map = probe_items(&mut items, &options).await;
+    skip_large_files(&mut items, &mut size_map, &options);
+    if items.is_empty() {
+        return Ok(());
+    }
+    check_total_size(&items, &size_map, &options)?;
+    apply_ascii_names(&mut items, &options);
+    rename_existing(&mut items, &options);
+    check_disk_space(&items, &size_map, &options).await?;
+    // A single URL has no inline hash; it can only come from `--checksum-file` or a sidecar on the server
+    attach_checksums(&mut items, &options).await?;
+    if options.dry_run {
+        let jobs = options.db_path.as_deref().and_then(|path| JobStore::open_read_only(path).ok());
+        print_plan(&items, &size_map, &options, jobs.as_ref());
+        return Ok(());
+    }
+    let expected_hashes: HashMap<String, String> = match (&items[0].hash, &options.verify_mode) {
+        (Some(hash), VerifyMode::Auto | VerifyMode::On) => HashMap::from([(url.clone(), hash.clone())]),
+        _ => HashMap::new(),
+    };
+
+    let hooks = Hooks::new(&options);
+    let json_progress = JsonProgress::open(&options).await?;
+    let total_bytes: u64 = size_map.values().sum();
+    let downloader = Arc::new(Downloader::new(options, 1, size_map, expected_hashes));
+    let hook_events = hooks.as_ref().map(|h| tokio::spawn(h.clone().watch(downloader.subscribe(), items.clone())));
+    let progress_stream = json_progress.map(|p| tokio::spawn(p.run(downloader.subscribe(), 1, total_bytes)));
     
     // For single file, we don't need complex semaphore logic, but we keep the structure consistent
     // Concurrency here applies to splits if we were downloading multiple files, 
     // but for single file, the splits are handled inside download_file.
     // However, download_file itself spawns tasks.
     
-    if let Err(e) = downloader.download_file(items[0].clone()).await {
+    spawn_signal_handler(downloader.pause_token());
+    let result = downloader.download_file(items[0].clone()).await.map(|_| ());
+    if let Err(e) = &result {
+        if e.is::<Interrupted>() {
+            downloader.abandon_progress();
+            return result;
+        }
         eprintln!("Failed to download {}: {}", url, e);
-        return Err(e);
     }
+    if let Some(stream) = progress_stream {
+        let _ = tokio::time::timeout(Duration::from_secs(1), stream).await;
+    }
+    if let (Some(hooks), Some(watch)) = (hooks, hook_events) {
+        let _ = tokio::time::timeout(Duration::from_secs(1), watch).await;
+        let failed = usize::from(result.is_err());
+        hooks.batch_finished(1 - failed, failed);
+        hooks.finish().await;
+    }
+    result
+}
+
+/// Status of one file as reported by `--list`.
+#[derive(Serialize, Debug)]
+pub struct FileStatus {
+    pub name: String,
+    pub url: String,
+    pub status: &'static str,
+    pub progress: f64,
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+    pub bytes_per_sec: Option<u64>,
+    pub eta_secs: Option<u64>,
+    pub error: Option<String>,
+    /// When a paused file was paused
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused_at: Option<String>,
+    /// When a scheduled file is due to start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_for: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ListSummary {
+    pub completed: usize,
+    pub total: usize,
+    pub active: usize,
+    pub paused: usize,
+    pub interrupted: usize,
+    pub pending: usize,
+    pub scheduled: usize,
+    pub failed: usize,
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+    /// Current throughput and time left of the daemon's batch, when one is running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_per_sec: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ListReport {
+    pub summary: ListSummary,
+    pub files: Vec<FileStatus>,
+}
+
+/// A running download whose state file was not saved for this long is stalled
+/// (or its process is gone), so its recorded speed no longer applies.
+const STALE_STATE_SECS: u64 = 10;
+
+pub async fn collect_status(output: &Path, input: &Path, db_path: &Path, temp_dir: Option<&Path>) -> Result<ListReport> {
+    let jobs = JobStore::open(db_path)?.in_dir(output)?;
+
+    let mut summary = ListSummary::default();
+    // Comments, blank lines and section headers are no tasks
+    if let Ok(items) = read_tasks_file(input).await {
+        summary.total = items.len();
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut files = vec![];
+
+    for job in jobs {
+        let name = job
+            .path
+            .strip_prefix(output)
+            .unwrap_or(&job.path)
+            .to_string_lossy()
+            .to_string();
+
+        if job.status == JobStatus::Completed {
+            let size = job.size.unwrap_or(0);
+            summary.completed += 1;
+            summary.downloaded_bytes += size;
+            summary.total_bytes += size;
+            files.push(FileStatus {
+                name,
+                url: job.url,
+                status: "Completed",
+                progress: 100.0,
+                downloaded_bytes: size,
+                total_bytes: size,
+                bytes_per_sec: None,
+                eta_secs: None,
+                error: None,
+                paused_at: None,
+                scheduled_for: None,
+            });
+            continue;
+        }
+
+        // Unfinished jobs report progress from their part state, if any was saved
+        let state = match fs::read_to_string(state_path(&partial_base(&job.path, output, temp_dir))).await {
+            Ok(content) => DownloadState::from_json(&content).ok(),
+            Err(_) => None,
+        };
+        let (downloaded, total) = state
+            .as_ref()
+            .map_or((0, job.size.unwrap_or(0)), |s| (s.downloaded_bytes(), s.total_size));
+        let progress = if total > 0 {
+            (downloaded as f64 / total as f64) * 100.0
+        } else {
+            0.0
+        };
+        summary.downloaded_bytes += downloaded;
+        summary.total_bytes += total;
+
+        let stale = state
+            .as_ref()
+            .and_then(|s| s.saved_at)
+            .is_some_and(|t| now.saturating_sub(t) > STALE_STATE_SECS);
+        // The run that started the job is gone without recording how it ended
+        let orphaned = job.pid.is_some_and(|pid| !crate::daemon::process_alive(pid));
+        let status = match job.status {
+            JobStatus::Running | JobStatus::Paused if orphaned => {
+                summary.interrupted += 1;
+                "Interrupted"
+            }
+            JobStatus::Failed => {
+                summary.failed += 1;
+                "Failed"
+            }
+            // Held back by a process that is still waiting for the start time
+            JobStatus::Pending if job.scheduled_for.is_some() && !orphaned => {
+                summary.scheduled += 1;
+                "Scheduled"
+            }
+            JobStatus::Pending => {
+                summary.pending += 1;
+                "Pending"
+            }
+            JobStatus::Paused => {
+                summary.paused += 1;
+                "Paused"
+            }
+            _ => {
+                summary.active += 1;
+                if stale { "Stalled" } else { "Downloading" }
+            }
+        };
+        let bytes_per_sec = state
+            .as_ref()
+            .filter(|_| status == "Downloading")
+            .and_then(|s| s.bytes_per_sec)
+            .filter(|&speed| speed > 0);
+        let eta_secs = bytes_per_sec.map(|speed| total.saturating_sub(downloaded) / speed);
+
+        files.push(FileStatus {
+            name,
+            url: job.url,
+            status,
+            progress,
+            downloaded_bytes: downloaded,
+            total_bytes: total,
+            bytes_per_sec,
+            eta_secs,
+            error: job.error,
+            paused_at: job.paused_at,
+            scheduled_for: job.scheduled_for.filter(|_| status == "Scheduled"),
+        });
+    }
+
+    Ok(ListReport { summary, files })
+}
 
+pub async fn list_downloads(
+    output: PathBuf,
+    input: PathBuf,
+    db_path: Option<PathBuf>,
+    temp_dir: Option<PathBuf>,
+    format: OutputFormat,
+) -> Result<()> {
+    let Some(db_path) = db_path else {
+        bail!("No job database configured (set --db or RDL_DB)");
+    };
+    let report = collect_status(&output, &input, &db_path, temp_dir.as_deref()).await?;
+    #[cfg(unix)]
+    let report = with_daemon_throughput(report);
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Csv => print_csv(&report),
+        OutputFormat::Table => print_table(&report, &output),
+    }
     Ok(())
 }
 
-pub async fn list_downloads(output: PathBuf, input: PathBuf) -> Result<()> {
-    if !output.exists() {
-        println!("Output directory '{:?}' does not exist.", output);
-        println!("Tip: If you used a custom output directory, please specify it with --output");
+/// Add the running daemon's throughput and ETA, which only it knows, to the summary.
+#[cfg(unix)]
+fn with_daemon_throughput(mut report: ListReport) -> ListReport {
+    if crate::daemon::get_daemon_pid().ok().flatten().is_some() {
+        if let Ok(status) = crate::ipc::send_request(&IpcRequest::Status) {
+            report.summary.bytes_per_sec = status.bytes_per_sec;
+            report.summary.eta_secs = status.eta_secs;
+        }
+    }
+    report
+}
+
+fn print_table(report: &ListReport, output: &Path) {
+    let summary = &report.summary;
+    let throughput = match (summary.bytes_per_sec, summary.eta_secs) {
+        (Some(speed), Some(eta)) => format!(
+            " | Speed: {}/s | ETA: {}",
+            HumanBytes(speed),
+            HumanDuration(Duration::from_secs(eta))
+        ),
+        (Some(speed), None) => format!(" | Speed: {}/s", HumanBytes(speed)),
+        _ => String::new(),
+    };
+    println!("Summary: Files: {}/{} | Active: {} | Paused: {} | Interrupted: {} | Pending: {} | Scheduled: {} | Failed: {} | Downloaded: {} / {}{}",
+        summary.completed,
+        summary.total,
+        summary.active,
+        summary.paused,
+        summary.interrupted,
+        summary.pending,
+        summary.scheduled,
+        summary.failed,
+        HumanBytes(summary.downloaded_bytes),
+        HumanBytes(summary.total_bytes),
+        throughput
+    );
+    println!();
+
+    println!("{:<50} {:<15} {:<15} {:<15} {:<12} {:<10}", "Filename", "Status", "Progress", "Size", "Speed", "ETA");
+    println!("{:-<50} {:-<15} {:-<15} {:-<15} {:-<12} {:-<10}", "", "", "", "", "", "");
+
+    for file in &report.files {
+        let speed = file
+            .bytes_per_sec
+            .map(|s| format!("{}/s", HumanBytes(s)))
+            .unwrap_or_else(|| "-".to_string());
+        let eta = match (&file.scheduled_for, file.eta_secs) {
+            (Some(at), _) => format!("starts {}", at),
+            (None, Some(s)) => format!("{}", HumanDuration(Duration::from_secs(s))),
+            (None, None) => "-".to_string(),
+        };
+        println!("{:<50} {:<15} {:<15} {:<15} {:<12} {:<10}",
+            file.name,
+            file.status,
+            format!("{:.2}%", file.progress),
+            format!("{}", HumanBytes(file.total_bytes)),
+            speed,
+            eta
+        );
+    }
+
+    if summary.interrupted > 0 {
+        println!();
+        println!("Interrupted downloads keep their progress: run the same rdl command again (or rdl --daemon) to resume them.");
+    }
+
+    if report.files.is_empty() {
+        println!("No downloads recorded for '{:?}'.", output);
+        println!("Tip: If you used a custom download directory, please specify it with --download-dir");
+    }
+}
+
+fn print_csv(report: &ListReport) {
+    println!("name,url,status,progress,downloaded_bytes,total_bytes,bytes_per_sec,eta_secs,error");
+    for file in &report.files {
+        let optional = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_default();
+        println!(
+            "{},{},{},{:.2},{},{},{},{},{}",
+            csv_field(&file.name),
+            csv_field(&file.url),
+            file.status,
+            file.progress,
+            file.downloaded_bytes,
+            file.total_bytes,
+            optional(file.bytes_per_sec),
+            optional(file.eta_secs),
+            csv_field(file.error.as_deref().unwrap_or(""))
+        );
+    }
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Print the most recent jobs from the database, newest first.
+pub fn show_history(db_path: Option<PathBuf>, status: Option<JobStatus>, limit: usize) -> Result<()> {
+    let Some(db_path) = db_path else {
+        bail!("No job database configured (set --db or RDL_DB)");
+    };
+    let jobs = JobStore::open(&db_path)?.list(status, Some(limit))?;
+    if jobs.is_empty() {
+        println!("No jobs recorded yet.");
         return Ok(());
     }
 
-    // Calculate summary stats
-    let mut total_files_count = 0;
-    if let Ok(file) = File::open(&input) {
-        use std::io::BufRead;
-        let reader = std::io::BufReader::new(file);
-        total_files_count = reader.lines().count();
-    }
-
-    let mut downloaded_files_count = 0;
-    let mut active_files_count = 0;
-    let mut total_downloaded_bytes: u64 = 0;
-    let mut total_known_bytes: u64 = 0;
-
-    // First pass: scan for stats
-    if let Ok(mut entries) = fs::read_dir(&output).await {
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            let path = entry.path();
-            let filename = path.file_name().unwrap().to_string_lossy();
-
-            if filename.ends_with(".part.json") {
-                if let Ok(content) = fs::read_to_string(&path).await {
-                    if let Ok(state) = serde_json::from_str::<DownloadState>(&content) {
-                        active_files_count += 1;
-                        let downloaded: u64 = state.parts.iter().map(|p| p.current_byte - p.start_byte).sum();
-                        total_downloaded_bytes += downloaded;
-                        total_known_bytes += state.total_size;
-                    }
-                }
-            } else if !filename.ends_with(".part") && filename != ".DS_Store" {
-                 if let Ok(metadata) = entry.metadata().await {
-                     if metadata.is_file() {
-                         downloaded_files_count += 1;
-                         total_downloaded_bytes += metadata.len();
-                         total_known_bytes += metadata.len();
-                     }
-                 }
+    println!("{:<20} {:<10} {:<12} Path", "Updated", "Status", "Size");
+    println!("{:-<20} {:-<10} {:-<12} {:-<40}", "", "", "", "");
+    for job in jobs {
+        let status = format!("{:?}", job.status);
+        let size = job.size.map(|s| HumanBytes(s).to_string()).unwrap_or_else(|| "-".to_string());
+        println!("{:<20} {:<10} {:<12} {}", job.updated_at, status, size, job.path.display());
+        if let Some(error) = &job.error {
+            println!("{:<20} {:<10} {:<12} {}", "", "", "", error);
+        }
+    }
+    Ok(())
+}
+
+/// Print the branches and tags of `model`, marking the provider's default revision.
+pub async fn show_revisions(provider: &str, model: &str, fetch_options: &FetchOptions) -> Result<()> {
+    let provider = providers::find_provider(provider, fetch_options)?;
+    let revisions = provider.list_revisions(model, fetch_options).await?;
+    if revisions.is_empty() {
+        println!("No branches or tags found.");
+        return Ok(());
+    }
+
+    println!("{:<8} {:<40} Commit", "Kind", "Name");
+    println!("{:-<8} {:-<40} {:-<12}", "", "", "");
+    for revision in revisions {
+        let kind = match revision.kind {
+            RevisionKind::Branch => "branch",
+            RevisionKind::Tag => "tag",
+        };
+        let name = if revision.name == provider.default_revision() {
+            format!("{} (default)", revision.name)
+        } else {
+            revision.name
+        };
+        let commit = revision.commit.map(|c| c.chars().take(12).collect()).unwrap_or_else(|| "-".to_string());
+        println!("{:<8} {:<40} {}", kind, name, commit);
+    }
+    Ok(())
+}
+
+/// Print the repositories matching `query` with the command that downloads each of them.
+pub async fn search(provider: &str, query: &str, limit: usize, fetch_options: &FetchOptions) -> Result<()> {
+    let provider = providers::find_provider(provider, fetch_options)?;
+    let results = provider.search(query, limit, fetch_options).await?;
+    if results.is_empty() {
+        println!("No repositories found for {:?}.", query);
+        return Ok(());
+    }
+
+    let repo_type = match fetch_options.repo_type {
+        RepoType::Model => String::new(),
+        other => format!(" --repo-type {}", other.as_str()),
+    };
+    let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+    println!("{:<50} {:>10} {:>7} {:>10}  Command", "ID", "Downloads", "Likes", "Size");
+    println!("{:-<50} {:->10} {:->7} {:->10}  {:-<30}", "", "", "", "", "");
+    for result in results {
+        let size = result.size.map(|s| HumanBytes(s).to_string()).unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<50} {:>10} {:>7} {:>10}  rdl --fetch-list {} -P {}{}",
+            result.id,
+            optional(result.downloads),
+            optional(result.likes),
+            size,
+            result.id,
+            provider.name(),
+            repo_type
+        );
+    }
+    Ok(())
+}
+
+/// Re-hash the files of a batch in `options.output_dir` against their expected hashes.
+/// With `requeue`, mismatched files are removed and downloaded again along with missing ones.
+pub async fn verify_downloads(mut items: Vec<DownloadItem>, options: DownloadOptions, requeue: bool) -> Result<()> {
+    for item in &mut items {
+        apply_layout(item, options.layout);
+    }
+    settle_names(&mut items, BTreeMap::new(), &options);
+    apply_ascii_names(&mut items, &options);
+    attach_checksums(&mut items, &options).await?;
+    let output = options.output_dir.clone();
+    let (mut ok, mut mismatched, mut missing, mut unchecked) = (0, 0, 0, 0);
+    let mut bad = vec![];
+
+    for item in items {
+        let relative = item_relative_path(&item)?;
+        let path = output.join(&relative);
+        let name = relative.to_string_lossy().to_string();
+        if !path.exists() {
+            println!("MISSING   {}", name);
+            missing += 1;
+            bad.push(item);
+            continue;
+        }
+        let Some(raw) = item.hash.as_deref() else {
+            println!("NO HASH   {}", name);
+            unchecked += 1;
+            continue;
+        };
+
+        let (algo, expected) = parse_expected_hash(raw, options.hash_algo);
+        let actual = calculate_hash(&path, algo)
+            .await
+            .context(format!("Failed to hash {:?}", path))?;
+        if actual.eq_ignore_ascii_case(&expected) {
+            println!("OK        {}", name);
+            ok += 1;
+        } else {
+            println!("MISMATCH  {} ({} expected {}, got {})", name, algo, expected, actual);
+            mismatched += 1;
+            if requeue {
+                fs::remove_file(&path).await.context(format!("Failed to remove {:?}", path))?;
             }
+            bad.push(item);
         }
     }
 
-    println!("Summary: Files: {}/{} | Active: {} | Downloaded: {} / {}",
-        downloaded_files_count,
-        total_files_count,
-        active_files_count,
-        HumanBytes(total_downloaded_bytes),
-        HumanBytes(total_known_bytes)
+    println!(
+        "Verified {} file(s): {} OK, {} mismatched, {} missing, {} without hash",
+        ok + mismatched + missing + unchecked,
+        ok,
+        mismatched,
+        missing,
+        unchecked
     );
-    println!();
+    if bad.is_empty() {
+        return Ok(());
+    }
+    if !requeue {
+        bail!("{} file(s) failed verification (use --requeue to download them again)", bad.len());
+    }
+    println!("Re-downloading {} file(s)...", bad.len());
+    run_items(bad, options, false).await
+}
 
-    println!("{:<50} {:<15} {:<15} {:<15}", "Filename", "Status", "Progress", "Size");
-    println!("{:-<50} {:-<15} {:-<15} {:-<15}", "", "", "", "");
-
-    let mut found_any = false;
-
-    if let Ok(mut entries) = fs::read_dir(&output).await {
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            let path = entry.path();
-            if let Some(ext) = path.extension() {
-                if ext == "json" {
-                    if let Some(stem) = path.file_stem() {
-                        if let Some(stem_str) = stem.to_str() {
-                            if stem_str.ends_with(".part") {
-                                if let Ok(content) = fs::read_to_string(&path).await {
-                                    if let Ok(state) = serde_json::from_str::<DownloadState>(&content) {
-                                        let filename = stem_str.trim_end_matches(".part");
-                                        let downloaded: u64 = state.parts.iter().map(|p| p.current_byte - p.start_byte).sum();
-                                        let total = state.total_size;
-                                        let progress = if total > 0 {
-                                            (downloaded as f64 / total as f64) * 100.0
-                                        } else {
-                                            0.0
-                                        };
-                                        
-                                        println!("{:<50} {:<15} {:<15} {:<15}",
-                                            filename,
-                                            "Downloading",
-                                            format!("{:.2}%", progress),
-                                            format!("{}", HumanBytes(total))
-                                        );
-                                        found_any = true;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+/// Download every failed job again, each into the directory it originally targeted.
+pub async fn retry_failed(options: DownloadOptions, daemon: bool) -> Result<()> {
+    let Some(db_path) = options.db_path.clone() else {
+        bail!("No job database configured (set --db or RDL_DB)");
+    };
+    let failed = JobStore::open(&db_path)?.list(Some(JobStatus::Failed), None)?;
+    if failed.is_empty() {
+        println!("No failed jobs to retry.");
+        return Ok(());
+    }
+
+    let mut by_dir: BTreeMap<PathBuf, Vec<DownloadItem>> = BTreeMap::new();
+    for job in failed {
+        if let Some(item) = parse_task_line(&job.task) {
+            by_dir.entry(job.output_dir).or_default().push(item);
+        }
+    }
+    // A directory with failures again should not keep the others from being retried
+    let mut failed_dirs = 0;
+    for (output_dir, items) in by_dir {
+        eprintln!("Retrying {} failed job(s) in {:?}", items.len(), output_dir);
+        let options = DownloadOptions { output_dir, ..options.clone() };
+        match run_items(items, options, daemon).await {
+            Err(e) if e.is::<Interrupted>() => return Err(e),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                failed_dirs += 1;
             }
+            Ok(()) => {}
         }
     }
-    
-    if let Ok(mut entries) = fs::read_dir(&output).await {
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            let path = entry.path();
-            if path.is_file() {
-                let filename = path.file_name().unwrap().to_string_lossy();
-                if !filename.ends_with(".part") && !filename.ends_with(".part.json") && filename != ".DS_Store" {
-                    if let Ok(metadata) = entry.metadata().await {
-                        println!("{:<50} {:<15} {:<15} {:<15}",
-                            filename,
-                            "Completed",
-                            "100.00%",
-                            format!("{}", HumanBytes(metadata.len()))
-                        );
-                        found_any = true;
-                    }
-                }
+    if failed_dirs > 0 {
+        bail!("Retrying failed in {} download dir(s)", failed_dirs);
+    }
+    Ok(())
+}
+
+/// Whether `target` of `rdl cancel` / `rdl remove` names a task: its URL, its
+/// path below the download dir, or the trailing part of that path (e.g. the file name).
+fn names_task(url: &str, relative_path: &Path, target: &str) -> bool {
+    url == target || relative_path.ends_with(target)
+}
+
+/// `rdl remove`: drop a task from the tasks file and the job database and delete
+/// its partial data, so no later run picks it up again. A running daemon is asked
+/// to cancel it first. Finished files are left alone.
+pub async fn remove_task(
+    target: &str,
+    tasks_file: &Path,
+    output_dir: &Path,
+    db_path: Option<PathBuf>,
+    temp_dir: Option<&Path>,
+    layout: Layout,
+) -> Result<()> {
+    #[cfg(unix)]
+    if crate::daemon::get_daemon_pid()?.is_some() {
+        // Not downloading right now is fine; the rest still applies
+        if let Ok(response) = crate::ipc::send_request(&IpcRequest::CancelTask { url: target.to_string(), purge: true }) {
+            if response.ok {
+                println!("{}", response.message);
+            }
+        }
+    }
+
+    let mut paths = BTreeSet::new();
+    let mut removed_lines = 0;
+    if let Ok(content) = fs::read_to_string(tasks_file).await {
+        // Parsed as a whole, so tasks get the `dir=` of their section
+        let tasks = parse_rdl_lines(&content).with_context(|| format!("Failed to parse {:?}", tasks_file))?;
+        let mut removed = HashSet::new();
+        for (index, mut item) in tasks {
+            apply_layout(&mut item, layout);
+            let Ok(relative) = item_relative_path(&item) else {
+                continue;
+            };
+            if names_task(&item.url, &relative, target) {
+                paths.insert(output_dir.join(relative));
+                removed.insert(index);
+            }
+        }
+        removed_lines = removed.len();
+        let mut kept = String::with_capacity(content.len());
+        for (index, line) in content.lines().enumerate() {
+            if !removed.contains(&index) {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+        if removed_lines > 0 {
+            fs::write(tasks_file, kept).await.context(format!("Failed to update {:?}", tasks_file))?;
+        }
+    }
+
+    let mut removed_jobs = 0;
+    if let Some(db_path) = db_path {
+        let jobs = JobStore::open(&db_path)?;
+        for job in jobs.in_dir(output_dir)? {
+            let relative = job.path.strip_prefix(output_dir).unwrap_or(&job.path);
+            if names_task(&job.url, relative, target) {
+                jobs.remove(job.id)?;
+                paths.insert(job.path);
+                removed_jobs += 1;
             }
         }
     }
 
-    if !found_any {
-        println!("No active or completed downloads found in '{:?}'.", output);
+    if removed_lines == 0 && removed_jobs == 0 {
+        bail!("No task matches {}", target);
+    }
+    let mut removed_parts = 0;
+    for path in &paths {
+        let base = partial_base(path, output_dir, temp_dir);
+        for partial in [part_path(&base), state_path(&base)] {
+            if fs::remove_file(&partial).await.is_ok() {
+                removed_parts += 1;
+            }
+        }
     }
+    println!(
+        "Removed {}: {} task line(s), {} job(s), {} partial file(s)",
+        target, removed_lines, removed_jobs, removed_parts
+    );
+    Ok(())
+}
 
+/// Delete the `.part` / `.part.json` files below the download dir (and `--temp-dir`)
+/// that no unfinished task needs: their file is complete, or neither the tasks file
+/// nor the job database knows it. With `abandoned` the partial data of unfinished
+/// tasks goes too. Files of downloads still running in a live process are never touched.
+#[allow(clippy::too_many_arguments)]
+pub async fn clean_partials(
+    tasks_file: &Path,
+    input_format: InputFormat,
+    output_dir: &Path,
+    db_path: Option<PathBuf>,
+    temp_dir: Option<&Path>,
+    layout: Layout,
+    older_than: Option<Duration>,
+    abandoned: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let mut unfinished = HashSet::new();
+    let mut running = HashSet::new();
+    // Downloaded files that merely end in `.part` are not partial data
+    let mut known = HashSet::new();
+    if let Ok(items) = read_tasks_file_as(tasks_file, input_format).await {
+        for mut item in items {
+            apply_layout(&mut item, layout);
+            if let Ok(relative) = item_relative_path(&item) {
+                unfinished.insert(output_dir.join(relative));
+            }
+        }
+    }
+    if let Some(db_path) = db_path {
+        for job in JobStore::open(&db_path)?.in_dir(output_dir)? {
+            known.insert(job.path.clone());
+            let active = matches!(job.status, JobStatus::Running | JobStatus::Paused);
+            if active && job.pid.is_some_and(crate::daemon::process_alive) {
+                running.insert(job.path);
+            } else if job.status != JobStatus::Completed {
+                unfinished.insert(job.path);
+            }
+        }
+    }
+
+    let mut partials = vec![];
+    find_partials(output_dir, &mut partials);
+    if let Some(temp_dir) = temp_dir {
+        find_partials(temp_dir, &mut partials);
+    }
+    partials.sort();
+    partials.dedup();
+
+    let (mut removed, mut freed, mut kept) = (0, 0, 0);
+    known.extend(unfinished.iter().cloned());
+    for (partial, base) in partials {
+        if known.contains(&partial) {
+            continue;
+        }
+        // Partial files in --temp-dir mirror the layout of the download dir
+        let filepath = match temp_dir.and_then(|t| base.strip_prefix(t).ok()) {
+            Some(relative) => output_dir.join(relative),
+            None => base,
+        };
+        if running.contains(&filepath) {
+            continue;
+        }
+        let Ok(metadata) = std::fs::metadata(&partial) else {
+            continue;
+        };
+        let age = metadata.modified().ok().and_then(|m| m.elapsed().ok()).unwrap_or_default();
+        if older_than.is_some_and(|limit| age < limit) {
+            continue;
+        }
+        if unfinished.contains(&filepath) && !filepath.exists() && !abandoned {
+            kept += 1;
+            continue;
+        }
+
+        if dry_run {
+            println!("Would delete {} ({})", partial.display(), HumanBytes(metadata.len()));
+        } else {
+            if let Err(e) = fs::remove_file(&partial).await {
+                eprintln!("Failed to delete {}: {}", partial.display(), e);
+                continue;
+            }
+            println!("Deleted {} ({})", partial.display(), HumanBytes(metadata.len()));
+        }
+        removed += 1;
+        freed += metadata.len();
+    }
+
+    println!(
+        "{} {} partial file(s), {} freed",
+        if dry_run { "Would remove" } else { "Removed" },
+        removed,
+        HumanBytes(freed)
+    );
+    if kept > 0 {
+        println!("Kept {} partial file(s) of unfinished tasks (use --abandoned to delete them too)", kept);
+    }
     Ok(())
 }
 
-pub async fn follow_log(output: PathBuf, input: PathBuf) -> Result<()> {
-    loop {
-        print!("\x1B[1;1H\x1B[0J");
-        list_downloads(output.clone(), input.clone()).await?;
-        println!("\n(Press Ctrl+C to exit view)");
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+/// Collect every `.part`, `.part.json` and leftover `.part.json.tmp` below `dir`,
+/// each with the path of the file it belongs to.
+fn find_partials(dir: &Path, found: &mut Vec<(PathBuf, PathBuf)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            find_partials(&path, found);
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let base = [".part", ".part.json", ".part.json.tmp"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix).filter(|b| !b.is_empty()));
+        if let Some(base) = base {
+            found.push((path.clone(), path.with_file_name(base)));
+        }
+    }
+}
+
+/// Where `--fetch-list` keeps the list and files of a repository by default:
+/// `downloads/<provider>/[datasets|spaces/]<model>`.
+pub fn default_list_dir(provider: &str, repo_type: RepoType, model: &str) -> PathBuf {
+    // Aliases share the directory of their provider, as `--layout provider-tree` does
+    let provider = providers::ProviderRegistry::default()
+        .get(provider)
+        .map_or_else(|| provider.to_lowercase(), |p| p.name().to_string());
+    let mut dir = PathBuf::from("downloads").join(provider);
+    if repo_type != RepoType::Model {
+        dir.push(repo_type.plural());
     }
+    dir.join(model)
 }
 
 pub async fn generate_download_list(
     model: &str,
     output_path: PathBuf,
     use_default_input_path: bool,
-    revision: String,
-    provider: String
+    revision: Option<String>,
+    provider: String,
+    fetch_options: FetchOptions,
+    select: bool,
 ) -> Result<PathBuf> {
-    let items = providers::fetch_urls(&provider, model, &revision).await?;
+    let mut items = providers::fetch_urls(&provider, model, revision.as_deref(), &fetch_options).await?;
     if items.is_empty() {
         bail!("文件列表为空");
     }
 
+    if select {
+        println!("正在获取 {} 个文件的大小...", items.len());
+        let probe = DownloadOptions {
+            token: fetch_options.token.clone(),
+            network: fetch_options.network.clone(),
+            ..DownloadOptions::new(".")
+        };
+        let sizes = probe_items(&mut items, &probe).await;
+        items = match crate::select::select_items(items, &sizes)? {
+            Some(chosen) if !chosen.is_empty() => chosen,
+            Some(_) => bail!("未选择任何文件"),
+            None => bail!("已取消选择"),
+        };
+    }
+
     // If user didn't override --input (still using default download.txt),
-    // place the generated list under providers/<provider>/<model>/download.txt.
+    // place the generated list under providers/<provider>/[datasets|spaces/]<model>/download.txt.
     let final_output = if use_default_input_path {
-        PathBuf::from("downloads")
-            .join(provider.to_lowercase())
-            .join(model)
-            .join("download.txt")
+        default_list_dir(&provider, fetch_options.repo_type, model).join("download.txt")
     } else {
         output_path
     };
@@ -331,14 +1743,7 @@ pub async fn generate_download_list(
         }
     }
 
-    let mut content_lines = Vec::with_capacity(items.len());
-    for item in &items {
-        let line = match &item.hash {
-            Some(hash) => format!("{}|{}", item.url, hash),
-            None => item.url.clone(),
-        };
-        content_lines.push(line);
-    }
+    let content_lines: Vec<String> = items.iter().map(format_task_line).collect();
     let content = content_lines.join("\n") + "\n";
     fs::write(&final_output, content)
         .await
@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use rdl_core::hashing::VerifyMode;
+
+/// User defaults loaded from `~/.config/rdl/config.toml`.
+/// Every field is optional; values given on the command line (or via env) take precedence.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub download_dir: Option<PathBuf>,
+    pub concurrency: Option<usize>,
+    pub split: Option<usize>,
+    pub rate_limit: Option<u32>,
+    pub provider: Option<String>,
+    pub token: Option<String>,
+    pub proxy: Option<String>,
+    pub verify_hash: Option<VerifyMode>,
+}
+
+/// Default config location: `$XDG_CONFIG_HOME/rdl/config.toml`, falling back to `~/.config/rdl/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("rdl").join("config.toml"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("rdl").join("config.toml"))
+}
+
+impl Config {
+    /// Load the config from `path` (or the default location). A missing default file is not an error.
+    pub fn load(path: Option<&Path>) -> Result<(Self, Option<PathBuf>)> {
+        let explicit = path.is_some();
+        let Some(path) = path.map(Path::to_path_buf).or_else(default_config_path) else {
+            return Ok((Self::default(), None));
+        };
+        if !path.exists() {
+            if explicit {
+                anyhow::bail!("Config file not found: {:?}", path);
+            }
+            return Ok((Self::default(), Some(path)));
+        }
+        let content = std::fs::read_to_string(&path).context(format!("Failed to read config file: {:?}", path))?;
+        let config: Config = toml::from_str(&content).context(format!("Failed to parse config file: {:?}", path))?;
+        Ok((config, Some(path)))
+    }
+}
+
+/// Print the effective settings (token masked) and which config file was used.
+pub fn show_config(effective: &Config, path: Option<&Path>) -> Result<()> {
+    match path {
+        Some(p) if p.exists() => println!("# Config file: {}", p.display()),
+        Some(p) => println!("# Config file: {} (not found, using defaults)", p.display()),
+        None => println!("# Config file: (none)"),
+    }
+    let mut shown = effective.clone();
+    if shown.token.is_some() {
+        shown.token = Some("********".to_string());
+    }
+    print!("{}", toml::to_string_pretty(&shown)?);
+    Ok(())
+}
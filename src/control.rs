@@ -0,0 +1,187 @@
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Exit code used when downloads stop because of SIGTERM/Ctrl+C (128 + SIGINT).
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RunState {
+    Running,
+    Paused,
+    /// Like a pause that is never resumed
+    ShuttingDown,
+}
+
+/// Cooperative pause flag shared by all download loops.
+///
+/// Unlike SIGSTOP, pausing makes the loops stop reading, persist their part
+/// state and drop their connections; resuming restarts them from that state.
+/// Shutting down stops the loops the same way, after which they return
+/// [`Interrupted`] instead of waiting for a resume.
+///
+/// A [`child`](Self::child) token controls a single task: it is paused while
+/// either itself or its parent is paused.
+#[derive(Clone)]
+pub struct PauseToken {
+    tx: Arc<watch::Sender<RunState>>,
+    parent: Option<Arc<watch::Sender<RunState>>>,
+}
+
+impl PauseToken {
+    pub fn new() -> Self {
+        let (tx, _rx) = watch::channel(RunState::Running);
+        Self { tx: Arc::new(tx), parent: None }
+    }
+
+    /// Token for one task that also follows the state of `self`.
+    pub fn child(&self) -> Self {
+        let (tx, _rx) = watch::channel(RunState::Running);
+        Self { tx: Arc::new(tx), parent: Some(self.tx.clone()) }
+    }
+
+    pub fn pause(&self) {
+        self.tx.send_if_modified(|state| {
+            let changed = *state == RunState::Running;
+            if changed {
+                *state = RunState::Paused;
+            }
+            changed
+        });
+    }
+
+    pub fn resume(&self) {
+        self.tx.send_if_modified(|state| {
+            let changed = *state == RunState::Paused;
+            if changed {
+                *state = RunState::Running;
+            }
+            changed
+        });
+    }
+
+    pub fn shutdown(&self) {
+        self.tx.send_replace(RunState::ShuttingDown);
+    }
+
+    fn state(&self) -> RunState {
+        let own = *self.tx.borrow();
+        let parent = self.parent.as_ref().map_or(RunState::Running, |p| *p.borrow());
+        combine(own, parent)
+    }
+
+    /// True while paused or shutting down: download loops must stop reading.
+    pub fn is_paused(&self) -> bool {
+        self.state() != RunState::Running
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.state() == RunState::ShuttingDown
+    }
+
+    /// True if this token itself (not its parent) is paused.
+    pub fn is_paused_locally(&self) -> bool {
+        *self.tx.borrow() == RunState::Paused
+    }
+
+    /// Wait until the token is no longer paused (returns immediately if it isn't).
+    /// Returns [`Interrupted`] if the process is shutting down instead.
+    pub async fn wait_resumed(&self) -> Result<(), Interrupted> {
+        let mut rx = self.tx.subscribe();
+        let mut parent_rx = self.parent.as_ref().map(|p| p.subscribe());
+        loop {
+            let own = *rx.borrow_and_update();
+            let parent = parent_rx.as_mut().map_or(RunState::Running, |p| *p.borrow_and_update());
+            match combine(own, parent) {
+                RunState::Running => return Ok(()),
+                RunState::ShuttingDown => return Err(Interrupted),
+                RunState::Paused => {}
+            }
+            let closed = match parent_rx.as_mut() {
+                Some(parent_rx) => tokio::select! {
+                    r = rx.changed() => r.is_err(),
+                    r = parent_rx.changed() => r.is_err(),
+                },
+                None => rx.changed().await.is_err(),
+            };
+            if closed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn combine(a: RunState, b: RunState) -> RunState {
+    if a == RunState::ShuttingDown || b == RunState::ShuttingDown {
+        RunState::ShuttingDown
+    } else if a == RunState::Paused || b == RunState::Paused {
+        RunState::Paused
+    } else {
+        RunState::Running
+    }
+}
+
+impl Default for PauseToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Error returned by download loops that stopped because of a pause request.
+#[derive(Debug)]
+pub struct Paused;
+
+impl fmt::Display for Paused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "download paused")
+    }
+}
+
+impl std::error::Error for Paused {}
+
+/// Error returned once downloads stopped because of SIGTERM/Ctrl+C.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "download interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Shut `token` down on the first SIGTERM/Ctrl+C so every loop saves its part
+/// state and returns; a second signal exits immediately. Abort the returned task
+/// once the batch ends, so a process running several batches keeps a single handler.
+pub fn spawn_signal_handler(token: PauseToken) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        eprintln!("Interrupted, saving progress... (press Ctrl+C again to exit immediately)");
+        token.shutdown();
+        wait_for_signal().await;
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    })
+}
+
+/// Wait for SIGTERM or Ctrl+C.
+#[cfg(unix)]
+pub async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+        }
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -7,16 +7,34 @@ use std::fs::File;
 #[cfg(unix)]
 use nix::sys::signal::{self, Signal};
 #[cfg(unix)]
-use nix::unistd::Pid;
+use nix::unistd::{dup2, Pid};
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use crate::ipc::IpcRequest;
+#[cfg(unix)]
+use crate::session::{log_file, pid_file};
 
+/// When the daemon starts a new `rdl.log`; old logs are kept as `rdl.log.1` (newest) .. `rdl.log.<keep>`.
 #[cfg(unix)]
-pub fn start_daemon() -> Result<()> {
+#[derive(Debug, Clone)]
+pub struct LogRotation {
+    pub max_size: u64,
+    pub max_age: Option<Duration>,
+    pub keep: usize,
+}
+
+#[cfg(unix)]
+pub fn start_daemon(rotation: LogRotation) -> Result<()> {
+    ensure_not_running()?;
     // Redirect both stdout and stderr to the same log file for easier following
-    let stdout = File::create("rdl.log").context("Failed to create log file")?;
+    let stdout = File::create(log_file()).context("Failed to create log file")?;
     let stderr = stdout.try_clone().context("Failed to clone log file handle")?;
 
     let daemonize = Daemonize::new()
-        .pid_file("rdl.pid")
+        .pid_file(pid_file())
         .chown_pid_file(true)
         .working_directory(".")
         .stdout(stdout)
@@ -25,16 +43,83 @@ pub fn start_daemon() -> Result<()> {
     match daemonize.start() {
         Ok(_) => {
             println!("Success, daemonized");
+            spawn_log_rotation(rotation);
             Ok(())
         },
         Err(e) => Err(anyhow::anyhow!("Error starting daemon: {}", e)),
     }
 }
 
+/// Check the log once in a while and rotate it when it is too large or too old.
+/// Runs on a plain thread: the tokio runtime only starts after daemonizing.
+#[cfg(unix)]
+fn spawn_log_rotation(rotation: LogRotation) {
+    std::thread::spawn(move || {
+        let mut opened = Instant::now();
+        loop {
+            std::thread::sleep(LOG_CHECK_INTERVAL);
+            let size = std::fs::metadata(log_file()).map(|m| m.len()).unwrap_or(0);
+            let expired = rotation.max_age.is_some_and(|age| opened.elapsed() >= age);
+            if size < rotation.max_size && !expired {
+                continue;
+            }
+            match rotate_log(rotation.keep) {
+                Ok(()) => opened = Instant::now(),
+                Err(e) => eprintln!("Failed to rotate {}: {:#}", log_file().display(), e),
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+const LOG_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Shift `rdl.log.N` to `rdl.log.N+1` (dropping the oldest), move the current log
+/// to `rdl.log.1` and point stdout/stderr at a fresh `rdl.log`.
+#[cfg(unix)]
+fn rotate_log(keep: usize) -> Result<()> {
+    let log_file = log_file();
+    let rotated = |n: usize| format!("{}.{}", log_file.display(), n);
+    if keep == 0 {
+        let _ = std::fs::remove_file(&log_file);
+    } else {
+        let _ = std::fs::remove_file(rotated(keep));
+        for n in (1..keep).rev() {
+            let _ = std::fs::rename(rotated(n), rotated(n + 1));
+        }
+        std::fs::rename(&log_file, rotated(1)).context("Failed to move log file")?;
+    }
+    let log = File::create(&log_file).context("Failed to create log file")?;
+    dup2(log.as_raw_fd(), std::io::stdout().as_raw_fd()).context("Failed to redirect stdout")?;
+    dup2(log.as_raw_fd(), std::io::stderr().as_raw_fd()).context("Failed to redirect stderr")?;
+    Ok(())
+}
+
+/// Two daemons would fight over the pid file, control socket and log.
+#[cfg(unix)]
+fn ensure_not_running() -> Result<()> {
+    if let Some(pid) = get_daemon_pid()?.filter(|pid| process_alive(*pid as u32)) {
+        anyhow::bail!(
+            "A daemon is already running in this session (PID: {}); start another one with --session <name>",
+            pid
+        );
+    }
+    Ok(())
+}
+
+/// Register this foreground process as the session's daemon, e.g. when systemd runs it.
+#[cfg(unix)]
+pub fn write_pid_file() -> Result<()> {
+    ensure_not_running()?;
+    let pid_file = pid_file();
+    std::fs::write(&pid_file, format!("{}\n", std::process::id()))
+        .with_context(|| format!("Failed to write {:?}", pid_file))
+}
+
 #[cfg(unix)]
 pub fn get_daemon_pid() -> Result<Option<i32>> {
-    let pid_file = "rdl.pid";
-    if !std::path::Path::new(pid_file).exists() {
+    let pid_file = pid_file();
+    if !pid_file.exists() {
         return Ok(None);
     }
     let content = std::fs::read_to_string(pid_file)?;
@@ -42,6 +127,18 @@ pub fn get_daemon_pid() -> Result<Option<i32>> {
     Ok(Some(pid))
 }
 
+/// Whether the process `pid` still exists, e.g. the run a download was recorded by.
+#[cfg(unix)]
+pub fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks for existence; EPERM means it exists as another user
+    !matches!(signal::kill(Pid::from_raw(pid as i32), None), Err(nix::errno::Errno::ESRCH))
+}
+
+#[cfg(not(unix))]
+pub fn process_alive(_pid: u32) -> bool {
+    true
+}
+
 #[cfg(unix)]
 pub fn send_signal(pid: i32, signal: Signal) -> Result<()> {
     signal::kill(Pid::from_raw(pid), signal)?;
@@ -53,7 +150,7 @@ pub fn stop_daemon() -> Result<()> {
     if let Some(pid) = get_daemon_pid()? {
         send_signal(pid, Signal::SIGTERM)?;
         println!("Stopped daemon (PID: {})", pid);
-        let _ = std::fs::remove_file("rdl.pid");
+        let _ = std::fs::remove_file(pid_file());
     } else {
         println!("Daemon is not running.");
     }
@@ -62,20 +159,31 @@ pub fn stop_daemon() -> Result<()> {
 
 #[cfg(unix)]
 pub fn pause_daemon() -> Result<()> {
-    if let Some(pid) = get_daemon_pid()? {
-        send_signal(pid, Signal::SIGSTOP)?;
-        println!("Paused daemon (PID: {})", pid);
-    } else {
-        println!("Daemon is not running.");
-    }
-    Ok(())
+    send_control(IpcRequest::Pause)
 }
 
 #[cfg(unix)]
 pub fn resume_daemon() -> Result<()> {
+    send_control(IpcRequest::Resume)
+}
+
+/// Stop one file of the running batch, by URL, path or file name.
+#[cfg(unix)]
+pub fn cancel_task(target: &str, purge: bool) -> Result<()> {
+    send_control(IpcRequest::CancelTask { url: target.to_string(), purge })
+}
+
+/// Pause/resume are cooperative: the daemon persists part state and closes its
+/// connections instead of being frozen with SIGSTOP.
+#[cfg(unix)]
+fn send_control(request: IpcRequest) -> Result<()> {
     if let Some(pid) = get_daemon_pid()? {
-        send_signal(pid, Signal::SIGCONT)?;
-        println!("Resumed daemon (PID: {})", pid);
+        let response = crate::ipc::send_request(&request)?;
+        if response.ok {
+            println!("{} (PID: {})", response.message, pid);
+        } else {
+            println!("Daemon rejected request: {}", response.message);
+        }
     } else {
         println!("Daemon is not running.");
     }
@@ -84,5 +192,5 @@ pub fn resume_daemon() -> Result<()> {
 
 #[cfg(unix)]
 pub fn cleanup_pid_file() {
-    let _ = std::fs::remove_file("rdl.pid");
+    let _ = std::fs::remove_file(pid_file());
 }
\ No newline at end of file
@@ -0,0 +1,321 @@
+//! Delta downloads (`--delta`): when a file is downloaded again over an older
+//! copy, blocks the two versions share are copied from the old file and only
+//! the changed ranges are requested.
+//!
+//! The blocks of the new version are described either by the piece hashes of a
+//! metalink (compared at the same offset, so only changes in place are found) or
+//! by a zsync control file published next to the file (`<url>.zsync`), whose
+//! rolling checksums also find blocks that moved. The reused blocks go straight
+//! into the `.part` file and the missing ranges become the parts of a fresh
+//! download state, so the segmented download fetches just those.
+
+use anyhow::{anyhow, bail, Context, Result};
+use md4::{Digest, Md4};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::downloader::write_all_at;
+use crate::hashing::{hash_bytes, HashAlgorithm};
+use crate::state::PartState;
+use crate::utils::NetworkOptions;
+
+/// Bytes read from the old file at a time while scanning it
+const READ_SIZE: usize = 4 * 1024 * 1024;
+
+/// Largest piece or block accepted, as each one is held in memory whole
+pub const MAX_BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Piece hashes of a metalink `<pieces>` element.
+#[derive(Clone, Debug)]
+pub struct PieceHashes {
+    pub algo: HashAlgorithm,
+    /// Size of every piece but the last
+    pub length: u64,
+    pub hashes: Vec<String>,
+}
+
+/// Block checksums of a zsync control file.
+#[derive(Clone, Debug)]
+pub struct ZsyncIndex {
+    pub block_size: usize,
+    pub length: u64,
+    rsum_bytes: usize,
+    checksum_bytes: usize,
+    /// Rolling checksum (masked to `rsum_bytes`) and truncated MD4 of every block
+    blocks: Vec<(u32, Vec<u8>)>,
+}
+
+/// How the blocks of the new version are described.
+pub enum BlockSums {
+    Pieces(PieceHashes),
+    Zsync(ZsyncIndex),
+}
+
+impl BlockSums {
+    /// Whether the blocks describe a file of `total_size` bytes (a stale control file does not).
+    pub fn covers(&self, total_size: u64) -> bool {
+        match self {
+            BlockSums::Pieces(pieces) => {
+                pieces.length > 0 && pieces.hashes.len() as u64 == total_size.div_ceil(pieces.length)
+            }
+            BlockSums::Zsync(index) => index.length == total_size,
+        }
+    }
+}
+
+/// `model.gguf` -> `model.gguf.old`: the previous version kept while the new one is assembled.
+pub fn seed_path(filepath: &Path) -> PathBuf {
+    let mut name = filepath.as_os_str().to_os_string();
+    name.push(".old");
+    PathBuf::from(name)
+}
+
+/// Fetch and parse `<url>.zsync`; `None` when there is none.
+pub async fn fetch_zsync(
+    client: &reqwest::Client,
+    network: &NetworkOptions,
+    url: &str,
+    headers: &reqwest::header::HeaderMap,
+) -> Result<Option<ZsyncIndex>> {
+    // Signed after `.zsync` is added, or the signature would cover the file itself
+    let control_url = zsync_url(url);
+    let request = crate::object_store::request_url(&control_url, "GET")?;
+    let response = network.send(client, client.get(request.as_ref()).headers(headers.clone())).await?;
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    let bytes = response.bytes().await?;
+    parse_zsync(&bytes).map(Some).with_context(|| format!("Invalid zsync file {}", control_url))
+}
+
+/// `<url>.zsync`: the suffix goes on the path, before any query or fragment of an HTTP URL.
+fn zsync_url(url: &str) -> String {
+    if crate::object_store::is_object_url(url) {
+        return format!("{}.zsync", url);
+    }
+    let url = url.split('#').next().unwrap_or(url);
+    match url.split_once('?') {
+        Some((path, query)) => format!("{}.zsync?{}", path, query),
+        None => format!("{}.zsync", url),
+    }
+}
+
+pub fn parse_zsync(data: &[u8]) -> Result<ZsyncIndex> {
+    let header_end = data
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .ok_or_else(|| anyhow!("no end of header"))?;
+    let header = std::str::from_utf8(&data[..header_end])?;
+    let field = |name: &str| {
+        header
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+            .ok_or_else(|| anyhow!("no {} header", name))
+    };
+    let block_size: usize = field("Blocksize")?.parse()?;
+    let length: u64 = field("Length")?.parse()?;
+    let hash_lengths: Vec<usize> = field("Hash-Lengths")?
+        .split(',')
+        .map(|n| n.trim().parse())
+        .collect::<Result<_, _>>()?;
+    let [_, rsum_bytes, checksum_bytes] = hash_lengths[..] else {
+        bail!("invalid Hash-Lengths");
+    };
+    if block_size == 0 || !(1..=4).contains(&rsum_bytes) || !(1..=16).contains(&checksum_bytes) {
+        bail!("unsupported block parameters");
+    }
+    if block_size as u64 > MAX_BLOCK_SIZE {
+        bail!("block size {} is over the {} byte limit", block_size, MAX_BLOCK_SIZE);
+    }
+
+    let count = usize::try_from(length.div_ceil(block_size as u64))?;
+    let entry = rsum_bytes + checksum_bytes;
+    let body = &data[header_end + 2..];
+    if count.checked_mul(entry).is_none_or(|needed| body.len() < needed) {
+        bail!("truncated block checksums");
+    }
+    let blocks = body
+        .chunks_exact(entry)
+        .take(count)
+        .map(|chunk| {
+            let rsum = chunk[..rsum_bytes].iter().fold(0u32, |acc, b| (acc << 8) | *b as u32);
+            (rsum, chunk[rsum_bytes..].to_vec())
+        })
+        .collect();
+    Ok(ZsyncIndex { block_size, length, rsum_bytes, checksum_bytes, blocks })
+}
+
+/// Copy the blocks of `seed` the new file shares into `part` (already sized to
+/// `total_size`) and return the byte ranges still to download, inclusive and in order.
+pub fn reuse_blocks(seed: &Path, part: &File, sums: &BlockSums, total_size: u64) -> Result<Vec<(u64, u64)>> {
+    let (block_size, known) = match sums {
+        BlockSums::Pieces(pieces) => (pieces.length, reuse_pieces(seed, part, pieces, total_size)?),
+        BlockSums::Zsync(index) => (index.block_size as u64, reuse_zsync(seed, part, index, total_size)?),
+    };
+    let mut missing: Vec<(u64, u64)> = vec![];
+    for (block, _) in known.iter().enumerate().filter(|(_, known)| !**known) {
+        let start = block as u64 * block_size;
+        let end = (start + block_size).min(total_size) - 1;
+        match missing.last_mut() {
+            Some(last) if last.1 + 1 == start => last.1 = end,
+            _ => missing.push((start, end)),
+        }
+    }
+    Ok(missing)
+}
+
+/// Part states for a download of only the `missing` ranges: at most `max_parts`
+/// ranges are downloaded (the ones separated by the smallest reused gaps are
+/// joined) and everything in between is recorded as already completed.
+pub fn plan_parts(missing: &[(u64, u64)], total_size: u64, max_parts: usize) -> Vec<PartState> {
+    let mut ranges = missing.to_vec();
+    if ranges.len() > max_parts.max(1) {
+        let mut gaps: Vec<(u64, usize)> = ranges.windows(2).enumerate().map(|(i, w)| (w[1].0 - w[0].1, i)).collect();
+        gaps.sort();
+        let mut joined = vec![false; ranges.len()];
+        for (_, i) in gaps.iter().take(ranges.len() - max_parts.max(1)) {
+            joined[*i] = true;
+        }
+        let mut merged: Vec<(u64, u64)> = vec![];
+        for (i, range) in ranges.iter().enumerate() {
+            match merged.last_mut() {
+                Some(last) if i > 0 && joined[i - 1] => last.1 = range.1,
+                _ => merged.push(*range),
+            }
+        }
+        ranges = merged;
+    }
+
+    let mut parts = vec![];
+    let mut push = |start_byte: u64, end_byte: u64, completed: bool| {
+        parts.push(PartState {
+            index: parts.len(),
+            start_byte,
+            end_byte,
+            current_byte: if completed { end_byte + 1 } else { start_byte },
+            completed,
+            tail_checksum: None,
+        });
+    };
+    let mut next = 0;
+    for (start, end) in ranges {
+        if start > next {
+            push(next, start - 1, true);
+        }
+        push(start, end, false);
+        next = end + 1;
+    }
+    if next < total_size {
+        push(next, total_size - 1, true);
+    }
+    parts
+}
+
+fn reuse_pieces(seed: &Path, part: &File, pieces: &PieceHashes, total_size: u64) -> Result<Vec<bool>> {
+    let mut old = File::open(seed)?;
+    let mut known = vec![false; pieces.hashes.len()];
+    let mut buf = vec![0u8; pieces.length as usize];
+    for (index, expected) in pieces.hashes.iter().enumerate() {
+        let len = pieces.length.min(total_size.saturating_sub(index as u64 * pieces.length)) as usize;
+        if read_full(&mut old, &mut buf[..len])? < len {
+            break;
+        }
+        if hash_bytes(&buf[..len], pieces.algo).eq_ignore_ascii_case(expected) {
+            write_all_at(part, &buf[..len], index as u64 * pieces.length)?;
+            known[index] = true;
+        }
+    }
+    Ok(known)
+}
+
+fn reuse_zsync(seed: &Path, part: &File, index: &ZsyncIndex, total_size: u64) -> Result<Vec<bool>> {
+    let size = index.block_size;
+    let mask = if index.rsum_bytes == 4 { u32::MAX } else { (1u32 << (8 * index.rsum_bytes)) - 1 };
+    let mut by_rsum: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (block, (rsum, _)) in index.blocks.iter().enumerate() {
+        by_rsum.entry(*rsum).or_default().push(block);
+    }
+    let mut known = vec![false; index.blocks.len()];
+    let mut remaining = known.len();
+
+    let mut old = File::open(seed)?;
+    let mut buf: Vec<u8> = vec![];
+    let mut eof = false;
+    let mut pos = 0;
+    let mut rsum: Option<(u16, u16)> = None;
+    while remaining > 0 {
+        // Keep at least one full window (plus the byte rolled in next) in the buffer
+        if buf.len() - pos < size + 1 && !eof {
+            buf.drain(..pos);
+            pos = 0;
+            let start = buf.len();
+            buf.resize(start + READ_SIZE, 0);
+            let n = read_full(&mut old, &mut buf[start..])?;
+            buf.truncate(start + n);
+            if n < READ_SIZE {
+                // The last block of the new file is zero-padded, so the old one is too
+                eof = true;
+                buf.resize(buf.len() + size, 0);
+            }
+        }
+        if buf.len() - pos < size {
+            break;
+        }
+
+        let window = &buf[pos..pos + size];
+        let (a, b) = *rsum.get_or_insert_with(|| rolling_sum(window));
+        let candidates = by_rsum.get(&((((a as u32) << 16) | b as u32) & mask));
+        let mut matched = false;
+        if let Some(candidates) = candidates.filter(|c| c.iter().any(|block| !known[*block])) {
+            let checksum = Md4::digest(window);
+            for &block in candidates {
+                if !known[block] && index.blocks[block].1 == checksum[..index.checksum_bytes] {
+                    let offset = block as u64 * size as u64;
+                    let len = (size as u64).min(total_size - offset) as usize;
+                    write_all_at(part, &window[..len], offset)?;
+                    known[block] = true;
+                    remaining -= 1;
+                    matched = true;
+                }
+            }
+        }
+        if matched {
+            pos += size;
+            rsum = None;
+        } else if pos + size < buf.len() {
+            let (out, inc) = (buf[pos] as u16, buf[pos + size] as u16);
+            let a = a.wrapping_sub(out).wrapping_add(inc);
+            let b = b.wrapping_sub((size as u16).wrapping_mul(out)).wrapping_add(a);
+            rsum = Some((a, b));
+            pos += 1;
+        } else {
+            pos += 1;
+            rsum = None;
+        }
+    }
+    Ok(known)
+}
+
+/// The zsync (rsync) weak checksum of a block.
+fn rolling_sum(block: &[u8]) -> (u16, u16) {
+    let len = block.len();
+    block.iter().enumerate().fold((0u16, 0u16), |(a, b), (i, byte)| {
+        let byte = *byte as u16;
+        (a.wrapping_add(byte), b.wrapping_add(((len - i) as u16).wrapping_mul(byte)))
+    })
+}
+
+/// Read until `buf` is full or the file ends; returns the bytes read.
+fn read_full(file: &mut File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
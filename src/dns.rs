@@ -0,0 +1,140 @@
+//! Name resolution overrides: fixed addresses for single hosts (`--resolve`) and
+//! DNS over HTTPS (`--doh-url`) for everything else, for pinning a CDN edge or
+//! getting around a broken local resolver.
+//!
+//! The DoH resolver uses the JSON API (`application/dns-json`) that Cloudflare,
+//! Google and most public resolvers offer. Its own server is looked up by the
+//! system resolver, so give it as an IP address where DNS cannot be trusted at
+//! all (e.g. `https://1.1.1.1/dns-query`).
+
+use anyhow::{anyhow, Result};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Answers are kept at least this long, whatever their TTL
+const MIN_TTL: Duration = Duration::from_secs(60);
+
+/// DNS record types asked for
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Parse a `--resolve` entry: `host:address` or curl's `host:port:address`.
+/// IPv6 addresses may be bracketed. The port is not part of the override: requests
+/// keep the port of their URL.
+pub fn parse_resolve(value: &str) -> Result<(String, SocketAddr)> {
+    let (host, rest) = value
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected host:address, got {}", value))?;
+    let address = rest
+        .split_once(':')
+        .filter(|(port, _)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(rest, |(_, address)| address);
+    let ip: IpAddr = address
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| anyhow!("invalid address {:?} in {}", address, value))?;
+    if host.is_empty() {
+        return Err(anyhow!("no host in {}", value));
+    }
+    Ok((host.to_ascii_lowercase(), SocketAddr::new(ip, 0)))
+}
+
+/// Addresses of each host and when they expire.
+type Cache = HashMap<String, (Instant, Vec<IpAddr>)>;
+
+/// Resolver asking a DNS-over-HTTPS server. Clones share the cache.
+#[derive(Clone)]
+pub struct DohResolver {
+    url: String,
+    client: reqwest::Client,
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    kind: u16,
+    #[serde(rename = "TTL", default)]
+    ttl: u64,
+    data: String,
+}
+
+impl DohResolver {
+    /// `client` sends the queries; it must not use this resolver itself.
+    pub fn new(url: impl Into<String>, client: reqwest::Client) -> Self {
+        Self { url: url.into(), client, cache: Arc::default() }
+    }
+
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        if let Some((expires, addresses)) = self.cache.lock().unwrap().get(host) {
+            if *expires > Instant::now() {
+                return Ok(addresses.clone());
+            }
+        }
+        let (v4, v6) = tokio::join!(self.query(host, TYPE_A), self.query(host, TYPE_AAAA));
+        let mut ttl = None;
+        let mut addresses = vec![];
+        for (answers, kind) in [(v4?, TYPE_A), (v6.unwrap_or_default(), TYPE_AAAA)] {
+            // CNAMEs come along in the answer; only the addresses count
+            for answer in answers.into_iter().filter(|a| a.kind == kind) {
+                if let Ok(ip) = answer.data.parse() {
+                    addresses.push(ip);
+                    ttl = Some(ttl.map_or(answer.ttl, |t: u64| t.min(answer.ttl)));
+                }
+            }
+        }
+        if addresses.is_empty() {
+            return Err(anyhow!("{} has no address (DNS over HTTPS)", host));
+        }
+        let ttl = Duration::from_secs(ttl.unwrap_or(0)).max(MIN_TTL);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(host.to_string(), (Instant::now() + ttl, addresses.clone()));
+        Ok(addresses)
+    }
+
+    async fn query(&self, host: &str, kind: u16) -> Result<Vec<DohAnswer>> {
+        let response: DohResponse = self
+            .client
+            .get(&self.url)
+            .query(&[("name", host), ("type", &kind.to_string())])
+            .header(reqwest::header::ACCEPT, "application/dns-json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // Any other status is a DNS error code (3 for a name that does not exist)
+        match response.status {
+            0 => Ok(response.answer),
+            status => Err(anyhow!("DNS over HTTPS lookup of {} failed (rcode {})", host, status)),
+        }
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses = resolver.lookup(&host).await.map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { e.into() })?;
+            let addrs: Addrs = Box::new(addresses.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
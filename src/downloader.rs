@@ -1,71 +1,570 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::{bail, Context, Result, anyhow};
+use clap::ValueEnum;
 use futures::StreamExt;
-use governor::{Quota, RateLimiter};
+use std::future::Future;
+use governor::{InsufficientCapacity, Quota, RateLimiter};
 use governor::clock::DefaultClock;
 use governor::state::{InMemoryState, NotKeyed};
 use chrono::{DateTime, Local};
-use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use reqwest::cookie::Jar;
 use reqwest::{Client, header};
-use serde::{Deserialize, Serialize};
 use std::num::NonZeroU32;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::fs::{self, File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, AsyncSeekExt, SeekFrom};
-use tokio::sync::Mutex;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, Mutex, MutexGuard};
+use tokio::task::JoinSet;
 use std::time::{Instant, Duration};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::utils::{get_filename_from_url, sanitize_filename};
-use crate::cli::VerifyMode;
-use crate::state::{DownloadState, PartState};
+use crate::utils::{backoff_delay, get_filename_from_url, sanitize_filename, sanitize_relative_path, NetworkOptions};
+use crate::state::{move_file, part_path, state_path, sync_dir, DownloadState, PartState};
+use crate::control::{Interrupted, PauseToken, Paused};
+use crate::hashing::{hash_file, parse_expected_hash, ChecksumManifest, HashAlgorithm, VerifyMode};
+use crate::delta::{fetch_zsync, plan_parts, reuse_blocks, seed_path, BlockSums};
+use crate::jobs::JobStore;
+use crate::object_store::{is_object_url, request_url};
+use crate::signatures::{fetch_signature, needs_signature, verify_detached, Keyring};
+use crate::throttle::{check_throttled, HostThrottle, Throttled};
+use crate::events::{DownloadEvent, EventSender, ProgressReporter, EVENT_CAPACITY};
+use crate::encoding::{body_stream, ContentEncoding, ACCEPT_ENCODING};
+use crate::bars::Bars;
+use crate::refresh::{is_forbidden, FreshUrls, MAX_URL_REFRESHES};
+use crate::extract::{decompress_file, extract_archive, ArchiveKind, Compression};
+
+/// Settings shared by every file of a batch.
+#[derive(Clone, Debug)]
+pub struct DownloadOptions {
+    pub output_dir: PathBuf,
+    /// Maximum number of files downloaded at the same time (defaults to logical CPUs)
+    pub concurrency: Option<usize>,
+    /// Global rate limit in bytes per second
+    pub rate_limit: Option<u32>,
+    /// Number of segments per file (the upper bound with `adaptive_split`)
+    pub split: usize,
+    /// What to do when a final file already exists
+    pub if_exists: IfExists,
+    /// Smallest segment worth its own connection; smaller files get fewer segments
+    pub min_split_size: u64,
+    /// `--split auto`: start with a few segments and add more while throughput keeps improving
+    pub adaptive_split: bool,
+    pub verify_mode: VerifyMode,
+    /// Provider token, attached to requests whose URL belongs to a known provider
+    pub token: Option<String>,
+    /// Retry attempts per part and per file before giving up
+    pub retries: u32,
+    /// Base delay of the exponential backoff between retries
+    pub retry_wait: Duration,
+    /// Proxy and TLS settings applied to every request
+    pub network: NetworkOptions,
+    /// Rate limit of every single file in bytes per second (tasks may override it with `limit=`)
+    pub rate_limit_per_file: Option<u32>,
+    /// Spread the segments of a file over all of its mirrors
+    pub race_mirrors: bool,
+    /// Algorithm for hashes without an `algo:` prefix
+    pub hash_algo: HashAlgorithm,
+    /// Job database recording every download (history disabled when unset)
+    pub db_path: Option<PathBuf>,
+    /// Skip URLs the job database already saw completed at another path (`--dedup`)
+    pub dedup: bool,
+    /// Extra headers (`--header`) sent with every download and HEAD request
+    pub headers: header::HeaderMap,
+    /// Cookies loaded from `--cookie-file`
+    pub cookies: Option<Arc<Jar>>,
+    /// Draw indicatif progress bars on stderr (events are emitted either way)
+    pub progress_bars: bool,
+    /// Bars of running files shown at once; finished files collapse into a short
+    /// "recently completed" list (`--max-visible-bars`, `None` shows every file)
+    pub max_visible_bars: Option<usize>,
+    /// Print a summary line (files, bytes, speed) on stderr this often, e.g. instead of the bars
+    pub summary_interval: Option<Duration>,
+    /// Stream every event as a JSON line (`--progress json`); handled by the caller
+    pub json_progress: bool,
+    /// Where that stream goes: a file, FIFO or Unix socket (`None` for stdout)
+    pub progress_to: Option<PathBuf>,
+    /// Checksum file (path or URL) supplying hashes for tasks without one
+    pub checksum_file: Option<String>,
+    /// Abort and retry a connection that delivers no data for this long (`None` disables it)
+    pub stall_timeout: Option<Duration>,
+    /// Time allowed to establish a connection
+    pub connect_timeout: Duration,
+    /// Longest wait for a response or for the next chunk of its body (`None` for no limit)
+    pub read_timeout: Option<Duration>,
+    /// Limit on a whole request, body included (`None` for no limit)
+    pub request_timeout: Option<Duration>,
+    /// Reserve the full size of each file on disk before writing (`--preallocate`)
+    pub preallocate: bool,
+    /// HTTP version of download requests
+    pub http_version: HttpVersion,
+    /// Reuse the unchanged blocks of a file being replaced (`--delta`)
+    pub delta: bool,
+    /// Ask for gzip, deflate or zstd transfers of text-like files and decode them (`--compress`)
+    pub compress: bool,
+    /// Decode bodies the server compressed on its own; off keeps the exact bytes (`--no-auto-decompress`)
+    pub auto_decompress: bool,
+    /// Unpack downloaded zip and tar archives next to them (`--extract`)
+    pub extract: bool,
+    /// Delete an archive once it was unpacked (`--remove-archive`)
+    pub remove_archive: bool,
+    /// Decompress downloaded `.gz`, `.xz` and `.zst` files (`--decompress`)
+    pub decompress: Option<DecompressMode>,
+    /// Directory for `.part` and `.part.json` files instead of next to the final file (`--temp-dir`)
+    pub temp_dir: Option<PathBuf>,
+    /// Flush finished files to disk before renaming them (`--sync`)
+    pub sync: SyncMode,
+    /// Directory structure of provider files below `output_dir` (`--layout`)
+    pub layout: Layout,
+    /// Start time of the items without their own `at=` (`--at`)
+    pub schedule: Option<crate::schedule::Schedule>,
+    /// Start a batch even when it does not fit in the free space of the output directory
+    /// or exceeds `max_total_size`
+    pub force: bool,
+    /// Byte budget of a whole batch (`--max-total-size`)
+    pub max_total_size: Option<u64>,
+    /// Files known to be larger are left out of the batch (`--max-file-size`)
+    pub max_file_size: Option<u64>,
+    /// `--out` template for the saved path of items without an explicit name
+    pub output_template: Option<String>,
+    /// Restrict file names to ASCII letters, digits, `.`, `-` and `_`
+    pub ascii_names: bool,
+    /// Trusted keys for `--verify-sig`; when set every downloaded file needs a valid detached signature
+    pub signature_keyring: Option<Arc<Keyring>>,
+    /// Shell command run with a JSON payload when a file or batch completes
+    pub on_complete_cmd: Option<String>,
+    /// Shell command run with a JSON payload when a file or batch fails
+    pub on_error_cmd: Option<String>,
+    /// URL the same payload is `POST`ed to for every file and batch
+    pub webhook: Option<String>,
+    /// Show a desktop notification when a batch completes or fails
+    pub notify: bool,
+    /// JSON report of a finished batch (`--report`)
+    pub report: Option<PathBuf>,
+    /// `.meta4` file describing the batch, written once it finishes (`--export-metalink`)
+    pub export_metalink: Option<PathBuf>,
+    /// Resolve names, sizes and existing files and print the plan instead of downloading (`--dry-run`)
+    pub dry_run: bool,
+    /// Checksum manifest written into the output directory once a batch finishes
+    pub checksum_manifest: Option<ChecksumManifest>,
+}
+
+impl DownloadOptions {
+    /// Options with the same defaults as the command line, saving into `output_dir`.
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            concurrency: None,
+            rate_limit: None,
+            split: 8,
+            adaptive_split: false,
+            min_split_size: 4 * 1024 * 1024,
+            if_exists: IfExists::Skip,
+            verify_mode: VerifyMode::Auto,
+            token: None,
+            retries: 5,
+            retry_wait: Duration::from_secs(1),
+            network: NetworkOptions::default(),
+            rate_limit_per_file: None,
+            race_mirrors: false,
+            hash_algo: HashAlgorithm::Sha256,
+            db_path: None,
+            dedup: false,
+            headers: header::HeaderMap::new(),
+            cookies: None,
+            progress_bars: true,
+            max_visible_bars: None,
+            summary_interval: None,
+            json_progress: false,
+            progress_to: None,
+            checksum_file: None,
+            stall_timeout: Some(Duration::from_secs(30)),
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: None,
+            request_timeout: None,
+            preallocate: false,
+            http_version: HttpVersion::Auto,
+            delta: false,
+            compress: false,
+            auto_decompress: true,
+            extract: false,
+            remove_archive: false,
+            decompress: None,
+            temp_dir: None,
+            sync: SyncMode::Auto,
+            layout: Layout::Flat,
+            schedule: None,
+            force: false,
+            max_total_size: None,
+            max_file_size: None,
+            output_template: None,
+            ascii_names: false,
+            signature_keyring: None,
+            on_complete_cmd: None,
+            on_error_cmd: None,
+            webhook: None,
+            notify: false,
+            report: None,
+            export_metalink: None,
+            dry_run: false,
+            checksum_manifest: None,
+        }
+    }
+
+    /// The path the `.part` and `.part.json` files of `filepath` are named after (see `--temp-dir`).
+    pub fn partial_base(&self, filepath: &Path) -> PathBuf {
+        crate::state::partial_base(filepath, &self.output_dir, self.temp_dir.as_deref())
+    }
+
+    /// Client builder with the proxy, TLS settings, timeouts, extra headers and cookies of this
+    /// batch applied. User headers are set last so they can override the default User-Agent.
+    pub fn client_builder(&self) -> reqwest::ClientBuilder {
+        let mut builder = self
+            .network
+            .client_builder()
+            .user_agent("rdl/0.1.0")
+            .connect_timeout(self.connect_timeout)
+            .default_headers(self.headers.clone());
+        if let Some(timeout) = self.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(jar) = &self.cookies {
+            builder = builder.cookie_provider(jar.clone());
+        }
+        match self.http_version {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder.http2_prior_knowledge(),
+            #[cfg(feature = "http3")]
+            HttpVersion::Http3 => builder.http3_prior_knowledge(),
+            // Rejected when the options are parsed
+            #[cfg(not(feature = "http3"))]
+            HttpVersion::Http3 => builder,
+        }
+    }
+
+    /// Longest silence allowed between two chunks of a body: the shorter of the
+    /// stall and read timeouts.
+    fn chunk_timeout(&self) -> Option<Duration> {
+        match (self.stall_timeout, self.read_timeout) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// A file [`Downloader::download_file`] finished.
+#[derive(Clone, Debug)]
+pub struct Downloaded {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// The file was already there (`--if-exists`, or downloaded to another path before)
+    pub skipped: bool,
+}
+
+/// The remote file no longer matches the partial download (or ignores ranges).
+#[derive(Debug)]
+pub struct RemoteChanged;
+
+impl std::fmt::Display for RemoteChanged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "remote file changed or range requests are not supported; restarting download")
+    }
+}
+
+impl std::error::Error for RemoteChanged {}
+
+/// A 206 response that does not cover the requested range. Not retried: writing
+/// it would put the wrong bytes at the requested offset.
+#[derive(Debug)]
+pub struct BadRange(String);
+
+impl std::fmt::Display for BadRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "server returned the wrong range: {}", self.0)
+    }
+}
+
+impl std::error::Error for BadRange {}
+
+/// Check the `Content-Range` of a 206 response to a request for bytes `start..=end`
+/// (`None`: to the end of the file) of a file of `total` bytes, if known. Returns the
+/// last byte the response covers.
+fn check_content_range(response: &reqwest::Response, start: u64, end: Option<u64>, total: Option<u64>) -> Result<u64> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| BadRange("no Content-Range header".to_string()))?;
+    // bytes <first>-<last>/<complete length or *>
+    let parsed = value.strip_prefix("bytes ").and_then(|range| {
+        let (span, complete) = range.split_once('/')?;
+        let (first, last) = span.split_once('-')?;
+        let complete = if complete == "*" { None } else { Some(complete.parse::<u64>().ok()?) };
+        Some((first.parse::<u64>().ok()?, last.parse::<u64>().ok()?, complete))
+    });
+    let Some((first, last, complete)) = parsed.filter(|(first, last, _)| first <= last) else {
+        return Err(BadRange(format!("invalid Content-Range {:?}", value)).into());
+    };
+    let requested = match end {
+        Some(end) => format!("{}-{}", start, end),
+        None => format!("{}-", start),
+    };
+    if first != start || end.is_some_and(|end| last > end) {
+        return Err(BadRange(format!("asked for bytes {}, got {}", requested, value)).into());
+    }
+    if let (Some(total), Some(complete)) = (total.filter(|t| *t > 0), complete) {
+        if complete != total {
+            return Err(RemoteChanged.into());
+        }
+    }
+    if let Some(length) = response.content_length().filter(|len| *len != last - first + 1) {
+        return Err(BadRange(format!("Content-Range {:?} but Content-Length {}", value, length)).into());
+    }
+    Ok(last)
+}
+
+/// Whether a resumed response still comes from the file `state` was started on: its
+/// ETag, else its Last-Modified, must match where both sides have one.
+fn unchanged(state: &DownloadState, headers: &header::HeaderMap) -> bool {
+    let value = |name: header::HeaderName| headers.get(name).and_then(|v| v.to_str().ok());
+    let strong = |tag: &str| tag.trim_start_matches("W/").to_string();
+    if let (Some(old), Some(new)) = (state.etag.as_deref(), value(header::ETAG)) {
+        return strong(old) == strong(new);
+    }
+    match (state.last_modified.as_deref(), value(header::LAST_MODIFIED)) {
+        (Some(old), Some(new)) => old == new,
+        _ => true,
+    }
+}
+
+/// Total size and response headers of a download, as far as the server tells.
+pub struct RemoteInfo {
+    /// `None` when unknown; `Some(0)` only for a file confirmed to be empty
+    pub size: Option<u64>,
+    pub headers: header::HeaderMap,
+}
+
+/// Ask the server about `url` with a HEAD request. Servers that refuse HEAD (e.g. CDNs
+/// answering 403/405) or send no length are asked again with a `GET` of the first
+/// byte, whose `Content-Range` carries the total size. Client errors leave the size
+/// unknown (the download itself reports them); throttling, server and network errors
+/// are returned so the caller can retry.
+pub async fn probe_remote(
+    client: &Client,
+    network: &NetworkOptions,
+    url: &str,
+    auth_headers: &header::HeaderMap,
+    timeout: Option<Duration>,
+) -> Result<RemoteInfo> {
+    let head = client.head(request_url(url, "HEAD")?.as_ref()).headers(auth_headers.clone());
+    let head = check_throttled(within(network.send(client, head), timeout).await??)?;
+    if head.status().is_success() {
+        if let Some(size) = content_length(head.headers()).filter(|size| *size > 0) {
+            return Ok(RemoteInfo { size: Some(size), headers: head.headers().clone() });
+        }
+    }
+
+    let get = client
+        .get(request_url(url, "GET")?.as_ref())
+        .headers(auth_headers.clone())
+        .header(header::RANGE, "bytes=0-0");
+    // Only the headers are read; dropping the response closes the connection
+    let get = check_throttled(within(network.send(client, get), timeout).await??)?;
+    let size = match get.status() {
+        reqwest::StatusCode::PARTIAL_CONTENT => content_range_total(get.headers()),
+        status if status.is_success() => content_length(get.headers()),
+        // `bytes */0`: there is no first byte because the file is empty
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE => content_range_total(get.headers()),
+        status if status.is_server_error() => bail!("HTTP {} for {}", status, url),
+        _ if head.status().is_success() => return Ok(RemoteInfo { size: None, headers: head.headers().clone() }),
+        _ => return Ok(RemoteInfo { size: None, headers: header::HeaderMap::new() }),
+    };
+    Ok(RemoteInfo { size, headers: get.headers().clone() })
+}
+
+/// `Content-Length` as sent, which unlike `Response::content_length` also holds for HEAD.
+fn content_length(headers: &header::HeaderMap) -> Option<u64> {
+    headers.get(header::CONTENT_LENGTH)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Whether `response` is a 416 for a range starting at `len` because the file is
+/// exactly `len` bytes long (`Content-Range: bytes */<len>`).
+fn already_complete(response: &reqwest::Response, len: u64) -> bool {
+    response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+        && response
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|value| value.trim() == format!("bytes */{}", len))
+}
+
+/// Complete length from a `Content-Range: bytes <first>-<last>/<length>` header.
+fn content_range_total(headers: &header::HeaderMap) -> Option<u64> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    value.strip_prefix("bytes ")?.split_once('/')?.1.parse().ok()
+}
+
+/// What to do when the final file of a download already exists (`--if-exists`).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IfExists {
+    /// Keep it and skip the download
+    #[default]
+    Skip,
+    /// Delete it and download again
+    Overwrite,
+    /// Keep it as partial data and download the rest
+    Resume,
+    /// Keep it and save the download under a new name (`name_1.ext`)
+    Rename,
+    /// Skip only if its size matches the expected size, else download again
+    CheckSize,
+    /// Skip only if it matches the expected hash (the size without one), else download again
+    CheckHash,
+}
+
+/// What `--decompress` keeps of a downloaded `.gz`, `.xz` or `.zst` file.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecompressMode {
+    /// Only the decompressed file
+    #[default]
+    Replace,
+    /// The compressed download next to the decompressed file
+    Keep,
+}
+
+/// HTTP version of download requests (`--http-version`).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// Whatever the client and server negotiate
+    #[default]
+    Auto,
+    /// HTTP/1.1 only, for CDNs that mishandle multiplexed range requests
+    #[value(name = "1.1")]
+    Http1,
+    /// HTTP/2 without negotiation
+    #[value(name = "2")]
+    Http2,
+    /// HTTP/3 over QUIC (needs the `http3` build feature)
+    #[value(name = "3")]
+    Http3,
+}
+
+/// When finished files are flushed to disk before their final rename (`--sync`).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Only files whose hash was verified
+    #[default]
+    Auto,
+    /// Every file
+    Always,
+    /// Never; leave it to the operating system
+    Never,
+}
+
+/// Where provider files are saved below the output directory (`--layout`).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// Directly below the output directory, by their path in the repository
+    #[default]
+    Flat,
+    /// Below `<provider>/[datasets|spaces/]<model>/`, like the lists of `--fetch-list`
+    ProviderTree,
+}
+
+/// Outcome of [`Downloader::check_existing`].
+enum Existing {
+    Skip,
+    Replace,
+    /// Continue after this many bytes
+    Resume(u64),
+}
+
+/// A connection stayed open but delivered no data within the stall or read timeout.
+#[derive(Debug)]
+pub struct Stalled(pub Duration);
+
+impl std::fmt::Display for Stalled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no data received for {:.0}s; reconnecting", self.0.as_secs_f64())
+    }
+}
+
+impl std::error::Error for Stalled {}
+
+/// Await a response or the next chunk of its body, failing with [`Stalled`] when
+/// nothing arrives within `timeout`.
+async fn within<F: Future>(fut: F, timeout: Option<Duration>) -> Result<F::Output> {
+    match timeout {
+        Some(limit) => tokio::time::timeout(limit, fut).await.map_err(|_| Stalled(limit).into()),
+        None => Ok(fut.await),
+    }
+}
 
 pub struct Downloader {
     client: Client,
-    output_dir: PathBuf,
+    options: DownloadOptions,
     multi_progress: MultiProgress,
-    rate_limiter: Option<Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>,
-    split_count: usize,
+    rate_limiter: Option<Arc<DirectLimiter>>,
     total_files: usize,
     downloaded_files: Arc<AtomicUsize>,
     total_downloaded_bytes: Arc<AtomicU64>,
     total_known_bytes: Arc<AtomicU64>,
+    /// Files whose hash is being computed right now
+    verifying_files: Arc<AtomicUsize>,
+    /// Bytes of files found already downloaded, left out of the throughput
+    skipped_bytes: Arc<AtomicU64>,
+    /// Throughput of the batch, see [`Downloader::bytes_per_sec`]
+    bytes_per_sec: Arc<AtomicU64>,
     header_pb: ProgressBar,
     size_map: HashMap<String, u64>,
     expected_hashes: HashMap<String, String>,
-    verify_mode: VerifyMode,
+    pause: PauseToken,
+    jobs: Option<JobStore>,
+    throttle: Arc<HostThrottle>,
+    /// Pause tokens of the files currently downloading, keyed by URL
+    tasks: std::sync::Mutex<HashMap<String, PauseToken>>,
+    /// Cancelled files whose partial data is deleted once they stop
+    purged: std::sync::Mutex<HashSet<String>>,
+    /// Files found already downloaded instead of being fetched, until their `Completed` event
+    skipped: std::sync::Mutex<HashSet<String>>,
+    /// Progress bar of every file, reused by all of its attempts
+    bars: Arc<Bars>,
+    /// Addresses re-resolved after a pre-signed one expired (403)
+    fresh_urls: Arc<FreshUrls>,
+    events: EventSender,
 }
 
 impl Downloader {
     pub fn new(
-        output_dir: PathBuf,
-        rate_limit_bytes_per_sec: Option<u32>,
-        split_count: usize,
+        options: DownloadOptions,
         total_files: usize,
         size_map: HashMap<String, u64>,
         expected_hashes: HashMap<String, String>,
-        verify_mode: VerifyMode,
     ) -> Self {
-        let client = Client::builder()
-            .user_agent("rdl/0.1.0")
-            .connect_timeout(std::time::Duration::from_secs(10))
+        let client = options
+            .client_builder()
+            .redirect(options.network.send_policy())
             .build()
             .unwrap_or_else(|_| Client::new());
 
         let multi_progress = MultiProgress::new();
         // Force draw target to stderr with a reasonable refresh rate (e.g., 5Hz)
         // This ensures progress bars are written even when redirected to a file (daemon mode)
-        multi_progress.set_draw_target(ProgressDrawTarget::stderr_with_hz(5));
+        multi_progress.set_draw_target(if options.progress_bars {
+            ProgressDrawTarget::stderr_with_hz(5)
+        } else {
+            ProgressDrawTarget::hidden()
+        });
         
         let header_pb = multi_progress.add(ProgressBar::new(0));
         header_pb.set_style(ProgressStyle::default_bar().template("{msg}").unwrap());
         header_pb.set_message(format!("Summary: Files: 0/{} | Downloaded: 0 B", total_files));
+        let bars = Arc::new(Bars::new(multi_progress.clone(), options.max_visible_bars));
+        let fresh_urls = Arc::new(FreshUrls::new(options.token.clone(), options.network.clone()));
 
-        let rate_limiter = rate_limit_bytes_per_sec.map(|limit| {
-            let quota = Quota::per_second(NonZeroU32::new(limit).unwrap());
-            Arc::new(RateLimiter::direct(quota))
-        });
+        let rate_limiter = options.rate_limit.and_then(new_limiter);
 
         let downloaded_files = Arc::new(AtomicUsize::new(0));
         let total_downloaded_bytes = Arc::new(AtomicU64::new(0));
@@ -73,109 +572,671 @@ impl Downloader {
         // Initialize total_known_bytes with the sum of pre-calculated sizes
         let initial_total_bytes: u64 = size_map.values().sum();
         let total_known_bytes = Arc::new(AtomicU64::new(initial_total_bytes));
+        let verifying_files = Arc::new(AtomicUsize::new(0));
+        let skipped_bytes = Arc::new(AtomicU64::new(0));
+        let bytes_per_sec = Arc::new(AtomicU64::new(0));
+
+        let pause = PauseToken::new();
+
+        // History is best effort: an unusable database must not block downloads
+        let jobs = options.db_path.as_deref().and_then(|path| match JobStore::open(path) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                eprintln!("Job history disabled: {:#}", e);
+                None
+            }
+        });
+
+        // At most every file of the batch downloading all of its segments from one host
+        let throttle = Arc::new(HostThrottle::new(
+            options.concurrency.unwrap_or_else(num_cpus::get) * options.split,
+        ));
 
-        // Spawn a monitor task to update the header periodically
-        let df = downloaded_files.clone();
+        // Spawn a monitor task to update the header periodically, until the Downloader is dropped
+        let df = Arc::downgrade(&downloaded_files);
         let tdb = total_downloaded_bytes.clone();
         let tkb = total_known_bytes.clone();
+        let vf = verifying_files.clone();
         let hpb = header_pb.clone();
+        let file_bars = bars.clone();
+        let sb = skipped_bytes.clone();
+        let bps = bytes_per_sec.clone();
+        let pt = pause.clone();
+        let summary_interval = options.summary_interval;
         tokio::spawn(async move {
-            loop {
-                let downloaded = df.load(Ordering::Relaxed);
+            let mut last_summary = Instant::now();
+            let mut last_sample = (Instant::now(), 0);
+            let mut speed = 0.0;
+            while let Some(downloaded) = df.upgrade().map(|df| df.load(Ordering::Relaxed)) {
                 let bytes = tdb.load(Ordering::Relaxed);
                 let known = tkb.load(Ordering::Relaxed);
-                hpb.set_message(format!(
-                    "Summary: Files: {}/{} | Downloaded: {} / {}", 
-                    downloaded, 
-                    total_files, 
-                    HumanBytes(bytes),
-                    HumanBytes(known)
-                ));
+                let verifying = vf.load(Ordering::Relaxed);
+
+                // Files found already downloaded count as progress but not as throughput
+                let transferred = bytes.saturating_sub(sb.load(Ordering::Relaxed));
+                let (at, at_bytes) = last_sample;
+                let elapsed = at.elapsed().as_secs_f64();
+                if elapsed > 0.0 {
+                    let sample = transferred.saturating_sub(at_bytes) as f64 / elapsed;
+                    let weight = 1.0 - (-elapsed / SPEED_WINDOW.as_secs_f64()).exp();
+                    speed += weight * (sample - speed);
+                    bps.store(speed as u64, Ordering::Relaxed);
+                    last_sample = (Instant::now(), transferred);
+                }
+
+                let message = format!(
+                    "Summary: {}",
+                    summary_text(downloaded, total_files, bytes, known, verifying, pt.is_paused(), speed as u64)
+                );
+                if let Some(interval) = summary_interval {
+                    if last_summary.elapsed() >= interval {
+                        eprintln!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+                        last_summary = Instant::now();
+                    }
+                }
+                hpb.set_message(message);
                 hpb.tick(); // Force refresh
+                file_bars.tidy();
                 tokio::time::sleep(Duration::from_millis(100)).await;
             }
         });
 
         Self {
             client,
-            output_dir,
+            options,
             multi_progress,
             rate_limiter,
-            split_count,
             total_files,
             downloaded_files,
             total_downloaded_bytes,
             total_known_bytes,
+            verifying_files,
+            skipped_bytes,
+            bytes_per_sec,
             header_pb,
             size_map,
             expected_hashes,
-            verify_mode,
+            pause,
+            jobs,
+            throttle,
+            tasks: std::sync::Mutex::new(HashMap::new()),
+            purged: std::sync::Mutex::new(HashSet::new()),
+            skipped: std::sync::Mutex::new(HashSet::new()),
+            bars,
+            fresh_urls,
+            events: broadcast::channel(EVENT_CAPACITY).0,
         }
     }
 
-    pub async fn download_file(&self, item: crate::providers::DownloadItem) -> Result<()> {
-        let url = item.url.clone();
-        let filename = get_filename_from_url(&url)?;
-        let sanitized_filename = sanitize_filename(&filename);
-        let filepath = self.output_dir.join(&sanitized_filename);
+    /// Receive a [`DownloadEvent`] for every file started, progressing, verified,
+    /// completed or failed from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<DownloadEvent> {
+        self.events.subscribe()
+    }
 
-        if filepath.exists() {
-            let metadata = fs::metadata(&filepath).await?;
-            let size = metadata.len();
-            let created: DateTime<Local> = metadata.created()?.into();
-            
-            let pb = self.multi_progress.add(ProgressBar::new(0));
-            pb.set_style(ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {msg}")
-                .unwrap());
-            
-            // Align with: {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4}
-            // Total width approx: 25 + 1 + 12 + 1 + 4 = 43 chars
-            let size_str = format!("{}", HumanBytes(size));
-            let date_str = created.format("%Y-%m-%d %H:%M").to_string();
-            
-            pb.finish_with_message(format!(
-                "{:>25} {:>17} Skipped {}",
-                size_str,
-                date_str,
-                sanitized_filename
+    fn emit(&self, event: DownloadEvent) {
+        // No subscribers is fine: the progress bars are the default consumer
+        let _ = self.events.send(event);
+    }
+
+    /// Show a "Queued" bar for every item of a batch up front, so the bars keep the
+    /// order of the tasks instead of appearing as downloads start and finish.
+    pub fn queue(&self, items: &[crate::providers::DownloadItem]) {
+        if !self.options.progress_bars {
+            return;
+        }
+        let files = items.iter().map(|item| {
+            let name = item_relative_path(item)
+                .ok()
+                .and_then(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+                .unwrap_or_else(|| item.url.clone());
+            (item.url.clone(), name)
+        });
+        self.bars.queue(files, &self.size_map);
+    }
+
+    fn file_bar(&self, url: &str, len: u64) -> ProgressBar {
+        self.bars.file_bar(url, len)
+    }
+
+    fn progress_reporter(&self, url: &str, total: u64) -> ProgressReporter {
+        ProgressReporter::new(self.events.clone(), url.to_string(), (total > 0).then_some(total))
+    }
+
+    /// One-line progress of the batch, e.g. "Files: 2/5 | Downloaded: 1.20 GiB / 4.00 GiB".
+    pub fn summary(&self) -> String {
+        summary_text(
+            self.downloaded_files.load(Ordering::Relaxed),
+            self.total_files,
+            self.total_downloaded_bytes.load(Ordering::Relaxed),
+            self.total_known_bytes.load(Ordering::Relaxed),
+            self.verifying_files.load(Ordering::Relaxed),
+            self.pause.is_paused(),
+            self.bytes_per_sec(),
+        )
+    }
+
+    /// Current throughput of the whole batch, averaged over the last few seconds.
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec.load(Ordering::Relaxed)
+    }
+
+    /// Time left for the bytes of the batch not downloaded yet at the current throughput.
+    pub fn eta(&self) -> Option<Duration> {
+        let left = self
+            .total_known_bytes
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.total_downloaded_bytes.load(Ordering::Relaxed));
+        batch_eta(left, self.bytes_per_sec())
+    }
+
+    /// Token used to pause/resume every download of this batch.
+    pub fn pause_token(&self) -> PauseToken {
+        self.pause.clone()
+    }
+
+    /// Token of one file: follows the batch token and can be paused on its own.
+    fn task_token(&self, url: &str) -> PauseToken {
+        self.tasks
+            .lock()
+            .unwrap()
+            .entry(url.to_string())
+            .or_insert_with(|| self.pause.child())
+            .clone()
+    }
+
+    fn running_task(&self, url: &str) -> Option<PauseToken> {
+        self.tasks.lock().unwrap().get(url).cloned()
+    }
+
+    /// Pause a single file; returns false if it is not downloading.
+    pub fn pause_task(&self, url: &str) -> bool {
+        self.running_task(url).map(|t| t.pause()).is_some()
+    }
+
+    pub fn resume_task(&self, url: &str) -> bool {
+        self.running_task(url).map(|t| t.resume()).is_some()
+    }
+
+    /// Stop a single file. Its part state is kept so it can be resumed later,
+    /// unless `purge` is set, in which case the partial data is deleted once it has stopped.
+    pub fn cancel_task(&self, url: &str, purge: bool) -> bool {
+        let Some(token) = self.running_task(url) else {
+            return false;
+        };
+        if purge {
+            self.purged.lock().unwrap().insert(url.to_string());
+        }
+        token.shutdown();
+        true
+    }
+
+    /// URLs of the files paused individually.
+    pub fn paused_tasks(&self) -> Vec<String> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, token)| token.is_paused_locally())
+            .map(|(url, _)| url.clone())
+            .collect()
+    }
+
+    /// Wait until a paused file may continue, recording the pause in the job history
+    /// so that `rdl list` tells it apart from a stalled download.
+    async fn wait_resumed(&self, url: &str, filepath: &Path, pause: &PauseToken) -> Result<(), Interrupted> {
+        self.record_paused(url, filepath, true);
+        pause.wait_resumed().await?;
+        self.record_paused(url, filepath, false);
+        Ok(())
+    }
+
+    fn record_paused(&self, url: &str, filepath: &Path, paused: bool) {
+        if let Some(jobs) = &self.jobs {
+            if let Err(e) = jobs.set_paused(url, filepath, paused) {
+                let _ = self.multi_progress.println(format!("Failed to update job history: {:#}", e));
+            }
+        }
+    }
+
+    /// Leave the summary line on screen after an interrupted batch.
+    pub fn abandon_progress(&self) {
+        self.header_pb.abandon();
+    }
+
+    /// Download one item and record the outcome in the job database.
+    pub async fn download_file(&self, mut item: crate::providers::DownloadItem) -> Result<Downloaded> {
+        apply_layout(&mut item, self.options.layout);
+        let path = self.options.output_dir.join(item_relative_path(&item)?);
+        self.emit(DownloadEvent::Started {
+            url: item.url.clone(),
+            path: path.clone(),
+            total: self.size_map.get(&item.url).copied(),
+        });
+
+        let result = self.download_and_record(&item).await;
+        self.tasks.lock().unwrap().remove(&item.url);
+        if self.purged.lock().unwrap().remove(&item.url) && result.is_err() {
+            let base = self.options.partial_base(&path);
+            let _ = fs::remove_file(part_path(&base)).await;
+            let _ = fs::remove_file(state_path(&base)).await;
+        }
+
+        // A failure before the download started leaves its bar queued
+        if let (Err(_), Some(pb)) = (&result, self.bars.get(&item.url)) {
+            if !pb.is_finished() {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                pb.abandon_with_message(format!("Failed      {}", name));
+            }
+        }
+
+        let skipped = self.skipped.lock().unwrap().remove(&item.url);
+        let result = match result {
+            Ok(()) => {
+                let bytes = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+                Ok(Downloaded { path, bytes, skipped })
+            }
+            Err(e) => Err(e),
+        };
+        self.emit(match &result {
+            Ok(done) => DownloadEvent::Completed {
+                url: item.url.clone(),
+                path: done.path.clone(),
+                bytes: done.bytes,
+                skipped: done.skipped,
+            },
+            Err(e) => DownloadEvent::Failed { url: item.url.clone(), error: format!("{:#}", e) },
+        });
+        result
+    }
+
+    async fn download_and_record(&self, item: &crate::providers::DownloadItem) -> Result<()> {
+        let Some(jobs) = &self.jobs else {
+            return self.download_verified(item).await;
+        };
+        let filepath = self.options.output_dir.join(item_relative_path(item)?);
+
+        if self.options.dedup && !filepath.exists() {
+            if let Ok(Some(job)) = jobs.find_completed(&item.url) {
+                if job.path != filepath && job.path.exists() {
+                    let size = fs::metadata(&job.path).await.map(|m| m.len()).unwrap_or(0);
+                    // Bars may be hidden; the requested file is never created, so always say where it is
+                    eprintln!("Skipped {} (already downloaded to {})", item.url, job.path.display());
+                    self.record_skip(&item.url, size);
+                    return Ok(());
+                }
+            }
+        }
+
+        let job_id = jobs.start(item, &self.options.output_dir, &filepath).ok();
+        let result = self.download_verified(item).await;
+        if let Some(id) = job_id {
+            let recorded = match &result {
+                Ok(()) => {
+                    let size = fs::metadata(&filepath).await.map(|m| m.len()).unwrap_or(0);
+                    jobs.complete(id, size)
+                }
+                Err(e) => jobs.fail(id, &format!("{:#}", e)),
+            };
+            if let Err(e) = recorded {
+                let _ = self.multi_progress.println(format!("Failed to update job history: {:#}", e));
+            }
+        }
+        result
+    }
+
+    /// Download one item and, with `--verify-sig`, check its detached signature;
+    /// with `--extract` a verified archive is unpacked afterwards.
+    async fn download_verified(&self, item: &crate::providers::DownloadItem) -> Result<()> {
+        // --decompress removed the download itself last time
+        let filepath = self.options.output_dir.join(item_relative_path(item)?);
+        if let Some(output) = self.decompressed_output(&filepath).filter(|o| o.is_file() && !filepath.exists()) {
+            let _ = self.multi_progress.println(format!(
+                "Skipped {} (already decompressed to {})", item.url, output.display()
             ));
-            self.downloaded_files.fetch_add(1, Ordering::Relaxed);
-            self.total_downloaded_bytes.fetch_add(size, Ordering::Relaxed);
-            
-            // If this file was NOT in the size_map (e.g. HEAD failed), we need to add it to known bytes now
-            if !self.size_map.contains_key(&url) {
-                 self.total_known_bytes.fetch_add(size, Ordering::Relaxed);
-            }
-            
+            self.record_skip(&item.url, fs::metadata(&output).await.map_or(0, |m| m.len()));
+            return Ok(());
+        }
+        self.download_file_with_retries(item).await?;
+        match &self.options.signature_keyring {
+            Some(keyring) if needs_signature(&item.url) => self.verify_signature(item, keyring.clone()).await?,
+            _ => {}
+        }
+        self.unpack(item).await
+    }
+
+    /// With `--extract` or `--decompress`, unpack a freshly downloaded archive or
+    /// compressed file into its directory. Files that were skipped as already
+    /// present are left alone.
+    async fn unpack(&self, item: &crate::providers::DownloadItem) -> Result<()> {
+        let filepath = self.options.output_dir.join(item_relative_path(item)?);
+        if !filepath.is_file() || self.skipped.lock().unwrap().contains(&item.url) {
             return Ok(());
         }
+        if let Some(kind) = ArchiveKind::from_path(&filepath).filter(|_| self.options.extract) {
+            return self.extract(item, &filepath, kind).await;
+        }
+        if let Some(mode) = self.options.decompress {
+            if let Some((compression, output)) = Compression::from_path(&filepath) {
+                return self.decompress(item, &filepath, compression, &output, mode).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// The file a `--decompress` run left in place of the compressed download at `filepath`.
+    fn decompressed_output(&self, filepath: &Path) -> Option<PathBuf> {
+        if self.options.decompress != Some(DecompressMode::Replace)
+            || (self.options.extract && ArchiveKind::from_path(filepath).is_some())
+        {
+            return None;
+        }
+        Compression::from_path(filepath).map(|(_, output)| output)
+    }
+
+    /// Progress bar of an unpacking step, over the `len` bytes of the file it reads.
+    fn unpack_bar(&self, url: &str, len: u64, message: String) -> ProgressBar {
+        let pb = self.file_bar(url, len);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.magenta} [{elapsed_precise}] [{bar:40.magenta/blue}] {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4} {msg}")
+            .unwrap()
+            .progress_chars("=>-"));
+        pb.set_message(message);
+        pb
+    }
+
+    async fn extract(&self, item: &crate::providers::DownloadItem, filepath: &Path, kind: ArchiveKind) -> Result<()> {
+        let dest = filepath.parent().unwrap_or(&self.options.output_dir).to_path_buf();
+        self.emit(DownloadEvent::Extracting { url: item.url.clone(), into: dest.clone() });
+
+        let name = filepath.file_name().unwrap().to_string_lossy().to_string();
+        let pb = self.unpack_bar(&item.url, fs::metadata(filepath).await?.len(), format!("Extracting  {}", name));
+        let bar = pb.clone();
+        match extract_archive(filepath, kind, &dest, move |read| bar.set_position(read)).await {
+            Ok(files) => {
+                pb.finish_with_message(format!("Extracted   {} ({} files)", name, files));
+                if self.options.remove_archive {
+                    fs::remove_file(filepath).await.context("Failed to remove the extracted archive")?;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                pb.abandon_with_message(format!("Failed      {}", name));
+                Err(e.context(format!("Failed to extract {}", name)))
+            }
+        }
+    }
+
+    /// Decompress a verified `.gz`, `.xz` or `.zst` download into `output`, which
+    /// replaces an older copy only once it is complete.
+    async fn decompress(
+        &self,
+        item: &crate::providers::DownloadItem,
+        filepath: &Path,
+        compression: Compression,
+        output: &Path,
+        mode: DecompressMode,
+    ) -> Result<()> {
+        self.emit(DownloadEvent::Extracting { url: item.url.clone(), into: output.to_path_buf() });
+        let name = filepath.file_name().unwrap().to_string_lossy().to_string();
+        let pb = self.unpack_bar(&item.url, fs::metadata(filepath).await?.len(), format!("Decoding    {}", name));
+        let bar = pb.clone();
+        let temp = part_path(output);
+        let written = match decompress_file(filepath, compression, &temp, move |read| bar.set_position(read)).await {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = fs::remove_file(&temp).await;
+                pb.abandon_with_message(format!("Failed      {}", name));
+                return Err(e.context(format!("Failed to decompress {}", name)));
+            }
+        };
+        self.complete_file(&temp, output, true).await?;
+        if mode == DecompressMode::Replace {
+            fs::remove_file(filepath).await.context("Failed to remove the compressed download")?;
+        }
+        let output_name = output.file_name().unwrap().to_string_lossy();
+        pb.finish_with_message(format!("Decoded     {} ({})", output_name, HumanBytes(written)));
+        Ok(())
+    }
+
+    /// Count a file that needs no download as done.
+    fn record_skip(&self, url: &str, size: u64) {
+        self.downloaded_files.fetch_add(1, Ordering::Relaxed);
+        self.total_downloaded_bytes.fetch_add(size, Ordering::Relaxed);
+        self.skipped_bytes.fetch_add(size, Ordering::Relaxed);
+        if !self.size_map.contains_key(url) {
+            self.total_known_bytes.fetch_add(size, Ordering::Relaxed);
+        }
+        self.skipped.lock().unwrap().insert(url.to_string());
+    }
+
+    /// Verify the downloaded file against the signature that came with `item` or else
+    /// the `.asc`/`.sig` next to any of its sources.
+    /// The file is removed when the signature is missing or bad.
+    async fn verify_signature(&self, item: &crate::providers::DownloadItem, keyring: Arc<Keyring>) -> Result<()> {
+        let filepath = self.options.output_dir.join(item_relative_path(item)?);
+        let mut signature = item
+            .signature
+            .as_ref()
+            .map(|armored| ("metalink".to_string(), armored.as_bytes().to_vec()));
+        // Signatures are only looked up over HTTP
+        let http_sources = item
+            .sources()
+            .into_iter()
+            .filter(|s| !crate::ftp::is_ftp_url(s) && !is_object_url(s) && !crate::torrent::is_torrent_source(s));
+        for source in http_sources.filter(|_| signature.is_none()) {
+            let headers = crate::providers::auth_headers_for(&source, self.options.token.as_deref());
+            if let Some(found) = fetch_signature(&self.client, &self.options.network, &source, &headers).await? {
+                signature = Some(found);
+                break;
+            }
+        }
+
+        let result = match signature {
+            Some((sig_url, bytes)) => verify_detached(keyring, filepath.clone(), bytes)
+                .await
+                .map(|signer| (sig_url, signer)),
+            None => Err(anyhow!("no .asc or .sig signature found")),
+        };
+        match result {
+            Ok((sig_url, signer)) => {
+                let _ = self.multi_progress.println(format!("Good signature {} from {}", sig_url, signer));
+                Ok(())
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&filepath).await;
+                Err(anyhow!("Signature verification failed for {}: {:#}", item.url, e))
+            }
+        }
+    }
+
+    /// Download one item, retrying the whole file with exponential backoff.
+    /// Each attempt resumes from the offsets saved in the `.part.json` state;
+    /// failures move on to the next mirror before spending regular retries.
+    async fn download_file_with_retries(&self, item: &crate::providers::DownloadItem) -> Result<()> {
+        let max_attempts = self.options.retries + item.mirrors.len() as u32;
+        let mut attempt = 0;
+        let pause = self.task_token(&item.url);
+        loop {
+            if pause.is_shutting_down() {
+                return Err(Interrupted.into());
+            }
+            match self.download_file_once(item, attempt).await {
+                Ok(()) => {
+                    for source in item.sources() {
+                        self.fresh_urls.forget(&source);
+                    }
+                    return Ok(());
+                }
+                Err(e) if e.is::<Paused>() => {
+                    let filepath = self.options.output_dir.join(item_relative_path(item)?);
+                    self.wait_resumed(&item.url, &filepath, &pause).await?
+                }
+                Err(e) if e.is::<Interrupted>() => return Err(e),
+                Err(e) if attempt < max_attempts => {
+                    let sources = item.sources();
+                    let source = &sources[attempt as usize % sources.len()];
+                    attempt += 1;
+                    let delay = if let Some(throttled) = e.downcast_ref::<Throttled>() {
+                        self.throttle.throttled(source, throttled, backoff_delay(self.options.retry_wait, attempt))
+                    } else if (attempt as usize) < sources.len() {
+                        // Switching to an untried mirror does not need a backoff
+                        Duration::ZERO
+                    } else {
+                        backoff_delay(self.options.retry_wait, attempt)
+                    };
+                    let _ = self.multi_progress.println(format!(
+                        "Download of {} failed: {} (retry {}/{} in {:.1}s)",
+                        item.url, e, attempt, max_attempts, delay.as_secs_f64()
+                    ));
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn download_file_once(&self, item: &crate::providers::DownloadItem, attempt: u32) -> Result<()> {
+        // `url` identifies the file (naming, sizes, hashes); requests go to `source`
+        let url = item.url.clone();
+        let sources = item.sources();
+        let source = sources[attempt as usize % sources.len()].clone();
+        let relative_path = item_relative_path(item)?;
+        let sanitized_filename = relative_path.to_string_lossy().to_string();
+        let filepath = self.options.output_dir.join(&relative_path);
+        let auth_headers = crate::providers::download_headers_for(&source, self.options.token.as_deref(), &self.options.network).await;
+
+        // The torrent engine keeps its own partial data and checks what is on disk
+        if crate::torrent::is_torrent_source(&source) {
+            return self.download_torrent(&url, &source, &filepath).await;
+        }
 
         // Determine partial file path and state file path
-        let mut part_filepath = filepath.clone();
-        if let Some(extension) = filepath.extension() {
-            let mut ext = extension.to_os_string();
-            ext.push(".part");
-            part_filepath.set_extension(ext);
-        } else {
-            part_filepath.set_extension("part");
+        let partial_base = self.options.partial_base(&filepath);
+        let part_filepath = part_path(&partial_base);
+        let state_filepath = state_path(&partial_base);
+
+        let mut resumed_len = None;
+        // Previous version moved aside by `--delta`; only rdl's own copy is ever deleted
+        let mut seed = None;
+        if filepath.exists() {
+            match self.check_existing(&url, &filepath).await? {
+                Existing::Skip => {
+                    let metadata = fs::metadata(&filepath).await?;
+                    let size = metadata.len();
+                    let created: DateTime<Local> = metadata.created()?.into();
+
+                    let pb = self.file_bar(&url, 0);
+                    pb.set_style(ProgressStyle::default_bar()
+                        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {msg}")
+                        .unwrap());
+
+                    // Align with: {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4}
+                    // Total width approx: 25 + 1 + 12 + 1 + 4 = 43 chars
+                    let size_str = format!("{}", HumanBytes(size));
+                    let date_str = created.format("%Y-%m-%d %H:%M").to_string();
+
+                    pb.finish_with_message(format!(
+                        "{:>25} {:>17} Skipped {}",
+                        size_str,
+                        date_str,
+                        sanitized_filename
+                    ));
+                    self.record_skip(&url, size);
+                    return Ok(());
+                }
+                Existing::Replace if self.options.delta => {
+                    let _ = self.multi_progress.println(format!("Downloading {} again (--if-exists --delta)", sanitized_filename));
+                    let old = seed_path(&filepath);
+                    fs::rename(&filepath, &old).await.context("Failed to keep the previous version")?;
+                    seed = Some(old);
+                    let _ = fs::remove_file(&part_filepath).await;
+                    let _ = fs::remove_file(&state_filepath).await;
+                }
+                Existing::Replace => {
+                    let _ = self.multi_progress.println(format!("Downloading {} again (--if-exists)", sanitized_filename));
+                    fs::remove_file(&filepath).await.context("Failed to remove existing file")?;
+                    let _ = fs::remove_file(&part_filepath).await;
+                    let _ = fs::remove_file(&state_filepath).await;
+                }
+                Existing::Resume(len) => {
+                    let _ = self.multi_progress.println(format!(
+                        "Resuming {} from {} (--if-exists resume)", sanitized_filename, HumanBytes(len)
+                    ));
+                    let _ = fs::remove_file(&state_filepath).await;
+                    if let Some(parent) = part_filepath.parent() {
+                        fs::create_dir_all(parent).await.context("Failed to create temporary directory")?;
+                    }
+                    move_file(&filepath, &part_filepath).await.context("Failed to resume existing file")?;
+                    resumed_len = Some(len);
+                }
+            }
+        }
+
+        if let Some(parent) = filepath.parent() {
+            fs::create_dir_all(parent).await.context("Failed to create parent directory")?;
+        }
+        if let Some(parent) = part_filepath.parent().filter(|_| self.options.temp_dir.is_some()) {
+            fs::create_dir_all(parent).await.context("Failed to create temporary directory")?;
+        }
+
+        // Blocks can only be reused by a transfer split into byte ranges
+        if crate::ftp::is_ftp_url(&source) || self.accepts_encoding(&filepath) {
+            if let Some(old) = seed.take() {
+                let _ = fs::remove_file(&old).await;
+            }
+        }
+
+        if crate::ftp::is_ftp_url(&source) {
+            return self
+                .download_ftp(url, source, filepath, part_filepath, state_filepath, self.file_rate_limiter(item))
+                .await;
+        }
+
+        // Compressed transfers cannot be split into byte ranges
+        if self.accepts_encoding(&filepath) {
+            let _ = fs::remove_file(&state_filepath).await;
+            return self
+                .download_single_connection(url, source, filepath, part_filepath, state_filepath, DownloadState::default(), auth_headers, self.file_rate_limiter(item))
+                .await;
         }
-        let state_filepath = part_filepath.with_extension("part.json");
 
         // Initialize or load state
         let mut state = if state_filepath.exists() {
             let content = fs::read_to_string(&state_filepath).await?;
-            match serde_json::from_str(&content) {
+            match DownloadState::from_json(&content) {
                 Ok(s) => s,
-                Err(_) => self.init_state(&url).await.unwrap_or(DownloadState {
-                    url: url.clone(),
-                    total_size: 0,
-                    parts: vec![],
-                }),
+                Err(e) => {
+                    let _ = self.multi_progress.println(format!(
+                        "Ignoring unusable state file {:?}: {}", state_filepath, e
+                    ));
+                    self.init_state(&source, &auth_headers).await.unwrap_or(DownloadState {
+                        url: url.clone(),
+                        ..Default::default()
+                    })
+                }
             }
         } else {
-            self.init_state(&url).await?
+            let remote = self.probe(&source, &auth_headers).await?;
+            // Empty files (e.g. `.gitattributes` placeholders) need no transfer at all
+            let mut state = self.plan_state(&source, &remote);
+            if let Some(old) = seed.take() {
+                if state.total_size > 0 {
+                    if let Err(e) = self.apply_delta(item, &source, &auth_headers, &old, &part_filepath, &mut state).await {
+                        let _ = self.multi_progress.println(format!("Delta download of {} not possible: {:#}", sanitized_filename, e));
+                    }
+                    state.save(&state_filepath).await?;
+                }
+                let _ = fs::remove_file(&old).await;
+            }
+            if remote.size == Some(0) {
+                return self.download_empty(&url, &filepath, &part_filepath).await;
+            }
+            state
         };
+        if let Some(len) = resumed_len {
+            state.mark_downloaded(len);
+        }
 
         // Update known bytes if not already counted
         if !self.size_map.contains_key(&url) && state.total_size > 0 {
@@ -184,12 +1245,15 @@ impl Downloader {
 
         // If total_size is 0 (unknown), fallback to single connection download
         if state.total_size == 0 {
-             return self.download_single_connection(url, filepath, part_filepath).await;
+             return self
+                 .download_single_connection(url, source, filepath, part_filepath, state_filepath, state, auth_headers, self.file_rate_limiter(item))
+                 .await;
         }
 
-        // Create/Open the partial file
+        // Create/Open the partial file; resumed data is kept
         let file = OpenOptions::new()
             .create(true)
+            .truncate(false)
             .write(true)
             .read(true)
             .open(&part_filepath)
@@ -198,110 +1262,143 @@ impl Downloader {
         
         // Pre-allocate file size if new
         if file.metadata().await?.len() < state.total_size {
-            file.set_len(state.total_size).await?;
+            if self.options.preallocate {
+                let std_file = file.try_clone().await?.into_std().await;
+                let total_size = state.total_size;
+                tokio::task::spawn_blocking(move || reserve_space(&std_file, total_size))
+                    .await?
+                    .context("Failed to pre-allocate partial file")?;
+            } else {
+                file.set_len(state.total_size).await?;
+            }
         }
-        
-        let file = Arc::new(Mutex::new(file));
-        let state_mutex = Arc::new(Mutex::new(state.clone()));
 
-        let pb = self.multi_progress.add(ProgressBar::new(state.total_size));
+        // Catch data lost since the last save instead of failing the final hash
+        let std_file = file.try_clone().await?.into_std().await;
+        let (checked, restarted) = tokio::task::spawn_blocking(move || {
+            let restarted = state.check_tails(&std_file);
+            (state, restarted)
+        })
+        .await?;
+        state = checked;
+        let restarted = restarted.context("Failed to check downloaded data")?;
+        if !restarted.is_empty() {
+            let _ = self.multi_progress.println(format!(
+                "Data of {} changed since it was saved, downloading part(s) {:?} again",
+                sanitized_filename, restarted
+            ));
+        }
+        
+        let pb = self.file_bar(&url, state.total_size);
         pb.set_style(ProgressStyle::default_bar()
             .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4} {msg}")
             .unwrap()
             .progress_chars("=>-"));
         pb.set_message(format!("Downloading {}", sanitized_filename));
         
-        let initial_progress: u64 = state.parts.iter().map(|p| p.current_byte - p.start_byte).sum();
+        let initial_progress: u64 = state.downloaded_bytes();
         pb.set_position(initial_progress);
+        // Bytes resumed from disk are no download speed; without this the ETA starts far too low
+        pb.reset_eta();
 
-        let mut handles = vec![];
-
-        for part in state.parts.iter_mut() {
-            if part.completed {
-                continue;
-            }
-
-            let client = self.client.clone();
-            let url = url.clone();
-            let file = file.clone();
-            let state_mutex = state_mutex.clone();
-            let pb = pb.clone();
-            let rate_limiter = self.rate_limiter.clone();
-            let part_index = part.index;
-            let start = part.current_byte;
-            let end = part.end_byte;
-            let state_filepath = state_filepath.clone();
-            let total_downloaded_bytes = self.total_downloaded_bytes.clone();
-            let downloaded_files = self.downloaded_files.clone();
-            let header_pb = self.header_pb.clone();
-            let total_files = self.total_files;
-
-            let handle = tokio::spawn(async move {
-                let range_header = format!("bytes={}-{}", start, end);
-                let mut request = client.get(&url).header(header::RANGE, range_header);
-                
-                let response = request.send().await.context("Failed to send request")?;
-                let mut stream = response.bytes_stream();
-                let mut current_pos = start;
+        let ctx = Arc::new(SegmentContext {
+            client: self.client.clone(),
+            network: self.options.network.clone(),
+            sources: sources.clone(),
+            race_mirrors: self.options.race_mirrors,
+            attempt,
+            token: self.options.token.clone(),
+            fresh_urls: self.fresh_urls.clone(),
+            if_range: state.if_range().map(str::to_string),
+            file: Arc::new(file.into_std().await),
+            state: Mutex::new(state.clone()),
+            state_filepath: state_filepath.clone(),
+            pb: pb.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            file_rate_limiter: self.file_rate_limiter(item),
+            total_downloaded_bytes: self.total_downloaded_bytes.clone(),
+            retries: self.options.retries,
+            retry_wait: self.options.retry_wait,
+            read_timeout: self.options.read_timeout,
+            chunk_timeout: self.options.chunk_timeout(),
+            pause: self.task_token(&url),
+            throttle: self.throttle.clone(),
+            progress: self.progress_reporter(&url, state.total_size),
+            min_steal_size: self.options.min_split_size.max(MIN_STEAL_SIZE),
+            workers: AtomicUsize::new(0),
+            target_workers: AtomicUsize::new(usize::MAX),
+            snapshots: AtomicU64::new(0),
+            saved_snapshot: AtomicU64::new(0),
+        });
 
-                while let Some(item) = stream.next().await {
-                    let chunk = item.context("Error while downloading chunk")?;
-                    let len = chunk.len();
+        // Restarted from the persisted part state after every pause
+        loop {
+            let pending: Vec<usize> = ctx
+                .state
+                .lock()
+                .await
+                .parts
+                .iter()
+                .filter(|p| !p.completed)
+                .map(|p| p.index)
+                .collect();
+            let mut workers = JoinSet::new();
+            for index in pending {
+                ctx.spawn_worker(&mut workers, index);
+            }
+            let mut tuner = self.options.adaptive_split.then(|| SplitTuner::new(&ctx, self.options.split));
+            let mut tick = tokio::time::interval_at(
+                tokio::time::Instant::now() + AUTO_SPLIT_INTERVAL,
+                AUTO_SPLIT_INTERVAL,
+            );
 
-                    if len > 0 {
-                        if let Some(limiter) = &rate_limiter {
-                            if let Some(nonzero) = NonZeroU32::new(len as u32) {
-                                limiter.until_n_ready(nonzero).await.unwrap();
-                            }
-                        }
-
-                        {
-                            let mut f = file.lock().await;
-                            f.seek(SeekFrom::Start(current_pos)).await?;
-                            f.write_all(&chunk).await?;
+            // Wait for every part (even after a failure) so no task keeps writing
+            // into the partial file while a file-level retry starts over.
+            let mut first_error = None;
+            loop {
+                tokio::select! {
+                    joined = workers.join_next() => {
+                        let Some(joined) = joined else { break };
+                        let result = match joined {
+                            Ok(r) => r,
+                            Err(e) => Err(e.into()),
+                        };
+                        if let Err(e) = result {
+                            first_error.get_or_insert(e);
                         }
-
-                        current_pos += len as u64;
-                        pb.inc(len as u64);
-                        
-                        // Update global stats
-                        total_downloaded_bytes.fetch_add(len as u64, Ordering::Relaxed);
-                        {
-                            let mut s = state_mutex.lock().await;
-                            if let Some(p) = s.parts.get_mut(part_index) {
-                                p.current_byte = current_pos;
-                                if p.current_byte > p.end_byte {
-                                     p.completed = true;
-                                } else if p.current_byte == p.end_byte + 1 {
-                                     p.completed = true;
-                                }
+                    }
+                    _ = tick.tick(), if tuner.is_some() && first_error.is_none() => {
+                        let tuner = tuner.as_mut().expect("checked by the guard");
+                        match tuner.tick(&ctx).await {
+                            Ok(Some(index)) => ctx.spawn_worker(&mut workers, index),
+                            Ok(None) => {}
+                            Err(e) => {
+                                first_error.get_or_insert(e);
                             }
-                            
-                            // Save state to file (throttled)
-                            let content = serde_json::to_string(&*s)?;
-                            fs::write(&state_filepath, content).await?;
                         }
                     }
                 }
-                
-                // Mark part as completed
-                {
-                    let mut s = state_mutex.lock().await;
-                    if let Some(p) = s.parts.get_mut(part_index) {
-                        p.completed = true;
-                        p.current_byte = p.end_byte + 1; // Ensure it marks as fully done
+            }
+            match first_error {
+                None => break,
+                Some(e) if e.is::<Paused>() => {
+                    pb.set_message(format!("Paused      {}", sanitized_filename));
+                    if let Err(e) = self.wait_resumed(&url, &filepath, &ctx.pause).await {
+                        pb.abandon_with_message(format!("Interrupted {}", sanitized_filename));
+                        return Err(e.into());
                     }
-                    let content = serde_json::to_string(&*s)?;
-                    fs::write(&state_filepath, content).await?;
+                    pb.set_message(format!("Downloading {}", sanitized_filename));
                 }
-
-                Ok::<(), anyhow::Error>(())
-            });
-            handles.push(handle);
-        }
-
-        for handle in handles {
-            handle.await??;
+                Some(e) => {
+                    if e.is::<RemoteChanged>() {
+                        // Partial data belongs to an older version; start over on the next attempt
+                        let _ = fs::remove_file(&state_filepath).await;
+                        let _ = fs::remove_file(&part_filepath).await;
+                    }
+                    pb.abandon_with_message(format!("Failed      {}", sanitized_filename));
+                    return Err(e);
+                }
+            }
         }
 
         // Cleanup
@@ -310,21 +1407,20 @@ impl Downloader {
         }
 
         // Hash/verify policy
-        let expected = self.expected_hashes.get(&url).cloned();
-        if matches!(self.verify_mode, VerifyMode::Off) {
-            tokio::fs::rename(&part_filepath, &filepath).await.context("Failed to rename partial file")?;
+        let expected = self.expected_hash(&url);
+        if matches!(self.options.verify_mode, VerifyMode::Off) {
+            self.complete_file(&part_filepath, &filepath, false).await?;
             pb.finish_with_message(format!("Completed   {}", sanitized_filename));
-        } else if let Some(_) = expected {
-            pb.set_message(format!("Verifying {}", sanitized_filename));
-            let hash = crate::hashing::calculate_hash(&part_filepath).await?;
+        } else if let Some((algo, _)) = expected {
+            let hash = self.verify_file(&url, algo, &part_filepath, &pb, &sanitized_filename).await?;
             self.verify_hash(&url, &hash, &part_filepath)?;
-            tokio::fs::rename(&part_filepath, &filepath).await.context("Failed to rename partial file")?;
-            pb.finish_with_message(format!("Verified    {} (SHA256: {})", sanitized_filename, hash));
-        } else if matches!(self.verify_mode, VerifyMode::On) {
+            self.complete_file(&part_filepath, &filepath, true).await?;
+            pb.finish_with_message(format!("Verified    {} ({}: {})", sanitized_filename, algo, hash));
+        } else if matches!(self.options.verify_mode, VerifyMode::On) {
             // Should be prevented earlier; keep a guard.
             return Err(anyhow!("缺少哈希：{}", url));
         } else {
-            tokio::fs::rename(&part_filepath, &filepath).await.context("Failed to rename partial file")?;
+            self.complete_file(&part_filepath, &filepath, false).await?;
             pb.finish_with_message(format!("Completed   {}", sanitized_filename));
         }
         
@@ -334,24 +1430,57 @@ impl Downloader {
         Ok(())
     }
 
-    async fn init_state(&self, url: &str) -> Result<DownloadState> {
-        let response = self.client.head(url).send().await?;
-        let total_size = response.content_length().unwrap_or(0);
+    async fn init_state(&self, url: &str, auth_headers: &header::HeaderMap) -> Result<DownloadState> {
+        let remote = self.probe(url, auth_headers).await?;
+        Ok(self.plan_state(url, &remote))
+    }
+
+    /// Size and validators of `url`. CDNs that refuse HEAD are asked with a one-byte
+    /// GET, so they keep segmented downloads.
+    async fn probe(&self, url: &str, auth_headers: &header::HeaderMap) -> Result<RemoteInfo> {
+        probe_remote(&self.client, &self.options.network, url, auth_headers, self.options.read_timeout).await
+    }
+
+    /// The part state of a new download of `url`; without a known size it has no parts.
+    fn plan_state(&self, url: &str, remote: &RemoteInfo) -> DownloadState {
+        // A body the server compresses on its own is decoded in one piece
+        let total_size = match ContentEncoding::from_headers(&remote.headers) {
+            Some(_) if self.options.auto_decompress => 0,
+            _ => remote.size.unwrap_or(0),
+        };
+        let header_value = |name: header::HeaderName| {
+            remote
+                .headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header_value(header::ETAG);
+        let last_modified = header_value(header::LAST_MODIFIED);
 
         if total_size == 0 {
-            return Ok(DownloadState {
+            return DownloadState {
                 url: url.to_string(),
-                total_size: 0,
-                parts: vec![],
-            });
+                etag,
+                last_modified,
+                ..Default::default()
+            };
         }
 
-        let part_size = total_size / self.split_count as u64;
+        let split_count = if self.options.adaptive_split {
+            AUTO_SPLIT_START.min(self.options.split)
+        } else {
+            self.options.split
+        };
+        // Every segment covers at least --min-split-size; small files use one connection
+        let by_size = total_size.div_ceil(self.options.min_split_size.max(1)).max(1);
+        let split_count = split_count.min(by_size.try_into().unwrap_or(usize::MAX));
+        let part_size = total_size / split_count as u64;
         let mut parts = vec![];
 
-        for i in 0..self.split_count {
+        for i in 0..split_count {
             let start_byte = i as u64 * part_size;
-            let end_byte = if i == self.split_count - 1 {
+            let end_byte = if i == split_count - 1 {
                 total_size - 1
             } else {
                 (i as u64 + 1) * part_size - 1
@@ -363,45 +1492,103 @@ impl Downloader {
                 end_byte,
                 current_byte: start_byte,
                 completed: false,
+                tail_checksum: None,
             });
         }
 
-        Ok(DownloadState {
+        DownloadState {
             url: url.to_string(),
             total_size,
             parts,
-        })
+            etag,
+            last_modified,
+            ..Default::default()
+        }
     }
 
-    async fn download_single_connection(&self, url: String, filepath: PathBuf, part_filepath: PathBuf) -> Result<()> {
-         // Fallback to original single connection logic for files without content-length
-         // ... (Simplified version of previous logic)
-         
+    #[allow(clippy::too_many_arguments)]
+    async fn download_single_connection(
+        &self,
+        url: String,
+        source: String,
+        filepath: PathBuf,
+        part_filepath: PathBuf,
+        state_filepath: PathBuf,
+        mut known: DownloadState,
+        auth_headers: header::HeaderMap,
+        file_rate_limiter: Option<Arc<DirectLimiter>>,
+    ) -> Result<()> {
+        // Files without a Content-Length (or compressed in transit) come in one piece.
+        // `known` holds the validators of the file the `.part` was started on, so a resume
+        // only appends when the server confirms it is still the same file.
+        let pause = self.task_token(&url);
+        let accept_encoding = self.accepts_encoding(&filepath);
         let mut downloaded_len = 0;
         if part_filepath.exists() {
             downloaded_len = fs::metadata(&part_filepath).await?.len();
         }
+        // A compressed body can only be decoded from its start
+        if accept_encoding && downloaded_len > 0 {
+            fs::remove_file(&part_filepath).await.context("Failed to remove partial file")?;
+            downloaded_len = 0;
+        }
 
-        let mut request = self.client.get(&url);
+        let if_range = known.if_range().filter(|_| downloaded_len > 0).map(str::to_string);
+        let mut response = self
+            .single_request(&source, &auth_headers, accept_encoding, downloaded_len, if_range.as_deref())
+            .await?;
+        let mut encoding = self.decoded_encoding(&response);
+        let mut complete = false;
         if downloaded_len > 0 {
-            request = request.header(header::RANGE, format!("bytes={}-", downloaded_len));
+            let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+                && encoding.is_none()
+                && unchanged(&known, response.headers());
+            if already_complete(&response, downloaded_len) && unchanged(&known, response.headers()) {
+                // The `.part` already holds the whole file, so there is nothing past its end
+                complete = true;
+                encoding = None;
+            } else if resumed {
+                check_content_range(&response, downloaded_len, None, None)?;
+            } else {
+                // The range was ignored or If-Range found the file changed, and the body is
+                // the whole file; or the ETag differs, or the range counted compressed bytes:
+                // the bytes on disk are no use, start over
+                fs::remove_file(&part_filepath).await.context("Failed to remove partial file")?;
+                if matches!(
+                    response.status(),
+                    reqwest::StatusCode::PARTIAL_CONTENT | reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+                ) {
+                    response = self.single_request(&source, &auth_headers, accept_encoding, 0, None).await?;
+                    encoding = self.decoded_encoding(&response);
+                }
+                downloaded_len = 0;
+            }
         }
-
-        let response = request.send().await.context("Failed to send request")?;
-        let total_size = response.content_length().unwrap_or(0) + downloaded_len;
+        if downloaded_len == 0 && encoding.is_none() {
+            known = self.remember_validators(&url, &response, &state_filepath).await?;
+        }
+        let total_size = match encoding {
+            // Content-Length counts the compressed bytes; only a size from the listing or
+            // an uncompressed HEAD tells the decoded one
+            Some(_) => self.size_map.get(&url).copied().unwrap_or(0),
+            None if complete => downloaded_len,
+            None => response.content_length().unwrap_or(0) + downloaded_len,
+        };
         
         // Update known bytes if we discovered size here AND it wasn't in the map
         if total_size > 0 && !self.size_map.contains_key(&url) {
              self.total_known_bytes.fetch_add(total_size, Ordering::Relaxed);
         }
 
-        let pb = self.multi_progress.add(ProgressBar::new(total_size));
+        let pb = self.file_bar(&url, total_size);
         pb.set_style(ProgressStyle::default_bar()
             .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4} {msg}")
             .unwrap()
             .progress_chars("=>-"));
         pb.set_message(format!("Downloading {}", filepath.file_name().unwrap().to_string_lossy()));
         pb.set_position(downloaded_len);
+        pb.reset_eta();
+        let progress = self.progress_reporter(&url, total_size);
 
         let mut file = OpenOptions::new()
             .create(true)
@@ -411,55 +1598,494 @@ impl Downloader {
             .await
             .context("Failed to open partial file")?;
 
-        let mut stream = response.bytes_stream();
+        let mut stream = match complete {
+            true => futures::stream::empty().boxed(),
+            false => body_stream(response, encoding),
+        };
+        let mut written = downloaded_len;
+        // Bytes of an earlier run already on disk are not part of this run's throughput
+        let mut counted_from = downloaded_len;
+
+        loop {
+            let mut paused = false;
+            // A stall fails the attempt; the file-level retry resumes from the bytes on disk
+            while let Some(item) = within(stream.next(), self.options.chunk_timeout()).await? {
+                let chunk = item.context("Error while downloading chunk")?;
+                let len = chunk.len();
+
+                if len > 0 {
+                    wait_for_bytes(self.rate_limiter.as_deref(), len).await;
+                    wait_for_bytes(file_rate_limiter.as_deref(), len).await;
+
+                    file.write_all(&chunk).await.context("Error while writing to file")?;
+                    written += len as u64;
+                    pb.inc(len as u64);
+                    progress.report(written);
+
+                    // Update global stats for single connection download
+                    self.total_downloaded_bytes.fetch_add(len as u64, Ordering::Relaxed);
+                }
+
+                if pause.is_paused() {
+                    paused = true;
+                    break;
+                }
+            }
+            if !paused {
+                break;
+            }
+
+            // Close the connection while paused, then continue from the bytes already on disk
+            drop(stream);
+            file.flush().await.context("Failed to flush file")?;
+            let name = filepath.file_name().unwrap().to_string_lossy().to_string();
+            pb.set_message(format!("Paused      {}", name));
+            if let Err(e) = self.wait_resumed(&url, &filepath, &pause).await {
+                pb.abandon_with_message(format!("Interrupted {}", name));
+                return Err(e.into());
+            }
+            pb.set_message(format!("Downloading {}", name));
 
-        while let Some(item) = stream.next().await {
-            let chunk = item.context("Error while downloading chunk")?;
-            let len = chunk.len();
+            let resumed = match encoding.is_some() || accept_encoding {
+                false => Some(self.single_request(&source, &auth_headers, false, written, known.if_range()).await?),
+                true => None,
+            };
+            match resumed {
+                Some(response) if already_complete(&response, written) && unchanged(&known, response.headers()) => {
+                    stream = futures::stream::empty().boxed();
+                }
+                Some(response)
+                    if response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+                        && unchanged(&known, response.headers()) =>
+                {
+                    check_content_range(&response, written, None, None)?;
+                    stream = body_stream(response, None);
+                }
+                other => {
+                    // A compressed body cannot continue mid-stream, and a changed file or a
+                    // server ignoring the range sends everything: write it again from the start
+                    let response = match other {
+                        Some(response)
+                            if !matches!(
+                                response.status(),
+                                reqwest::StatusCode::PARTIAL_CONTENT | reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+                            ) =>
+                        {
+                            response
+                        }
+                        _ => self.single_request(&source, &auth_headers, accept_encoding, 0, None).await?,
+                    };
+                    file.set_len(0).await.context("Failed to truncate partial file")?;
+                    self.total_downloaded_bytes.fetch_sub(written - counted_from, Ordering::Relaxed);
+                    written = 0;
+                    counted_from = 0;
+                    pb.set_position(0);
+                    pb.reset_eta();
+                    encoding = self.decoded_encoding(&response);
+                    if encoding.is_none() {
+                        known = self.remember_validators(&url, &response, &state_filepath).await?;
+                    }
+                    stream = body_stream(response, encoding);
+                }
+            }
+        }
+
+        file.flush().await.context("Failed to flush file")?;
+        drop(file);
+
+        self.finish_download(&url, &part_filepath, &filepath, &pb).await?;
+        let _ = fs::remove_file(&state_filepath).await;
+        Ok(())
+    }
+
+    /// Create a file the server reports as empty instead of downloading it, then verify
+    /// it (against the hash of no content) and move it into place like any other.
+    async fn download_empty(&self, url: &str, filepath: &Path, part_filepath: &Path) -> Result<()> {
+        let pb = self.file_bar(url, 0);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4} {msg}")
+            .unwrap()
+            .progress_chars("=>-"));
+        pb.set_message(format!("Downloading {}", filepath.file_name().unwrap().to_string_lossy()));
+        fs::File::create(part_filepath).await.context("Failed to create partial file")?;
+        self.finish_download(url, part_filepath, filepath, &pb).await
+    }
 
-            if len > 0 {
-                if let Some(limiter) = &self.rate_limiter {
-                    if let Some(nonzero) = NonZeroU32::new(len as u32) {
-                        limiter.until_n_ready(nonzero).await.unwrap();
+    /// GET `source` from byte `offset` on (only if still `if_range`, when given), asking
+    /// for a compressed body when `accept_encoding` is set. A 403 re-resolves an expired
+    /// pre-signed address through the provider and asks again from the same offset.
+    async fn single_request(
+        &self,
+        source: &str,
+        auth_headers: &header::HeaderMap,
+        accept_encoding: bool,
+        offset: u64,
+        if_range: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let mut refreshes = 0;
+        loop {
+            match self.send_single(source, auth_headers, accept_encoding, offset, if_range).await {
+                Err(e) if is_forbidden(&e) && refreshes < MAX_URL_REFRESHES => {
+                    if !self.fresh_urls.refresh(source).await.unwrap_or(false) {
+                        return Err(e);
                     }
+                    refreshes += 1;
+                    let _ = self.multi_progress.println(format!(
+                        "Download address of {} expired, continuing from {} with a fresh one", source, HumanBytes(offset)
+                    ));
                 }
+                other => return other,
+            }
+        }
+    }
+
+    async fn send_single(
+        &self,
+        source: &str,
+        auth_headers: &header::HeaderMap,
+        accept_encoding: bool,
+        offset: u64,
+        if_range: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let mut request = match self.fresh_urls.target(source) {
+            Some((url, headers)) => self.client.get(url).headers(headers),
+            None => self.client.get(request_url(source, "GET")?.as_ref()).headers(auth_headers.clone()),
+        };
+        if accept_encoding {
+            request = request.header(header::ACCEPT_ENCODING, ACCEPT_ENCODING);
+        }
+        if offset > 0 {
+            request = request.header(header::RANGE, format!("bytes={}-", offset));
+            if let Some(validator) = if_range {
+                request = request.header(header::IF_RANGE, validator);
+            }
+        }
+        let response = check_throttled(
+            within(self.options.network.send(&self.client, request), self.options.read_timeout)
+                .await?
+                .context("Failed to send request")?,
+        )?;
+        // A resume from the very end is no error; the caller checks `already_complete`
+        if offset > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(response);
+        }
+        Ok(response.error_for_status()?)
+    }
+
+    /// Save the ETag and Last-Modified of a single-connection download starting from
+    /// scratch in its `.part.json`, for the next resume to check.
+    async fn remember_validators(&self, url: &str, response: &reqwest::Response, state_filepath: &Path) -> Result<DownloadState> {
+        let header_value = |name: header::HeaderName| {
+            response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+        };
+        let state = DownloadState {
+            url: url.to_string(),
+            etag: header_value(header::ETAG),
+            last_modified: header_value(header::LAST_MODIFIED),
+            ..Default::default()
+        };
+        state.save(state_filepath).await?;
+        Ok(state)
+    }
+
+    /// Whether `--compress` applies to the file saved at `filepath`.
+    fn accepts_encoding(&self, filepath: &Path) -> bool {
+        self.options.compress && self.options.auto_decompress && crate::encoding::is_text_like(filepath)
+    }
+
+    /// The encoding `response` is decoded from while it is written, if any.
+    fn decoded_encoding(&self, response: &reqwest::Response) -> Option<ContentEncoding> {
+        ContentEncoding::from_headers(response.headers()).filter(|_| self.options.auto_decompress)
+    }
+
+    /// Download from an FTP source over one connection, resuming after the bytes
+    /// already in the partial file.
+    async fn download_ftp(
+        &self,
+        url: String,
+        source: String,
+        filepath: PathBuf,
+        part_filepath: PathBuf,
+        state_filepath: PathBuf,
+        file_rate_limiter: Option<Arc<DirectLimiter>>,
+    ) -> Result<()> {
+        // A part state describes the segments of an HTTP download; FTP can only append
+        if state_filepath.exists() {
+            let _ = fs::remove_file(&state_filepath).await;
+            let _ = fs::remove_file(&part_filepath).await;
+        }
+        let pause = self.task_token(&url);
+        let mut written = fs::metadata(&part_filepath).await.map_or(0, |m| m.len());
+        let mut transfer = within(
+            crate::ftp::retrieve(&source, written, self.options.connect_timeout),
+            self.options.read_timeout,
+        )
+        .await??;
+        let total_size = transfer.size.unwrap_or(0);
+        if total_size > 0 && !self.size_map.contains_key(&url) {
+            self.total_known_bytes.fetch_add(total_size, Ordering::Relaxed);
+        }
+
+        let name = filepath.file_name().unwrap().to_string_lossy().to_string();
+        let pb = self.file_bar(&url, total_size);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4} {msg}")
+            .unwrap()
+            .progress_chars("=>-"));
+        pb.set_message(format!("Downloading {}", name));
+        pb.set_position(written);
+        pb.reset_eta();
+        let progress = self.progress_reporter(&url, total_size);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&part_filepath)
+            .await
+            .context("Failed to open partial file")?;
 
-                file.write_all(&chunk).await.context("Error while writing to file")?;
+        loop {
+            let mut paused = false;
+            while let Some(block) = within(transfer.next(), self.options.chunk_timeout()).await? {
+                let block = block?;
+                let len = block.len();
+                wait_for_bytes(self.rate_limiter.as_deref(), len).await;
+                wait_for_bytes(file_rate_limiter.as_deref(), len).await;
+
+                file.write_all(&block).await.context("Error while writing to file")?;
+                written += len as u64;
                 pb.inc(len as u64);
-                
-                // Update global stats for single connection download
+                progress.report(written);
                 self.total_downloaded_bytes.fetch_add(len as u64, Ordering::Relaxed);
+
+                if pause.is_paused() {
+                    paused = true;
+                    break;
+                }
+            }
+            if !paused {
+                break;
+            }
+
+            // Close the connection while paused, then REST after the bytes already on disk
+            drop(transfer);
+            file.flush().await.context("Failed to flush file")?;
+            pb.set_message(format!("Paused      {}", name));
+            if let Err(e) = self.wait_resumed(&url, &filepath, &pause).await {
+                pb.abandon_with_message(format!("Interrupted {}", name));
+                return Err(e.into());
             }
+            pb.set_message(format!("Downloading {}", name));
+            transfer = within(
+                crate::ftp::retrieve(&source, written, self.options.connect_timeout),
+                self.options.read_timeout,
+            )
+            .await??;
         }
 
         file.flush().await.context("Failed to flush file")?;
         drop(file);
+        if total_size > 0 && written < total_size {
+            // The partial file stays for the retry to resume from
+            return Err(anyhow!("Transfer ended after {} of {} bytes", written, total_size));
+        }
 
-        let expected = self.expected_hashes.get(&url).cloned();
-        if matches!(self.verify_mode, VerifyMode::Off) {
-            tokio::fs::rename(&part_filepath, &filepath).await.context("Failed to rename partial file")?;
-            pb.finish_with_message(format!("Completed   {}", filepath.file_name().unwrap().to_string_lossy()));
-        } else if let Some(_) = expected {
-            pb.set_message(format!("Verifying {}", filepath.file_name().unwrap().to_string_lossy()));
-            let hash = crate::hashing::calculate_hash(&part_filepath).await?;
-            self.verify_hash(&url, &hash, &part_filepath)?;
-            tokio::fs::rename(&part_filepath, &filepath).await.context("Failed to rename partial file")?;
-            pb.finish_with_message(format!("Verified    {} (SHA256: {})", filepath.file_name().unwrap().to_string_lossy(), hash));
-        } else if matches!(self.verify_mode, VerifyMode::On) {
+        self.finish_download(&url, &part_filepath, &filepath, &pb).await
+    }
+
+    /// Copy the blocks the previous version `seed` shares with the new file into the
+    /// partial file and narrow `state` to the ranges still to download. Needs piece
+    /// hashes from the metalink or a `.zsync` control file next to `source`.
+    async fn apply_delta(
+        &self,
+        item: &crate::providers::DownloadItem,
+        source: &str,
+        auth_headers: &header::HeaderMap,
+        seed: &Path,
+        part_filepath: &Path,
+        state: &mut DownloadState,
+    ) -> Result<()> {
+        let sums = match &item.pieces {
+            Some(pieces) => BlockSums::Pieces(pieces.clone()),
+            None => match fetch_zsync(&self.client, &self.options.network, source, auth_headers).await? {
+                Some(index) => BlockSums::Zsync(index),
+                None => bail!("no piece hashes and no .zsync file"),
+            },
+        };
+        let total_size = state.total_size;
+        if !sums.covers(total_size) {
+            bail!("block checksums are for a different version");
+        }
+        let file = std::fs::OpenOptions::new().create(true).write(true).truncate(true).open(part_filepath)?;
+        file.set_len(total_size)?;
+        let seed = seed.to_path_buf();
+        let missing = tokio::task::spawn_blocking(move || reuse_blocks(&seed, &file, &sums, total_size)).await??;
+        let fetched: u64 = missing.iter().map(|(start, end)| end - start + 1).sum();
+        let _ = self.multi_progress.println(format!(
+            "Reusing {} of {} from the previous version of {}",
+            HumanBytes(total_size - fetched),
+            HumanBytes(total_size),
+            item_relative_path(item)?.display()
+        ));
+        state.parts = plan_parts(&missing, total_size, self.options.split);
+        Ok(())
+    }
+
+    /// Download a magnet link or `.torrent` from the swarm. Pieces are checked by the
+    /// engine against the torrent, so there is no whole-file hash to verify.
+    async fn download_torrent(&self, url: &str, source: &str, filepath: &Path) -> Result<()> {
+        let name = filepath.file_name().unwrap().to_string_lossy().to_string();
+        let dir = filepath.parent().unwrap_or(&self.options.output_dir).to_path_buf();
+        let pause = self.task_token(url);
+        let pb = self.file_bar(url, 0);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4} {msg}")
+            .unwrap()
+            .progress_chars("=>-"));
+        pb.set_message(format!("Downloading {}", name));
+
+        let mut progress = None;
+        let mut counted = 0;
+        let result = crate::torrent::download(source, &dir, &pause, |(done, total)| {
+            if total == 0 {
+                return;
+            }
+            let reporter = progress.get_or_insert_with(|| {
+                pb.set_length(total);
+                if !self.size_map.contains_key(url) {
+                    self.total_known_bytes.fetch_add(total, Ordering::Relaxed);
+                }
+                self.progress_reporter(url, total)
+            });
+            pb.set_position(done);
+            reporter.report(done);
+            // The engine may re-check pieces that were already on disk
+            self.total_downloaded_bytes.fetch_add(done.saturating_sub(counted), Ordering::Relaxed);
+            counted = counted.max(done);
+        })
+        .await;
+        if let Err(e) = result {
+            pb.abandon_with_message(format!("Failed      {}", name));
+            return Err(e);
+        }
+        pb.finish_with_message(format!("Completed   {}", name));
+        self.downloaded_files.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Move a finished `.part` file to its final name. With `--sync` (by default for
+    /// verified files) the data and then the rename are flushed to disk, so a power
+    /// loss right after "Completed" cannot leave a truncated file under the final name.
+    async fn complete_file(&self, part_filepath: &Path, filepath: &Path, verified: bool) -> Result<()> {
+        let sync = match self.options.sync {
+            SyncMode::Auto => verified,
+            SyncMode::Always => true,
+            SyncMode::Never => false,
+        };
+        if sync {
+            fs::File::open(part_filepath)
+                .await?
+                .sync_all()
+                .await
+                .context("Failed to flush partial file")?;
+        }
+        move_file(part_filepath, filepath).await.context("Failed to rename partial file")?;
+        if sync {
+            sync_dir(filepath).await.context("Failed to flush directory")?;
+        }
+        Ok(())
+    }
+
+    /// Hash a finished file, turning its bar into a hashing progress bar (bytes
+    /// hashed, speed and ETA) so that large files don't look stuck.
+    async fn verify_file(&self, url: &str, algo: HashAlgorithm, part_filepath: &Path, pb: &ProgressBar, name: &str) -> Result<String> {
+        self.emit(DownloadEvent::Verifying { url: url.to_string(), algorithm: algo });
+        // The download may have had an unknown length; hash against the real size
+        pb.set_length(fs::metadata(part_filepath).await?.len());
+        pb.reset();
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.yellow} [{elapsed_precise}] [{bar:40.yellow/blue}] {bytes:>12}/{total_bytes:<12} {bytes_per_sec:>12} {eta:>4} {msg}")
+            .unwrap()
+            .progress_chars("=>-"));
+        pb.set_message(format!("Verifying   {} ({})", name, algo));
+
+        self.verifying_files.fetch_add(1, Ordering::Relaxed);
+        let bar = pb.clone();
+        let hash = hash_file(part_filepath, algo, move |hashed| bar.set_position(hashed)).await;
+        self.verifying_files.fetch_sub(1, Ordering::Relaxed);
+        hash
+    }
+
+    /// Verify the hash of a finished single-connection download (if required) and
+    /// move it to its final name.
+    async fn finish_download(&self, url: &str, part_filepath: &Path, filepath: &Path, pb: &ProgressBar) -> Result<()> {
+        let expected = self.expected_hash(url);
+        let part_filepath = &part_filepath.to_path_buf();
+        let name = filepath.file_name().unwrap().to_string_lossy().to_string();
+        if matches!(self.options.verify_mode, VerifyMode::Off) {
+            self.complete_file(part_filepath, filepath, false).await?;
+            pb.finish_with_message(format!("Completed   {}", name));
+        } else if let Some((algo, _)) = expected {
+            let hash = self.verify_file(url, algo, part_filepath, pb, &name).await?;
+            self.verify_hash(url, &hash, part_filepath)?;
+            self.complete_file(part_filepath, filepath, true).await?;
+            pb.finish_with_message(format!("Verified    {} ({}: {})", name, algo, hash));
+        } else if matches!(self.options.verify_mode, VerifyMode::On) {
             return Err(anyhow!("缺少哈希：{}", url));
         } else {
-            tokio::fs::rename(&part_filepath, &filepath).await.context("Failed to rename partial file")?;
-            pb.finish_with_message(format!("Completed   {}", filepath.file_name().unwrap().to_string_lossy()));
+            self.complete_file(part_filepath, filepath, false).await?;
+            pb.finish_with_message(format!("Completed   {}", name));
         }
-        
+
         // Update completed files count
         self.downloaded_files.fetch_add(1, Ordering::Relaxed);
-        
+
         Ok(())
     }
 
+    /// What `--if-exists` makes of a final file that is already on disk.
+    async fn check_existing(&self, url: &str, filepath: &Path) -> Result<Existing> {
+        let size = fs::metadata(filepath).await?.len();
+        let expected_size = self.size_map.get(url).copied().filter(|s| *s > 0);
+        // Without a known size there is nothing to compare against
+        let size_ok = expected_size.is_none_or(|expected| expected == size);
+        let keep_if = |ok: bool| if ok { Existing::Skip } else { Existing::Replace };
+        Ok(match self.options.if_exists {
+            IfExists::Skip | IfExists::Rename => Existing::Skip,
+            IfExists::Overwrite => Existing::Replace,
+            IfExists::Resume => match expected_size {
+                Some(expected) if size < expected => Existing::Resume(size),
+                Some(expected) if size > expected => Existing::Replace,
+                _ => Existing::Skip,
+            },
+            IfExists::CheckSize => keep_if(size_ok),
+            IfExists::CheckHash => match self.expected_hash(url) {
+                Some((algo, expected)) if size_ok => {
+                    let hash = crate::hashing::calculate_hash(filepath, algo).await?;
+                    keep_if(hash.eq_ignore_ascii_case(&expected))
+                }
+                Some(_) => Existing::Replace,
+                None => keep_if(size_ok),
+            },
+        })
+    }
+
+    /// Bandwidth cap of one file: its `limit=` field, else `--rate-limit-per-file`.
+    fn file_rate_limiter(&self, item: &crate::providers::DownloadItem) -> Option<Arc<DirectLimiter>> {
+        item.rate_limit
+            .or(self.options.rate_limit_per_file)
+            .and_then(new_limiter)
+    }
+
+    /// Expected digest for `url`, honoring an `algo:` prefix and falling back to `--hash-algo`.
+    fn expected_hash(&self, url: &str) -> Option<(HashAlgorithm, String)> {
+        self.expected_hashes
+            .get(url)
+            .map(|raw| parse_expected_hash(raw, self.options.hash_algo))
+    }
+
     fn verify_hash(&self, url: &str, computed: &str, temp_path: &PathBuf) -> Result<()> {
-        if let Some(expected) = self.expected_hashes.get(url) {
+        if let Some((_, expected)) = self.expected_hash(url) {
             if !expected.eq_ignore_ascii_case(computed) {
                 // Remove corrupted temp file to avoid confusion
                 let _ = std::fs::remove_file(temp_path);
@@ -474,3 +2100,506 @@ impl Downloader {
     }
 
 }
+
+type DirectLimiter = RateLimiter<NotKeyed, InMemoryState, DefaultClock>;
+
+fn new_limiter(bytes_per_sec: u32) -> Option<Arc<DirectLimiter>> {
+    let quota = Quota::per_second(NonZeroU32::new(bytes_per_sec)?);
+    Some(Arc::new(RateLimiter::direct(quota)))
+}
+
+/// Wait until `limiter` allows `len` more bytes. Chunks larger than the burst
+/// (one second worth of bytes) are admitted piecewise instead of failing.
+async fn wait_for_bytes(limiter: Option<&DirectLimiter>, len: usize) {
+    let Some(limiter) = limiter else {
+        return;
+    };
+    let mut remaining = len as u32;
+    while let Some(n) = NonZeroU32::new(remaining) {
+        match limiter.until_n_ready(n).await {
+            Ok(()) => return,
+            Err(InsufficientCapacity(burst)) => {
+                if let Some(burst) = NonZeroU32::new(burst) {
+                    let _ = limiter.until_n_ready(burst).await;
+                }
+                remaining = remaining.saturating_sub(burst);
+            }
+        }
+    }
+}
+
+fn summary_text(
+    downloaded: usize,
+    total_files: usize,
+    bytes: u64,
+    known: u64,
+    verifying: usize,
+    paused: bool,
+    speed: u64,
+) -> String {
+    let throughput = match batch_eta(known.saturating_sub(bytes), speed) {
+        Some(eta) if !paused => format!(" | Speed: {}/s | ETA: {}", HumanBytes(speed), HumanDuration(eta)),
+        _ => String::new(),
+    };
+    format!(
+        "Files: {}/{} | Downloaded: {} / {}{}{}{}",
+        downloaded,
+        total_files,
+        HumanBytes(bytes),
+        HumanBytes(known),
+        throughput,
+        if verifying > 0 { format!(" | Verifying: {}", verifying) } else { String::new() },
+        if paused { " | Paused" } else { "" }
+    )
+}
+
+fn batch_eta(left: u64, speed: u64) -> Option<Duration> {
+    (speed > 0 && left > 0).then(|| Duration::from_secs(left / speed))
+}
+
+/// Location of a download below the output directory. Provider URLs keep their
+/// path inside the repo (e.g. tokenizer/vocab.json), everything else is
+/// flattened to the last URL segment.
+pub fn target_relative_path(url: &str) -> Result<PathBuf> {
+    match crate::providers::relative_path_for(url) {
+        Some(path) => Ok(path),
+        None => Ok(PathBuf::from(sanitize_filename(&get_filename_from_url(url)?))),
+    }
+}
+
+/// Location of `item` below the output directory: its explicit `name` (from the
+/// tasks file or a `Content-Disposition` header) if any, else [`target_relative_path`].
+pub fn item_relative_path(item: &crate::providers::DownloadItem) -> Result<PathBuf> {
+    match item.name.as_deref().and_then(sanitize_relative_path) {
+        Some(path) => Ok(path),
+        None => target_relative_path(&item.url),
+    }
+}
+
+/// Pin `item` below its provider/model directory when `layout` asks for one.
+/// The name is kept in the task line; items already placed there are left alone.
+pub fn apply_layout(item: &mut crate::providers::DownloadItem, layout: Layout) {
+    if layout != Layout::ProviderTree {
+        return;
+    }
+    let (Some(dir), Ok(path)) = (crate::providers::layout_dir(&item.url), item_relative_path(item)) else {
+        return;
+    };
+    if !path.starts_with(&dir) {
+        item.name = Some(dir.join(path).to_string_lossy().replace('\\', "/"));
+    }
+}
+
+/// Reserve `len` bytes on disk for `file` (`posix_fallocate` on Unix,
+/// `SetFileInformationByHandle` on Windows) so the space is really taken and
+/// the data lands contiguously, unlike the sparse file `set_len` may create.
+/// Filesystems without allocation support fall back to `set_len`.
+fn reserve_space(file: &std::fs::File, len: u64) -> std::io::Result<()> {
+    use fs2::FileExt;
+    match file.allocate(len) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => file.set_len(len),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn write_all_at(file: &std::fs::File, buf: &[u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn write_all_at(file: &std::fs::File, mut buf: &[u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_write(buf, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Time constant of the moving average behind the batch speed and ETA in the summary.
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+/// Part state is flushed to disk at most this often, or after this many unsaved bytes
+/// (plus always on completion, pause and failure).
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+const STATE_SAVE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// `--split auto`: segments a new file starts with, the most it may grow to, and
+/// how often throughput is sampled to decide on the next step.
+const AUTO_SPLIT_START: usize = 2;
+pub const AUTO_SPLIT_MAX: usize = 16;
+const AUTO_SPLIT_INTERVAL: Duration = Duration::from_secs(2);
+/// Throughput must beat the best sample by this factor to count as an improvement.
+const AUTO_SPLIT_GAIN: f64 = 1.1;
+
+/// Adjusts the number of segment workers of one file from its throughput.
+///
+/// Every interval, if throughput beat the best sample so far another segment is
+/// split off (up to `max`); if it fell clearly below it, the server is taken as
+/// saturated and one worker leaves after its current part. More segments than
+/// the per-host limit just wait for a permit and add nothing, so growth stops there.
+struct SplitTuner {
+    max: usize,
+    last_position: u64,
+    best_rate: u64,
+}
+
+impl SplitTuner {
+    fn new(ctx: &SegmentContext, max: usize) -> Self {
+        Self { max, last_position: ctx.pb.position(), best_rate: 0 }
+    }
+
+    /// Returns a new part to start a worker on, if one should be added.
+    async fn tick(&mut self, ctx: &SegmentContext) -> Result<Option<usize>> {
+        let position = ctx.pb.position();
+        let rate = position.saturating_sub(self.last_position);
+        self.last_position = position;
+        let workers = ctx.workers.load(Ordering::Relaxed);
+
+        if rate as f64 > self.best_rate as f64 * AUTO_SPLIT_GAIN {
+            self.best_rate = rate;
+            if workers < self.max {
+                ctx.target_workers.store(workers + 1, Ordering::Relaxed);
+                return ctx.steal_work().await;
+            }
+        } else if (rate as f64) * AUTO_SPLIT_GAIN < self.best_rate as f64 && workers > 1 {
+            ctx.target_workers.store(workers - 1, Ordering::Relaxed);
+            self.best_rate = rate;
+        }
+        Ok(None)
+    }
+}
+
+/// Smallest range worth stealing from a slow segment (each half keeps at least this
+/// much, or `--min-split-size` if larger).
+const MIN_STEAL_SIZE: u64 = 1024 * 1024;
+/// 429 answers a part may wait out before they count as failures.
+const MAX_THROTTLED_WAITS: u32 = 20;
+
+/// Shared state of all segment workers of one file.
+struct SegmentContext {
+    client: Client,
+    network: NetworkOptions,
+    sources: Vec<String>,
+    race_mirrors: bool,
+    attempt: u32,
+    token: Option<String>,
+    fresh_urls: Arc<FreshUrls>,
+    if_range: Option<String>,
+    file: Arc<std::fs::File>,
+    state: Mutex<DownloadState>,
+    state_filepath: PathBuf,
+    pb: ProgressBar,
+    rate_limiter: Option<Arc<DirectLimiter>>,
+    /// Limit of this file alone, applied on top of the global one
+    file_rate_limiter: Option<Arc<DirectLimiter>>,
+    total_downloaded_bytes: Arc<AtomicU64>,
+    retries: u32,
+    retry_wait: Duration,
+    read_timeout: Option<Duration>,
+    chunk_timeout: Option<Duration>,
+    pause: PauseToken,
+    throttle: Arc<HostThrottle>,
+    progress: ProgressReporter,
+    /// Neither half of a stolen range may be smaller than this
+    min_steal_size: u64,
+    /// Segment workers currently running
+    workers: AtomicUsize,
+    /// Workers wanted by `--split auto`; extra ones leave after finishing their part
+    target_workers: AtomicUsize,
+    /// Snapshots of the state taken for saving, and the newest one saved so far
+    snapshots: AtomicU64,
+    saved_snapshot: AtomicU64,
+}
+
+impl SegmentContext {
+    /// Source used for a segment; with --race-mirrors every segment picks its own mirror.
+    fn source_for(&self, part_index: usize) -> &str {
+        let offset = if self.race_mirrors { part_index } else { 0 };
+        &self.sources[(offset + self.attempt as usize) % self.sources.len()]
+    }
+
+    /// Save a snapshot of the locked `state`. The lock is released while the part tails
+    /// are read back, so the other workers are not held up by that disk I/O.
+    async fn persist(&self, state: MutexGuard<'_, DownloadState>) -> Result<()> {
+        let mut snapshot = state.clone();
+        let sequence = self.snapshots.fetch_add(1, Ordering::Relaxed) + 1;
+        drop(state);
+        snapshot.bytes_per_sec = Some(self.pb.per_sec() as u64);
+        let file = self.file.clone();
+        let snapshot = tokio::task::spawn_blocking(move || snapshot.record_tails(&file).map(|()| snapshot))
+            .await?
+            .context("Failed to read back downloaded data")?;
+        // Saves still take turns; a snapshot overtaken by a newer one is not written over it
+        let _state = self.state.lock().await;
+        if self.saved_snapshot.load(Ordering::Relaxed) > sequence {
+            return Ok(());
+        }
+        self.saved_snapshot.store(sequence, Ordering::Relaxed);
+        snapshot.save(&self.state_filepath).await
+    }
+
+    fn spawn_worker(self: &Arc<Self>, workers: &mut JoinSet<Result<()>>, part_index: usize) {
+        self.workers.fetch_add(1, Ordering::Relaxed);
+        workers.spawn(self.clone().run_worker(part_index));
+    }
+
+    /// Download a part, then keep taking over work from the slowest parts until none is left.
+    async fn run_worker(self: Arc<Self>, part_index: usize) -> Result<()> {
+        let mut index = part_index;
+        let result = loop {
+            if let Err(e) = self.download_part(index).await {
+                break Err(e);
+            }
+            // Leave when the tuner wants fewer workers; the parts left all have a worker
+            let target = self.target_workers.load(Ordering::Relaxed);
+            if self
+                .workers
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| (n > target).then(|| n - 1))
+                .is_ok()
+            {
+                return Ok(());
+            }
+            match self.steal_work().await {
+                Ok(Some(next)) => index = next,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.workers.fetch_sub(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Split the largest remaining range in half and return the new part holding the upper half.
+    async fn steal_work(&self) -> Result<Option<usize>> {
+        let mut s = self.state.lock().await;
+        let victim = s
+            .parts
+            .iter()
+            .filter(|p| !p.completed)
+            .max_by_key(|p| (p.end_byte + 1).saturating_sub(p.current_byte))
+            .map(|p| (p.index, p.current_byte, p.end_byte));
+        let Some((victim_index, current_byte, end_byte)) = victim else {
+            return Ok(None);
+        };
+
+        let remaining = (end_byte + 1).saturating_sub(current_byte);
+        if remaining < 2 * self.min_steal_size {
+            return Ok(None);
+        }
+
+        // The victim notices its shortened end on its next chunk and stops there
+        let split_at = current_byte + remaining / 2;
+        s.parts[victim_index].end_byte = split_at - 1;
+        let index = s.parts.len();
+        s.parts.push(PartState {
+            index,
+            start_byte: split_at,
+            end_byte,
+            current_byte: split_at,
+            completed: false,
+            tail_checksum: None,
+        });
+        self.persist(s).await?;
+        Ok(Some(index))
+    }
+
+    async fn download_part(&self, part_index: usize) -> Result<()> {
+        let url = self.source_for(part_index).to_string();
+        let auth_headers = crate::providers::download_headers_for(&url, self.token.as_deref(), &self.network).await;
+        let mut current_pos = self.state.lock().await.parts[part_index].current_byte;
+        let mut attempt = 0;
+        let mut throttled_waits = 0;
+        let mut refreshes = 0;
+        let mut refreshed_at = None;
+        let mut last_save = Instant::now();
+        let mut unsaved_bytes: u64 = 0;
+
+        loop {
+            let result = async {
+                if self.pause.is_paused() {
+                    return Err(Paused.into());
+                }
+                // The end may shrink at any time when another worker steals the tail of this part
+                let end = self.state.lock().await.parts[part_index].end_byte;
+                if current_pos > end {
+                    return Ok(());
+                }
+
+                // Held while streaming: limits how many segments hit a throttled host at once
+                let _permit = self.throttle.acquire(&url).await;
+                let range_header = format!("bytes={}-{}", current_pos, end);
+                let request = match self.fresh_urls.target(&url) {
+                    Some((fresh, headers)) => self.client.get(fresh).headers(headers),
+                    None => self.client.get(request_url(&url, "GET")?.as_ref()).headers(auth_headers.clone()),
+                };
+                let mut request = request.header(header::RANGE, range_header);
+                if let Some(validator) = &self.if_range {
+                    request = request.header(header::IF_RANGE, validator.as_str());
+                }
+
+                let response = check_throttled(
+                    within(self.network.send(&self.client, request), self.read_timeout)
+                        .await?
+                        .context("Failed to send request")?,
+                )?
+                    .error_for_status()?;
+                // A full 200 body means the range was not honored: with If-Range
+                // the remote file changed, without it the server ignores ranges.
+                if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                    return Err(RemoteChanged.into());
+                }
+                let total_size = self.state.lock().await.total_size;
+                let range_end = check_content_range(&response, current_pos, Some(end), Some(total_size))?;
+                let mut stream = response.bytes_stream();
+
+                // A stall fails this attempt; the retry below resumes from `current_pos`
+                while let Some(item) = within(stream.next(), self.chunk_timeout).await? {
+                    let chunk = item.context("Error while downloading chunk")?;
+                    if current_pos + chunk.len() as u64 > range_end + 1 {
+                        return Err(BadRange(format!("more than the bytes up to {} its Content-Range announced", range_end)).into());
+                    }
+                    if !chunk.is_empty() {
+                        wait_for_bytes(self.rate_limiter.as_deref(), chunk.len()).await;
+                        wait_for_bytes(self.file_rate_limiter.as_deref(), chunk.len()).await;
+                    }
+                    // Read the end only now: a steal may have shortened it while this chunk was on its way
+                    let end = self.state.lock().await.parts[part_index].end_byte;
+                    let remaining = (end + 1).saturating_sub(current_pos);
+                    let data = chunk.slice(..(chunk.len() as u64).min(remaining) as usize);
+                    let len = data.len() as u64;
+
+                    if len > 0 {
+                        // Positional write: segments write concurrently without sharing a cursor
+                        let file = self.file.clone();
+                        let offset = current_pos;
+                        tokio::task::spawn_blocking(move || write_all_at(&file, &data, offset))
+                            .await?
+                            .context("Error while writing to file")?;
+                    }
+
+                    let done = {
+                        let mut s = self.state.lock().await;
+                        let p = &mut s.parts[part_index];
+                        // A steal during the write moves the end once more; the bytes past it are
+                        // the same ones the new part writes, and only that part counts them
+                        let len = len.min((p.end_byte + 1).saturating_sub(current_pos));
+                        if len > 0 {
+                            current_pos += len;
+                            unsaved_bytes += len;
+                            self.pb.inc(len);
+                            self.progress.report(self.pb.position());
+                            self.total_downloaded_bytes.fetch_add(len, Ordering::Relaxed);
+                        }
+                        p.current_byte = current_pos;
+                        if p.current_byte > p.end_byte {
+                            p.completed = true;
+                        }
+                        let done = p.completed;
+
+                        // Save state to file (throttled)
+                        if done
+                            || unsaved_bytes >= STATE_SAVE_BYTES
+                            || last_save.elapsed() >= STATE_SAVE_INTERVAL
+                        {
+                            self.persist(s).await?;
+                            last_save = Instant::now();
+                            unsaved_bytes = 0;
+                        }
+                        done
+                    };
+                    if done {
+                        // Drop the connection even if the server would send more (stolen tail)
+                        return Ok(());
+                    }
+
+                    // Stop reading and drop the connection; the caller resumes from the saved offset
+                    if self.pause.is_paused() {
+                        return Err(Paused.into());
+                    }
+                }
+
+                let end = self.state.lock().await.parts[part_index].end_byte;
+                if current_pos <= end {
+                    return Err(anyhow!("Connection closed at byte {} (expected up to {})", current_pos, end));
+                }
+                Ok::<(), anyhow::Error>(())
+            }
+            .await;
+
+            // An expired pre-signed address is not a failure of this part either; only
+            // refreshes in a row without a byte downloaded in between are limited
+            let refreshed = match &result {
+                Err(e) if is_forbidden(e) => {
+                    if refreshed_at != Some(current_pos) {
+                        refreshes = 0;
+                    }
+                    refreshes < MAX_URL_REFRESHES && self.fresh_urls.refresh(&url).await.unwrap_or(false)
+                }
+                _ => false,
+            };
+
+            match result {
+                Ok(()) => break,
+                Err(e) if e.is::<Paused>() => {
+                    self.persist(self.state.lock().await).await?;
+                    return Err(e);
+                }
+                Err(_) if refreshed => {
+                    refreshes += 1;
+                    refreshed_at = Some(current_pos);
+                    self.pb.println(format!(
+                        "Part {} download address expired, continuing from byte {} with a fresh one",
+                        part_index, current_pos
+                    ));
+                }
+                Err(e) if e.is::<Throttled>() && throttled_waits < MAX_THROTTLED_WAITS => {
+                    // Throttling is not a failure of this part: wait as told instead of spending a retry
+                    self.persist(self.state.lock().await).await?;
+                    throttled_waits += 1;
+                    let throttled = e.downcast_ref::<Throttled>().expect("checked above");
+                    let wait = self.throttle.throttled(&url, throttled, backoff_delay(self.retry_wait, throttled_waits));
+                    self.pb.println(format!(
+                        "Part {} throttled: {} (waiting {:.1}s)",
+                        part_index, e, wait.as_secs_f64()
+                    ));
+                    tokio::time::sleep(wait).await;
+                }
+                // A changed file answers every retry of the same If-Range in full; restart at once
+                Err(e) if attempt < self.retries && !e.is::<BadRange>() && !e.is::<RemoteChanged>() => {
+                    // Keep the progress made before the failure
+                    self.persist(self.state.lock().await).await?;
+                    attempt += 1;
+                    let delay = backoff_delay(self.retry_wait, attempt);
+                    self.pb.println(format!(
+                        "Part {} failed: {} (retry {}/{} in {:.1}s)",
+                        part_index, e, attempt, self.retries, delay.as_secs_f64()
+                    ));
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    let _ = self.persist(self.state.lock().await).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        // Mark part as completed
+        let mut s = self.state.lock().await;
+        let p = &mut s.parts[part_index];
+        p.completed = true;
+        p.current_byte = p.end_byte + 1; // Ensure it marks as fully done
+        self.persist(s).await?;
+        Ok(())
+    }
+}
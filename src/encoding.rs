@@ -0,0 +1,71 @@
+//! Compressed transfer encodings (`Content-Encoding: gzip`, `deflate`, `zstd`).
+//!
+//! With `--compress` text-like files are requested with `Accept-Encoding` and
+//! decoded while they are written; a body the server compresses without being
+//! asked is decoded too unless `--no-auto-decompress` keeps the bytes as sent.
+//! Either way sizes and hashes refer to the file on disk.
+
+use async_compression::tokio::bufread::{GzipDecoder, ZlibDecoder, ZstdDecoder};
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::header::{self, HeaderMap};
+use std::io;
+use std::path::Path;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// `Accept-Encoding` sent with `--compress`.
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, zstd";
+
+/// Extensions of files that usually compress well; binaries such as weights,
+/// archives and images are left alone.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "json", "jsonl", "ndjson", "txt", "csv", "tsv", "md", "rst", "xml", "html", "htm", "yaml", "yml", "toml",
+    "ini", "cfg", "py", "js", "css", "svg", "log", "sql", "vocab",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    /// HTTP's `deflate` is a zlib stream
+    Deflate,
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// The encoding of a response body; `None` for `identity` and encodings rdl cannot decode.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(header::CONTENT_ENCODING)?.to_str().ok()?;
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `--compress` asks for a compressed transfer of `path`.
+pub fn is_text_like(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    TEXT_EXTENSIONS.iter().any(|ext| name.ends_with(&format!(".{}", ext)))
+}
+
+/// The body of `response`, decoded when `encoding` is set.
+pub fn body_stream(response: reqwest::Response, encoding: Option<ContentEncoding>) -> BoxStream<'static, io::Result<Bytes>> {
+    let body = response.bytes_stream().map_err(io::Error::other);
+    let Some(encoding) = encoding else {
+        return body.boxed();
+    };
+    let reader = StreamReader::new(body);
+    match encoding {
+        ContentEncoding::Gzip => {
+            let mut decoder = GzipDecoder::new(reader);
+            // Concatenated gzip members decode to the concatenated contents, as with gunzip
+            decoder.multiple_members(true);
+            ReaderStream::new(decoder).boxed()
+        }
+        ContentEncoding::Deflate => ReaderStream::new(ZlibDecoder::new(reader)).boxed(),
+        ContentEncoding::Zstd => ReaderStream::new(ZstdDecoder::new(reader)).boxed(),
+    }
+}
@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::hashing::HashAlgorithm;
+
+/// Events buffered per subscriber before the oldest ones are dropped.
+pub const EVENT_CAPACITY: usize = 1024;
+/// Minimum time between two `Progress` events of the same file.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What happened to a file, broadcast to every [`Downloader::subscribe`](crate::Downloader::subscribe)r.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DownloadEvent {
+    Started {
+        url: String,
+        path: PathBuf,
+        total: Option<u64>,
+    },
+    Progress {
+        url: String,
+        bytes: u64,
+        total: Option<u64>,
+    },
+    Verifying {
+        url: String,
+        algorithm: HashAlgorithm,
+    },
+    /// `--extract` is unpacking the downloaded archive into `into`
+    Extracting {
+        url: String,
+        into: PathBuf,
+    },
+    Completed {
+        url: String,
+        path: PathBuf,
+        bytes: u64,
+        /// The file was already there (`--if-exists`, or downloaded to another path before)
+        skipped: bool,
+    },
+    Failed {
+        url: String,
+        error: String,
+    },
+}
+
+pub type EventSender = broadcast::Sender<DownloadEvent>;
+
+/// Emits `Progress` events for one file, at most every [`PROGRESS_INTERVAL`].
+pub(crate) struct ProgressReporter {
+    tx: EventSender,
+    url: String,
+    total: Option<u64>,
+    last: Mutex<Option<Instant>>,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(tx: EventSender, url: String, total: Option<u64>) -> Self {
+        Self { tx, url, total, last: Mutex::new(None) }
+    }
+
+    pub(crate) fn report(&self, bytes: u64) {
+        if self.tx.receiver_count() == 0 {
+            return;
+        }
+        {
+            let mut last = self.last.lock().unwrap();
+            if last.is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        let _ = self.tx.send(DownloadEvent::Progress {
+            url: self.url.clone(),
+            bytes,
+            total: self.total,
+        });
+    }
+}
@@ -0,0 +1,212 @@
+//! `--extract`: unpack downloaded `.zip`, `.tar`, `.tar.gz` and `.tar.zst`
+//! archives next to them once their hash (and signature) checked out, and
+//! `--decompress`: turn single `.gz`, `.xz` and `.zst` files into what they contain.
+//!
+//! Entries are only ever written below the target directory: absolute paths,
+//! `..` components and links pointing outside fail the extraction.
+
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveKind {
+    /// The kind of archive `path` is, going by its name.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Self::TarZst)
+        } else {
+            None
+        }
+    }
+}
+
+/// Compression of a single file (`--decompress`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression of `path` and the path it decompresses to, going by its extension
+    /// (`shard-00001.jsonl.zst` becomes `shard-00001.jsonl`).
+    pub fn from_path(path: &Path) -> Option<(Self, PathBuf)> {
+        let compression = match path.extension()?.to_string_lossy().to_ascii_lowercase().as_str() {
+            "gz" => Self::Gzip,
+            "xz" => Self::Xz,
+            "zst" => Self::Zstd,
+            _ => return None,
+        };
+        Some((compression, path.with_extension("")))
+    }
+}
+
+/// Decompress `input` into `output` on a blocking thread and return the bytes written.
+/// `progress` gets the compressed bytes read so far.
+pub async fn decompress_file(
+    input: &Path,
+    compression: Compression,
+    output: &Path,
+    progress: impl FnMut(u64) + Send + 'static,
+) -> Result<u64> {
+    let (input, output) = (input.to_path_buf(), output.to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        let file = File::open(&input).with_context(|| format!("Failed to open {:?}", input))?;
+        let reader = Counting { inner: BufReader::new(file), read: 0, progress };
+        let mut decoder: Box<dyn Read> = match compression {
+            // Concatenated members and streams decode to the concatenated contents
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        };
+        let mut out = File::create(&output).with_context(|| format!("Failed to create {:?}", output))?;
+        io::copy(&mut decoder, &mut out).with_context(|| format!("Failed to decompress {:?}", input))
+    })
+    .await?
+}
+
+/// Unpack `archive` into `dest` on a blocking thread and return the number of files
+/// written. `progress` gets the bytes of the archive read so far.
+pub async fn extract_archive(
+    archive: &Path,
+    kind: ArchiveKind,
+    dest: &Path,
+    progress: impl FnMut(u64) + Send + 'static,
+) -> Result<usize> {
+    let (archive, dest) = (archive.to_path_buf(), dest.to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        let file = File::open(&archive).with_context(|| format!("Failed to open {:?}", archive))?;
+        let reader = Counting { inner: BufReader::new(file), read: 0, progress };
+        std::fs::create_dir_all(&dest).with_context(|| format!("Failed to create {:?}", dest))?;
+        match kind {
+            ArchiveKind::Zip => extract_zip(reader, &dest),
+            ArchiveKind::Tar => extract_tar(reader, &dest),
+            ArchiveKind::TarGz => extract_tar(flate2::read::MultiGzDecoder::new(reader), &dest),
+            ArchiveKind::TarZst => extract_tar(zstd::stream::read::Decoder::new(reader)?, &dest),
+        }
+    })
+    .await?
+}
+
+fn extract_zip<R: Read + io::Seek>(reader: R, dest: &Path) -> Result<usize> {
+    let mut zip = zip::ZipArchive::new(reader).context("Not a zip archive")?;
+    let mut files = 0;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        let Some(name) = entry.enclosed_name().map(Path::to_path_buf) else {
+            bail!("Refusing to extract {:?} outside the target directory", entry.name());
+        };
+        let path = enclosed(dest, &name)?;
+        if entry.is_dir() {
+            std::fs::create_dir_all(&path).with_context(|| format!("Failed to create {:?}", path))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        let mut out = File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+        io::copy(&mut entry, &mut out).with_context(|| format!("Failed to extract {:?}", name))?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o777));
+        }
+        files += 1;
+    }
+    Ok(files)
+}
+
+fn extract_tar<R: Read>(reader: R, dest: &Path) -> Result<usize> {
+    let mut tar = tar::Archive::new(reader);
+    tar.set_preserve_permissions(false);
+    let mut files = 0;
+    for entry in tar.entries().context("Not a tar archive")? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let path = enclosed(dest, &name)?;
+        let entry_type = entry.header().entry_type();
+        if let Some(target) = entry.link_name()? {
+            // Symlink targets are relative to the link, hard link targets to the archive root
+            let base = if entry_type.is_symlink() {
+                name.parent().unwrap_or(Path::new("")).to_path_buf()
+            } else {
+                PathBuf::new()
+            };
+            if target.is_absolute() || normalize(&base.join(&target)).is_none() {
+                bail!("Refusing to extract link {:?} -> {:?} pointing outside the target directory", name, target);
+            }
+        }
+        if !entry.unpack_in(dest).with_context(|| format!("Failed to extract {:?}", name))? {
+            bail!("Refusing to extract {:?} outside the target directory", name);
+        }
+        if entry_type.is_file() && path.is_file() {
+            files += 1;
+        }
+    }
+    Ok(files)
+}
+
+/// `name` below `dest`, or an error when it is absolute or climbs out with `..`.
+fn enclosed(dest: &Path, name: &Path) -> Result<PathBuf> {
+    match normalize(name) {
+        Some(relative) if name.is_relative() => Ok(dest.join(relative)),
+        _ => bail!("Refusing to extract {:?} outside the target directory", name),
+    }
+}
+
+/// `path` with `.` and `..` resolved, or `None` when it leaves its root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.iter().collect())
+}
+
+/// Reports how much of the archive has been read.
+struct Counting<R, F> {
+    inner: R,
+    read: u64,
+    progress: F,
+}
+
+impl<R: Read, F: FnMut(u64)> Read for Counting<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        (self.progress)(self.read);
+        Ok(n)
+    }
+}
+
+impl<R: io::Seek, F> io::Seek for Counting<R, F> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let position = self.inner.seek(pos)?;
+        self.read = position;
+        Ok(position)
+    }
+}
@@ -0,0 +1,130 @@
+//! FTP and FTPS (`ftp://`, `ftps://`) sources for mirrors that only speak FTP.
+//!
+//! FTP has no ranged requests to split a file over, so these downloads use a
+//! single connection: `SIZE` gives the total, `REST` resumes after the bytes
+//! already on disk. `ftps://` upgrades the control and data connections with
+//! `AUTH TLS` (explicit FTPS). Credentials come from the URL, else the login is
+//! anonymous.
+//!
+//! suppaftp is blocking, so each transfer runs on the blocking thread pool and
+//! hands its data over a channel; dropping the [`FtpTransfer`] ends it.
+
+use anyhow::{anyhow, Context, Result};
+use std::io::Read;
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+use suppaftp::types::FileType;
+use suppaftp::{NativeTlsConnector, NativeTlsFtpStream};
+use tokio::sync::mpsc;
+use url::Url;
+
+use crate::utils::percent_decode;
+
+/// Size of the blocks read from the data connection
+const READ_SIZE: usize = 64 * 1024;
+/// Blocks buffered between the transfer thread and the writer
+const CHANNEL_BLOCKS: usize = 16;
+
+pub fn is_ftp_url(url: &str) -> bool {
+    let scheme = url.split_once("://").map_or("", |(scheme, _)| scheme);
+    scheme.eq_ignore_ascii_case("ftp") || scheme.eq_ignore_ascii_case("ftps")
+}
+
+/// A running `RETR`.
+pub struct FtpTransfer {
+    /// Size reported by `SIZE`, if the server supports it
+    pub size: Option<u64>,
+    chunks: mpsc::Receiver<Result<Vec<u8>>>,
+}
+
+impl FtpTransfer {
+    /// The next block of data, `None` once the file is complete.
+    pub async fn next(&mut self) -> Option<Result<Vec<u8>>> {
+        self.chunks.recv().await
+    }
+}
+
+/// Size of the remote file via `SIZE` (`None` when the server does not tell).
+pub async fn remote_size(url: &str, connect_timeout: Duration) -> Result<Option<u64>> {
+    let url = Url::parse(url)?;
+    tokio::task::spawn_blocking(move || {
+        let (mut ftp, path) = connect(&url, connect_timeout)?;
+        let size = ftp.size(&path).ok().map(|s| s as u64);
+        let _ = ftp.quit();
+        Ok(size)
+    })
+    .await?
+}
+
+/// Start downloading `url` from byte `offset` on.
+pub async fn retrieve(url: &str, offset: u64, connect_timeout: Duration) -> Result<FtpTransfer> {
+    let url = Url::parse(url)?;
+    let (tx, chunks) = mpsc::channel(CHANNEL_BLOCKS);
+    let (started_tx, started) = tokio::sync::oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+        let opened = connect(&url, connect_timeout).and_then(|(mut ftp, path)| {
+            let size = ftp.size(&path).ok().map(|s| s as u64);
+            if offset > 0 {
+                ftp.resume_transfer(offset as usize).context("Server does not support resuming (REST)")?;
+            }
+            let stream = ftp.retr_as_stream(&path).with_context(|| format!("RETR {} failed", path))?;
+            Ok((ftp, stream, size))
+        });
+        let (mut ftp, mut stream, size) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                let _ = started_tx.send(Err(e));
+                return;
+            }
+        };
+        if started_tx.send(Ok(size)).is_err() {
+            return;
+        }
+        let mut buf = vec![0u8; READ_SIZE];
+        loop {
+            let block = match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => Ok(buf[..n].to_vec()),
+                Err(e) => Err(anyhow!(e).context("Error while downloading chunk")),
+            };
+            let failed = block.is_err();
+            // The receiver is gone when the download was paused, cancelled or timed out
+            if tx.blocking_send(block).is_err() || failed {
+                return;
+            }
+        }
+        if let Err(e) = ftp.finalize_retr_stream(stream) {
+            let _ = tx.blocking_send(Err(anyhow!(e).context("Transfer did not complete")));
+            return;
+        }
+        let _ = ftp.quit();
+    });
+    let size = started.await.map_err(|_| anyhow!("FTP transfer ended unexpectedly"))??;
+    Ok(FtpTransfer { size, chunks })
+}
+
+/// Log in to the server of `url` and return the session with the decoded remote path.
+fn connect(url: &Url, connect_timeout: Duration) -> Result<(NativeTlsFtpStream, String)> {
+    let host = url.host_str().ok_or_else(|| anyhow!("No host in {}", url))?;
+    let secure = url.scheme().eq_ignore_ascii_case("ftps");
+    let port = url.port().unwrap_or(21);
+    let addr = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Could not resolve {}", host))?;
+    let mut ftp = NativeTlsFtpStream::connect_timeout(addr, connect_timeout)
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+    if secure {
+        let tls = native_tls::TlsConnector::new()?;
+        ftp = ftp
+            .into_secure(NativeTlsConnector::from(tls), host)
+            .context("AUTH TLS failed")?;
+    }
+    let (user, password) = match url.username() {
+        "" => ("anonymous".to_string(), "anonymous@".to_string()),
+        user => (percent_decode(user), percent_decode(url.password().unwrap_or_default())),
+    };
+    ftp.login(&user, &password).with_context(|| format!("FTP login as {} failed", user))?;
+    ftp.transfer_type(FileType::Binary)?;
+    Ok((ftp, percent_decode(url.path())))
+}
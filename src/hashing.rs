@@ -1,21 +1,192 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
 use sha2::{Sha256, Digest};
-use std::path::PathBuf;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::io::Read;
 
-pub async fn calculate_hash(filepath: &PathBuf) -> Result<String> {
-    let mut file = File::open(filepath).await?;
-    let mut hasher = Sha256::new();
-    let mut buffer = [0; 8192];
+/// Bytes read at a time while hashing a file
+const HASH_BUFFER_SIZE: usize = 4 * 1024 * 1024;
 
-    loop {
-        let n = file.read(&mut buffer).await?;
-        if n == 0 {
-            break;
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub(crate) fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix.to_lowercase().as_str() {
+            "sha256" => Some(Self::Sha256),
+            "sha1" => Some(Self::Sha1),
+            "md5" => Some(Self::Md5),
+            "blake3" => Some(Self::Blake3),
+            _ => None,
         }
-        hasher.update(&buffer[..n]);
     }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Sha256 => "SHA256",
+            Self::Sha1 => "SHA1",
+            Self::Md5 => "MD5",
+            Self::Blake3 => "BLAKE3",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Hash verification policy: auto (only when a hash is provided), on (require a hash), off (skip).
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyMode {
+    Auto,
+    On,
+    Off,
+}
+
+/// Checksum files written into the output directory after a batch, readable by `sha256sum -c`.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumManifest {
+    /// One `SHA256SUMS` file listing every file of the batch
+    Sums,
+    /// A `<file>.sha256` next to every file
+    Files,
+    /// Both of the above
+    Both,
+}
+
+pub const SHA256SUMS: &str = "SHA256SUMS";
+
+/// Hash `files` (relative to `output_dir`) and write the requested manifest in `sha256sum` format.
+/// Files that do not exist are left out; returns how many were listed.
+pub async fn write_checksum_manifest(output_dir: &Path, files: &[PathBuf], kind: ChecksumManifest) -> Result<usize> {
+    let mut lines = Vec::new();
+    for relative in files {
+        let path = output_dir.join(relative);
+        if !path.is_file() {
+            continue;
+        }
+        let digest = calculate_hash(&path, HashAlgorithm::Sha256)
+            .await
+            .context(format!("Failed to hash {:?}", path))?;
 
-    Ok(hex::encode(hasher.finalize()))
-}
\ No newline at end of file
+        if kind != ChecksumManifest::Sums {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(".sha256");
+            tokio::fs::write(&sidecar, format!("{}  {}\n", digest, name))
+                .await
+                .context(format!("Failed to write {:?}", sidecar))?;
+        }
+        // `sha256sum -c` expects forward slashes on every platform
+        lines.push(format!("{}  {}", digest, relative.to_string_lossy().replace('\\', "/")));
+    }
+
+    if kind != ChecksumManifest::Files && !lines.is_empty() {
+        let manifest = output_dir.join(SHA256SUMS);
+        tokio::fs::write(&manifest, lines.join("\n") + "\n")
+            .await
+            .context(format!("Failed to write {:?}", manifest))?;
+    }
+    Ok(lines.len())
+}
+
+/// Split an expected hash like `md5:abcd...` into algorithm and hex digest.
+/// Unprefixed values use `default`.
+pub fn parse_expected_hash(raw: &str, default: HashAlgorithm) -> (HashAlgorithm, String) {
+    if let Some((prefix, digest)) = raw.split_once(':') {
+        if let Some(algo) = HashAlgorithm::from_prefix(prefix) {
+            return (algo, digest.trim().to_string());
+        }
+    }
+    (default, raw.trim().to_string())
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algo: HashAlgorithm) -> Self {
+        match algo {
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgorithm::Sha1 => Self::Sha1(Sha1::new()),
+            HashAlgorithm::Md5 => Self::Md5(Md5::new()),
+            HashAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(h) => Digest::update(h, data),
+            Self::Sha1(h) => Digest::update(h, data),
+            Self::Md5(h) => Digest::update(h, data),
+            // Spread over all cores; only pays off for the large buffers of `hash_file`
+            #[cfg(feature = "parallel-blake3")]
+            Self::Blake3(h) => {
+                h.update_rayon(data);
+            }
+            #[cfg(not(feature = "parallel-blake3"))]
+            Self::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Self::Sha256(h) => hex::encode(h.finalize()),
+            Self::Sha1(h) => hex::encode(h.finalize()),
+            Self::Md5(h) => hex::encode(h.finalize()),
+            Self::Blake3(h) => h.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Hex digest of `data`, for hashes of single pieces.
+pub fn hash_bytes(data: &[u8], algo: HashAlgorithm) -> String {
+    let mut hasher = Hasher::new(algo);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+pub async fn calculate_hash(filepath: &Path, algo: HashAlgorithm) -> Result<String> {
+    hash_file(filepath, algo, |_| {}).await
+}
+
+/// Hash a file on a blocking thread, so hashing a 100 GB file neither stalls the
+/// downloads running on the runtime nor reads it in small pieces. `progress` gets
+/// the bytes hashed so far after every buffer.
+pub async fn hash_file(filepath: &Path, algo: HashAlgorithm, mut progress: impl FnMut(u64) + Send + 'static) -> Result<String> {
+    let filepath = filepath.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&filepath)?;
+        let mut hasher = Hasher::new(algo);
+        let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+        let mut hashed = 0u64;
+        loop {
+            let n = file.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+            hashed += n as u64;
+            progress(hashed);
+        }
+        Ok(hasher.finalize())
+    })
+    .await?
+}
@@ -0,0 +1,262 @@
+//! `--on-complete-cmd`, `--on-error-cmd`, `--webhook` and `--notify`: notify
+//! scripts, services and the desktop when a file or a whole batch finishes.
+//!
+//! Every hook receives the same JSON payload: commands read it on stdin (and the
+//! most useful fields as `RDL_*` environment variables), webhooks get it as the
+//! body of a `POST`. The `text` field is a one-line summary, which is what chat
+//! webhooks such as Slack's display.
+
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use rdl_core::downloader::{item_relative_path, DownloadOptions};
+use rdl_core::events::DownloadEvent;
+use rdl_core::providers::DownloadItem;
+
+#[derive(Serialize, Debug)]
+struct HookPayload {
+    /// `file` or `batch`
+    scope: &'static str,
+    /// `completed` or `failed`
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    /// Saved file, or the download directory for a batch
+    file: PathBuf,
+    /// Bytes saved (for a batch: by its completed files)
+    size: u64,
+    duration_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed: Option<usize>,
+    text: String,
+}
+
+/// Hooks of one batch. Each one runs in its own task so slow scripts do not hold
+/// up the downloads; [`finish`](Self::finish) waits for all of them.
+pub struct Hooks {
+    on_complete: Option<String>,
+    on_error: Option<String>,
+    webhook: Option<String>,
+    /// Desktop notification for the batch
+    notify: bool,
+    client: Client,
+    output_dir: PathBuf,
+    started: Instant,
+    bytes: AtomicU64,
+    running: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Hooks {
+    /// `None` when no hook is configured.
+    pub fn new(options: &DownloadOptions) -> Option<Arc<Self>> {
+        if options.on_complete_cmd.is_none()
+            && options.on_error_cmd.is_none()
+            && options.webhook.is_none()
+            && !options.notify
+        {
+            return None;
+        }
+        Some(Arc::new(Self {
+            on_complete: options.on_complete_cmd.clone(),
+            on_error: options.on_error_cmd.clone(),
+            webhook: options.webhook.clone(),
+            notify: options.notify,
+            client: options.client_builder().build().unwrap_or_else(|_| Client::new()),
+            output_dir: options.output_dir.clone(),
+            started: Instant::now(),
+            bytes: AtomicU64::new(0),
+            running: Mutex::new(vec![]),
+        }))
+    }
+
+    /// Fire the file hooks for every file of `items` that completes or fails.
+    /// Ends once `items.len()` files have finished or the downloader is gone.
+    pub async fn watch(self: Arc<Self>, mut events: broadcast::Receiver<DownloadEvent>, items: Vec<DownloadItem>) {
+        let files: HashMap<String, DownloadItem> = items.into_iter().map(|i| (i.url.clone(), i)).collect();
+        let mut started: HashMap<String, Instant> = HashMap::new();
+        let mut finished = 0;
+        while finished < files.len() {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let (url, completed) = match event {
+                DownloadEvent::Started { url, .. } => {
+                    started.insert(url, Instant::now());
+                    continue;
+                }
+                DownloadEvent::Completed { url, bytes, .. } => (url, Ok(bytes)),
+                DownloadEvent::Failed { url, error } => (url, Err(error)),
+                _ => continue,
+            };
+            finished += 1;
+            let duration = started.remove(&url).map_or(0.0, |t| t.elapsed().as_secs_f64());
+            let item = files.get(&url);
+            let file = item
+                .and_then(|i| item_relative_path(i).ok())
+                .map(|p| self.output_dir.join(p))
+                .unwrap_or_default();
+            let hash = item.and_then(|i| i.hash.clone());
+            let payload = match completed {
+                Ok(bytes) => {
+                    self.bytes.fetch_add(bytes, Ordering::Relaxed);
+                    HookPayload {
+                        scope: "file",
+                        status: "completed",
+                        text: format!("rdl: downloaded {}", file.display()),
+                        url: Some(url),
+                        file,
+                        size: bytes,
+                        duration_secs: duration,
+                        hash,
+                        error: None,
+                        completed: None,
+                        failed: None,
+                    }
+                }
+                Err(error) => HookPayload {
+                    scope: "file",
+                    status: "failed",
+                    text: format!("rdl: failed to download {}: {}", url, error),
+                    url: Some(url),
+                    file,
+                    size: 0,
+                    duration_secs: duration,
+                    hash,
+                    error: Some(error),
+                    completed: None,
+                    failed: None,
+                },
+            };
+            self.fire(payload);
+        }
+    }
+
+    /// Fire the batch hooks: the complete command if nothing failed, else the error
+    /// command, plus the desktop notification.
+    pub fn batch_finished(self: &Arc<Self>, completed: usize, failed: usize) {
+        let status = if failed == 0 { "completed" } else { "failed" };
+        if self.notify {
+            let summary = if failed == 0 { "Downloads complete" } else { "Downloads failed" };
+            let body = format!("{} completed, {} failed\n{}", completed, failed, self.output_dir.display());
+            // Talking to the notification service blocks
+            let handle = tokio::task::spawn_blocking(move || {
+                if let Err(e) = notify_rust::Notification::new().appname("rdl").summary(summary).body(&body).show() {
+                    eprintln!("Desktop notification failed: {}", e);
+                }
+            });
+            self.running.lock().unwrap().push(handle);
+        }
+        self.fire(HookPayload {
+            scope: "batch",
+            status,
+            text: format!(
+                "rdl: batch in {} finished: {} completed, {} failed",
+                self.output_dir.display(),
+                completed,
+                failed
+            ),
+            url: None,
+            file: self.output_dir.clone(),
+            size: self.bytes.load(Ordering::Relaxed),
+            duration_secs: self.started.elapsed().as_secs_f64(),
+            hash: None,
+            error: None,
+            completed: Some(completed),
+            failed: Some(failed),
+        });
+    }
+
+    /// Wait for every hook fired so far.
+    pub async fn finish(&self) {
+        let running = std::mem::take(&mut *self.running.lock().unwrap());
+        for handle in running {
+            let _ = handle.await;
+        }
+    }
+
+    fn fire(self: &Arc<Self>, payload: HookPayload) {
+        let command = if payload.status == "completed" { &self.on_complete } else { &self.on_error };
+        let command = command.clone();
+        let hooks = self.clone();
+        let handle = tokio::spawn(async move {
+            let Ok(body) = serde_json::to_string(&payload) else {
+                return;
+            };
+            if let Some(command) = command {
+                if let Err(e) = run_command(&command, &payload, &body).await {
+                    eprintln!("Hook {:?} failed: {:#}", command, e);
+                }
+            }
+            if let Some(url) = &hooks.webhook {
+                if let Err(e) = post_webhook(&hooks.client, url, body).await {
+                    eprintln!("Webhook {} failed: {:#}", url, e);
+                }
+            }
+        });
+        self.running.lock().unwrap().push(handle);
+    }
+}
+
+/// Run `command` through the shell with the payload on stdin.
+async fn run_command(command: &str, payload: &HookPayload, body: &str) -> Result<()> {
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+    let mut child = cmd
+        .env("RDL_SCOPE", payload.scope)
+        .env("RDL_STATUS", payload.status)
+        .env("RDL_FILE", &payload.file)
+        .env("RDL_URL", payload.url.as_deref().unwrap_or_default())
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to start")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A script that does not read its stdin is fine
+        let _ = stdin.write_all(body.as_bytes()).await;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        bail!("exited with {}", status);
+    }
+    Ok(())
+}
+
+async fn post_webhook(client: &Client, url: &str, body: String) -> Result<()> {
+    let resp = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        bail!("HTTP {}", resp.status());
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+use tokio::task::JoinHandle;
+
+/// Control commands sent to a running daemon, one JSON object per line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum IpcRequest {
+    Pause,
+    Resume,
+    /// Pause/resume/cancel a single file, identified by its URL
+    PauseTask { url: String },
+    ResumeTask { url: String },
+    /// `url` may also be the file's path below the download dir or its file name;
+    /// `purge` deletes the `.part` / `.part.json` once the file has stopped
+    CancelTask {
+        url: String,
+        #[serde(default)]
+        purge: bool,
+    },
+    /// List the files paused individually
+    Status,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IpcResponse {
+    pub ok: bool,
+    pub message: String,
+    /// Paused state of the daemon, filled in for `Status`
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paused_tasks: Vec<String>,
+    /// Throughput of the running batch over the last few seconds, filled in for `Status`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_per_sec: Option<u64>,
+    /// Time left for the whole batch at that throughput
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<u64>,
+}
+
+impl IpcResponse {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Self { ok: true, message: message.into(), ..Self::empty() }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { ok: false, message: message.into(), ..Self::empty() }
+    }
+
+    fn empty() -> Self {
+        Self { ok: false, message: String::new(), paused: false, paused_tasks: vec![], bytes_per_sec: None, eta_secs: None }
+    }
+}
+
+pub type IpcHandler = Arc<dyn Fn(IpcRequest) -> IpcResponse + Send + Sync>;
+
+pub fn socket_path() -> PathBuf {
+    crate::session::socket_path()
+}
+
+/// Bind the control socket and answer requests in the background, until the
+/// returned task is aborted.
+pub fn spawn_server(handler: IpcHandler) -> Result<JoinHandle<()>> {
+    let path = socket_path();
+    // A stale socket from a crashed daemon would make bind fail
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).context("Failed to bind control socket")?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                // Errors like EMFILE persist for a while; do not spin on them
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            };
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                if let Ok(Some(line)) = lines.next_line().await {
+                    let response = match serde_json::from_str::<IpcRequest>(&line) {
+                        Ok(request) => handler(request),
+                        Err(e) => IpcResponse::error(format!("Invalid request: {}", e)),
+                    };
+                    if let Ok(mut out) = serde_json::to_string(&response) {
+                        out.push('\n');
+                        let _ = write.write_all(out.as_bytes()).await;
+                    }
+                }
+            });
+        }
+    }))
+}
+
+/// Send a single request to the daemon and wait for its answer.
+pub fn send_request(request: &IpcRequest) -> Result<IpcResponse> {
+    let mut stream = std::os::unix::net::UnixStream::connect(socket_path())
+        .context("Failed to connect to daemon control socket")?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reader = std::io::BufReader::new(stream);
+    let mut response = String::new();
+    reader.read_line(&mut response)?;
+    serde_json::from_str(&response).context("Invalid response from daemon")
+}
+
+pub fn cleanup_socket() {
+    let _ = std::fs::remove_file(socket_path());
+}
@@ -0,0 +1,354 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::downloader::item_relative_path;
+use crate::providers::DownloadItem;
+use crate::tasks::{format_task_line, parse_task_line};
+
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Queued in a batch that has not reached it yet
+    Pending,
+    Running,
+    /// Started, then paused with `rdl --pause` / `rdl pause`
+    Paused,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "pending" => Self::Pending,
+            "paused" => Self::Paused,
+            "completed" => Self::Completed,
+            "failed" => Self::Failed,
+            _ => Self::Running,
+        }
+    }
+}
+
+/// One download as recorded in the job database.
+#[derive(Serialize, Debug, Clone)]
+pub struct Job {
+    pub id: i64,
+    pub url: String,
+    /// Original task line (url, mirrors, hash), used to re-run the job
+    pub task: String,
+    pub output_dir: PathBuf,
+    pub path: PathBuf,
+    pub size: Option<u64>,
+    pub hash: Option<String>,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// When the job was last paused; cleared once it resumes
+    pub paused_at: Option<String>,
+    /// Process that ran the job last, to tell an interrupted job from a running one
+    pub pid: Option<u32>,
+    /// When a pending job held back by its `at=` schedule is due to start
+    pub scheduled_for: Option<String>,
+}
+
+impl Job {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            url: row.get("url")?,
+            task: row.get("task")?,
+            output_dir: PathBuf::from(row.get::<_, String>("output_dir")?),
+            path: PathBuf::from(row.get::<_, String>("path")?),
+            size: row.get::<_, Option<i64>>("size")?.map(|s| s as u64),
+            hash: row.get("hash")?,
+            status: JobStatus::parse(&row.get::<_, String>("status")?),
+            error: row.get("error")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+            paused_at: row.get("paused_at")?,
+            pid: row.get("pid")?,
+            scheduled_for: row.get("scheduled_for")?,
+        })
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS jobs (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    url         TEXT NOT NULL,
+    task        TEXT NOT NULL,
+    output_dir  TEXT NOT NULL,
+    path        TEXT NOT NULL,
+    size        INTEGER,
+    hash        TEXT,
+    status      TEXT NOT NULL,
+    error       TEXT,
+    created_at  TEXT NOT NULL,
+    updated_at  TEXT NOT NULL,
+    paused_at   TEXT,
+    pid         INTEGER,
+    scheduled_for TEXT,
+    UNIQUE (url, path)
+);
+CREATE INDEX IF NOT EXISTS jobs_status ON jobs (status);
+";
+
+/// Columns added after the first release, created on databases that lack them.
+const ADDED_COLUMNS: &[(&str, &str)] = &[("paused_at", "TEXT"), ("pid", "INTEGER"), ("scheduled_for", "TEXT")];
+
+/// Default database location: `jobs.db` in [`crate::utils::state_dir`]. A database left at the
+/// old location (`$XDG_DATA_HOME/rdl/jobs.db`, or `~/.local/share/rdl/jobs.db`) keeps being used.
+pub fn default_db_path() -> Option<PathBuf> {
+    let legacy = match std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")),
+    };
+    if let Some(legacy) = legacy.map(|dir| dir.join("rdl").join("jobs.db")).filter(|path| path.exists()) {
+        return Some(legacy);
+    }
+    crate::utils::state_dir().map(|dir| dir.join("jobs.db"))
+}
+
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Persistent history of every download, shared by foreground runs and the daemon.
+pub struct JobStore {
+    conn: Mutex<Connection>,
+}
+
+impl JobStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent).context("Failed to create job database directory")?;
+            }
+        }
+        let conn = Connection::open(path).context(format!("Failed to open job database: {:?}", path))?;
+        // The daemon and a `--list` in another terminal may touch the database at the same time
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(SCHEMA)?;
+        for (column, declaration) in ADDED_COLUMNS {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('jobs') WHERE name = ?1")?
+                .exists(params![column])?;
+            if !exists {
+                conn.execute_batch(&format!("ALTER TABLE jobs ADD COLUMN {} {}", column, declaration))?;
+            }
+        }
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Open an existing database for lookups only (`--dry-run`), creating and
+    /// migrating nothing.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .context(format!("Failed to open job database: {:?}", path))?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Split a batch into the items still to download and those already completed
+    /// into `output_dir` by an earlier run (their files still exist).
+    /// Sizes recorded for the remaining items are copied over so they need no HEAD request.
+    pub fn resume(&self, items: Vec<DownloadItem>, output_dir: &Path) -> Result<(Vec<DownloadItem>, Vec<DownloadItem>)> {
+        let known: HashMap<String, Job> = self
+            .in_dir(output_dir)?
+            .into_iter()
+            .map(|job| (job.url.clone(), job))
+            .collect();
+
+        let mut remaining = Vec::with_capacity(items.len());
+        let mut completed = Vec::new();
+        for mut item in items {
+            let job = known.get(&item.url);
+            // A name learned from Content-Disposition in an earlier run is kept in the task line
+            if item.name.is_none() {
+                item.name = job.and_then(|j| parse_task_line(&j.task)).and_then(|t| t.name);
+            }
+            let path = output_dir.join(item_relative_path(&item)?);
+            match job.filter(|job| job.path == path) {
+                Some(job) if job.status == JobStatus::Completed && path.exists() => completed.push(item),
+                Some(job) => {
+                    item.size = item.size.or(job.size);
+                    remaining.push(item);
+                }
+                None => remaining.push(item),
+            }
+        }
+        Ok((remaining, completed))
+    }
+
+    /// Queue every item of a batch as pending, so a restarted run knows what is left.
+    /// Errors of earlier attempts are kept until the item starts again.
+    pub fn enqueue(&self, items: &[DownloadItem], output_dir: &Path) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = now();
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO jobs (url, task, output_dir, path, size, hash, status, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'pending', ?7, ?7)
+                 ON CONFLICT (url, path) DO UPDATE SET
+                    task = excluded.task, hash = excluded.hash, size = COALESCE(excluded.size, jobs.size),
+                    status = 'pending', scheduled_for = NULL, updated_at = excluded.updated_at",
+            )?;
+            for item in items {
+                let path = output_dir.join(item_relative_path(item)?);
+                stmt.execute(params![
+                    item.url,
+                    format_task_line(item),
+                    output_dir.to_string_lossy(),
+                    path.to_string_lossy(),
+                    item.size.map(|s| s as i64),
+                    item.hash,
+                    now
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record that `item` started downloading to `path`; re-running a job reuses its row.
+    pub fn start(&self, item: &DownloadItem, output_dir: &Path, path: &Path) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        let now = now();
+        let id = conn.query_row(
+            "INSERT INTO jobs (url, task, output_dir, path, hash, status, created_at, updated_at, pid)
+             VALUES (?1, ?2, ?3, ?4, ?5, 'running', ?6, ?6, ?7)
+             ON CONFLICT (url, path) DO UPDATE SET
+                task = excluded.task, hash = excluded.hash, status = 'running',
+                error = NULL, paused_at = NULL, scheduled_for = NULL, updated_at = excluded.updated_at,
+                pid = excluded.pid
+             RETURNING id",
+            params![
+                item.url,
+                format_task_line(item),
+                output_dir.to_string_lossy(),
+                path.to_string_lossy(),
+                item.hash,
+                now,
+                std::process::id()
+            ],
+            |row| row.get(0),
+        )?;
+        Ok(id)
+    }
+
+    /// Record that this process holds the pending download of `url` to `path` until `at`.
+    pub fn set_scheduled(&self, url: &str, path: &Path, at: DateTime<Local>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE jobs SET scheduled_for = ?3, pid = ?4, updated_at = ?5
+             WHERE url = ?1 AND path = ?2 AND status = 'pending'",
+            params![
+                url,
+                path.to_string_lossy(),
+                at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                std::process::id(),
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn complete(&self, id: i64, size: u64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE jobs SET status = 'completed', size = ?2, error = NULL, updated_at = ?3 WHERE id = ?1",
+            params![id, size as i64, now()],
+        )?;
+        Ok(())
+    }
+
+    /// Drop a job from the database, so later runs no longer know about it.
+    pub fn remove(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM jobs WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn fail(&self, id: i64, error: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE jobs SET status = 'failed', error = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, error, now()],
+        )?;
+        Ok(())
+    }
+
+    /// Record that the running download of `url` to `path` paused or resumed.
+    pub fn set_paused(&self, url: &str, path: &Path, paused: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = now();
+        if paused {
+            conn.execute(
+                "UPDATE jobs SET status = 'paused', paused_at = ?3, updated_at = ?3
+                 WHERE url = ?1 AND path = ?2 AND status = 'running'",
+                params![url, path.to_string_lossy(), now],
+            )?;
+        } else {
+            conn.execute(
+                "UPDATE jobs SET status = 'running', paused_at = NULL, updated_at = ?3
+                 WHERE url = ?1 AND path = ?2 AND status = 'paused'",
+                params![url, path.to_string_lossy(), now],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Most recent completed download of `url`, wherever it was saved.
+    pub fn find_completed(&self, url: &str) -> Result<Option<Job>> {
+        let conn = self.conn.lock().unwrap();
+        let job = conn
+            .query_row(
+                "SELECT * FROM jobs WHERE url = ?1 AND status = 'completed' ORDER BY updated_at DESC LIMIT 1",
+                params![url],
+                Job::from_row,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    /// Newest jobs first, optionally filtered by status and capped at `limit`.
+    pub fn list(&self, status: Option<JobStatus>, limit: Option<usize>) -> Result<Vec<Job>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT * FROM jobs WHERE ?1 IS NULL OR status = ?1 ORDER BY updated_at DESC, id DESC LIMIT ?2",
+        )?;
+        let jobs = stmt
+            // A negative LIMIT means no limit in SQLite
+            .query_map(params![status.map(|s| s.as_str()), limit.map_or(-1, |l| l as i64)], Job::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(jobs)
+    }
+
+    /// Every job that saved (or is saving) into `output_dir`, ordered by path.
+    pub fn in_dir(&self, output_dir: &Path) -> Result<Vec<Job>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT * FROM jobs WHERE output_dir = ?1 ORDER BY path")?;
+        let jobs = stmt
+            .query_map(params![output_dir.to_string_lossy()], Job::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(jobs)
+    }
+}
@@ -0,0 +1,50 @@
+//! Segmented, resumable downloads as a library.
+//!
+//! The `rdl` command line tool is a thin layer over this crate: embedders can
+//! build a [`Downloader`] from [`DownloadOptions`], feed it [`DownloadItem`]s
+//! (parsed from a tasks file with [`tasks::read_tasks_file`] or listed from a
+//! model repository with [`fetch_urls`]) and await [`Downloader::download_file`].
+//! Progress is reported as [`DownloadEvent`]s on [`Downloader::subscribe`];
+//! set [`DownloadOptions::progress_bars`] to `false` to draw nothing on stderr.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use rdl_core::{DownloadItem, DownloadOptions, Downloader};
+//!
+//! let options = DownloadOptions::new("downloads");
+//! let downloader = Downloader::new(options, 1, Default::default(), Default::default());
+//! downloader
+//!     .download_file(DownloadItem { url: "https://example.com/file.bin".into(), ..Default::default() })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod bars;
+pub mod checksums;
+pub mod control;
+pub mod delta;
+pub mod dns;
+pub mod downloader;
+pub mod encoding;
+pub mod events;
+pub mod extract;
+pub mod ftp;
+pub mod hashing;
+pub mod jobs;
+pub mod metalink;
+pub mod mirror;
+pub mod object_store;
+pub mod providers;
+mod refresh;
+pub mod schedule;
+pub mod signatures;
+pub mod state;
+pub mod tasks;
+pub mod throttle;
+pub mod torrent;
+pub mod utils;
+
+pub use downloader::{DownloadOptions, Downloaded, Downloader};
+pub use events::DownloadEvent;
+pub use providers::{fetch_urls, DownloadItem, FetchOptions};
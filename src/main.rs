@@ -1,32 +1,81 @@
 mod commands;
 mod daemon;
-mod downloader;
-mod state;
-mod hashing;
-mod utils;
-mod providers;
 mod cli;
+mod clip;
+mod config;
+mod hooks;
+mod progress;
+mod watch;
+mod select;
+mod session;
+#[cfg(unix)]
+mod ipc;
+#[cfg(unix)]
+mod systemd;
+#[cfg(unix)]
+mod tui;
 
 use anyhow::Result;
-use clap::Parser;
-use crate::cli::VerifyMode;
-use std::path::PathBuf;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use crate::cli::{
+    parse_duration, parse_glob, parse_header, parse_output_template, parse_resolve, parse_schedule, parse_split,
+    Command, DaemonCommand, OutputFormat, ProgressMode, Split,
+};
+use crate::config::Config;
+use rdl_core::downloader::{DecompressMode, DownloadOptions, HttpVersion, IfExists, Layout, SyncMode, AUTO_SPLIT_MAX};
+use rdl_core::hashing::{ChecksumManifest, HashAlgorithm, VerifyMode};
+use rdl_core::providers::RepoType;
+use rdl_core::schedule::Schedule;
+use rdl_core::tasks::InputFormat;
+use rdl_core::utils::{parse_rate, parse_size, NetworkOptions};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Single URL to download (optional, if provided, tasks-file is ignored)
     #[arg(index = 1)]
     url: Option<String>,
 
     /// Path to the file containing URLs (one per line)
-    #[arg(short = 't', long = "tasks-file", default_value = "download.txt")]
+    #[arg(short = 't', long = "tasks-file", default_value = "download.txt", global = true)]
     tasks_file: PathBuf,
 
+    /// Format of the tasks file: rdl, aria2 (an aria2 --input-file), plain (one URL per line)
+    /// or metalink (the default for .meta4 / .metalink files)
+    #[arg(long = "input-format", value_enum, default_value = "rdl", global = true)]
+    input_format: InputFormat,
+
     /// Directory to save downloaded files
-    #[arg(short = 'd', long = "download-dir", default_value = "downloads")]
+    #[arg(short = 'd', long = "download-dir", default_value = "downloads", global = true)]
     download_dir: PathBuf,
 
+    /// Where provider files go below the download dir: flat (by their repository path) or
+    /// provider-tree (<provider>/[datasets|spaces/]<model>/...)
+    #[arg(long, value_enum, default_value = "flat", global = true)]
+    layout: Layout,
+
+    /// Hold downloads without their own at= until this time: "01:00", "2026-10-20 01:00"
+    /// or a cron expression such as "0 1 * * 1-5"
+    #[arg(long = "at", value_parser = parse_schedule, global = true)]
+    at: Option<Schedule>,
+
+    /// Flush finished files to disk before giving them their final name: auto (files with a
+    /// verified hash), always or never
+    #[arg(long, value_enum, default_value = "auto")]
+    sync: SyncMode,
+
+    /// Directory for the .part/.part.json files of unfinished downloads (e.g. a fast scratch disk);
+    /// finished files are moved into the download dir
+    #[arg(long = "temp-dir", global = true)]
+    temp_dir: Option<PathBuf>,
+
     /// Maximum number of concurrent downloads (defaults to number of logical CPUs)
     #[arg(short = 'c', long)]
     concurrency: Option<usize>,
@@ -35,15 +84,63 @@ struct Args {
     #[arg(short = 'r', long)]
     rate_limit: Option<u32>,
 
-    /// Number of splits per file (segmented download)
-    #[arg(short = 's', long, default_value_t = 8)]
-    split: usize,
+    /// Rate limit for each single file in bytes per second (e.g. 2M); tasks can override it with `limit=`
+    #[arg(long = "rate-limit-per-file", value_parser = parse_rate)]
+    rate_limit_per_file: Option<u32>,
+
+    /// Number of splits per file (segmented download), or "auto" to add segments while throughput improves
+    #[arg(short = 's', long, value_parser = parse_split, default_value = "8")]
+    split: Split,
+
+    /// Smallest segment worth its own connection, e.g. "4M"; small files use fewer segments
+    #[arg(long = "min-split-size", value_parser = parse_size, default_value = "4M")]
+    min_split_size: u64,
 
     /// Run in background (daemon mode) [Unix only]
     #[arg(short = 'd', long)]
     daemon: bool,
 
-    /// Follow the log file of the daemon process (like tail -f) [Unix only]
+    /// Progress display: bars, summary lines every --summary-interval (the default with --daemon),
+    /// or json for one JSON event per line on stdout
+    #[arg(long, value_enum)]
+    progress: Option<ProgressMode>,
+
+    /// Write the --progress json stream to this file, FIFO or Unix socket instead of stdout
+    #[arg(long = "progress-to", requires = "progress")]
+    progress_to: Option<PathBuf>,
+
+    /// Show bars for at most N running files; finished files collapse into a short
+    /// "recently completed" list below them (every file gets a bar by default)
+    #[arg(long = "max-visible-bars", value_name = "N")]
+    max_visible_bars: Option<usize>,
+
+    /// Time between summary lines with --progress summary
+    #[arg(long = "summary-interval", value_parser = parse_duration, default_value = "30s")]
+    summary_interval: Duration,
+
+    /// Start a new rdl.log once it reaches this size, e.g. "10M" [daemon]
+    #[arg(long = "log-max-size", value_parser = parse_size, default_value = "10M")]
+    log_max_size: u64,
+
+    /// Also start a new rdl.log after this long, e.g. "1d" [daemon]
+    #[arg(long = "log-max-age", value_parser = parse_duration)]
+    log_max_age: Option<Duration>,
+
+    /// Number of rotated logs (rdl.log.1 ..) to keep [daemon]
+    #[arg(long = "log-keep", default_value_t = 3)]
+    log_keep: usize,
+
+    /// Run or control the named daemon session, whose pid file, socket and log live under
+    /// $XDG_RUNTIME_DIR/rdl/sessions/<name> and ~/.local/state/rdl/sessions/<name>
+    #[arg(long, env = "RDL_SESSION", global = true)]
+    session: Option<String>,
+
+    /// Keep the job database and the daemon's pid file, socket and log here
+    /// (default: $XDG_STATE_HOME/rdl, or ~/.local/state/rdl)
+    #[arg(long = "state-dir", env = "RDL_STATE_DIR", global = true)]
+    state_dir: Option<PathBuf>,
+
+    /// Open a live dashboard of all downloads with pause/resume/cancel controls for the daemon [Unix only]
     #[arg(short = 'f', long)]
     follow: bool,
 
@@ -63,27 +160,437 @@ struct Args {
     #[arg(short = 'l', long)]
     list: bool,
 
-    /// Fetch ModelScope file list (org/model) and write to --input path, then exit
+    /// Output format for --list
+    #[arg(long, value_enum, default_value = "table")]
+    format: OutputFormat,
+
+    /// Shorthand for --format json
+    #[arg(long)]
+    json: bool,
+
+    /// Fetch provider file list (org/model) and write to --input path, then exit
     #[arg(short = 'f', long = "fetch-list")]
     fetch_list: Option<String>,
 
-    /// Revision/branch used when generating resolve URLs (default: master)
-    #[arg(short = 'b', long = "branch", default_value = "master")]
-    branch: String,
+    /// Revision/branch used when generating resolve URLs (default: master for modelscope, main for huggingface, latest tag for oci)
+    #[arg(short = 'b', long = "branch")]
+    branch: Option<String>,
 
-    /// Provider name (e.g., modelscope, huggingface)
-    #[arg(short = 'P', long, default_value = "modelscope")]
+    /// Provider name (e.g., modelscope, huggingface, oci/ollama, gitlab, zenodo, figshare)
+    #[arg(short = 'P', long, default_value = "modelscope", global = true)]
     provider: String,
 
     /// Hash verification: auto (only when hash provided), on (require hash), off (skip)
     #[arg(long = "verify-hash", value_enum, default_value = "auto")]
     verify_hash: VerifyMode,
+
+    /// Checksum file (path or URL, sha256sum/md5sum format) giving hashes to tasks without one
+    #[arg(long = "checksum-file")]
+    checksum_file: Option<String>,
+
+    /// After the batch, write SHA256 checksums of its files: one SHA256SUMS file, a .sha256 per file, or both
+    #[arg(long, value_enum)]
+    checksums: Option<ChecksumManifest>,
+
+    /// Hash algorithm for task hashes without an `algo:` prefix
+    #[arg(long = "hash-algo", value_enum, default_value = "sha256")]
+    hash_algo: HashAlgorithm,
+
+    /// Access token for private/gated provider repos (sent to provider APIs and download URLs)
+    #[arg(long, env = "RDL_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+
+    /// Retry attempts per segment and per file on transient errors
+    #[arg(long, default_value_t = 5)]
+    retries: u32,
+
+    /// Base wait before retrying (doubles each attempt, e.g. 1s, 500ms)
+    #[arg(long = "retry-wait", value_parser = parse_duration, default_value = "1s")]
+    retry_wait: Duration,
+
+    /// Split segments of a file across all mirrors instead of only failing over
+    #[arg(long = "race-mirrors")]
+    race_mirrors: bool,
+
+    /// Proxy URL for all requests (e.g. http://127.0.0.1:7890, socks5://...)
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+
+    /// Additional trusted CA certificate, PEM or DER (repeatable), for private CAs and TLS-inspecting proxies
+    #[arg(long = "ca-cert", global = true)]
+    ca_certs: Vec<PathBuf>,
+
+    /// Client certificate (PEM) for servers that require mutual TLS
+    #[arg(long = "client-cert", requires = "client_key", global = true)]
+    client_cert: Option<PathBuf>,
+
+    /// Private key (PEM, PKCS#8) of --client-cert
+    #[arg(long = "client-key", requires = "client_cert", global = true)]
+    client_key: Option<PathBuf>,
+
+    /// Do not verify server certificates (insecure; only for trusted internal servers)
+    #[arg(long, global = true)]
+    insecure: bool,
+
+    /// Connect over IPv4 only
+    #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6", global = true)]
+    ipv4: bool,
+
+    /// Connect over IPv6 only
+    #[arg(short = '6', long = "ipv6", global = true)]
+    ipv6: bool,
+
+    /// Network interface (name, Unix only) or local IP address to connect from
+    #[arg(long, global = true)]
+    interface: Option<String>,
+
+    /// Connect to HOST at ADDRESS instead of looking it up, curl-style (repeatable), e.g. cdn.example.com:203.0.113.7
+    #[arg(long, value_name = "HOST:ADDRESS", value_parser = parse_resolve, global = true)]
+    resolve: Vec<(String, SocketAddr)>,
+
+    /// Look up host names over DNS over HTTPS (JSON API), e.g. https://1.1.1.1/dns-query
+    #[arg(long = "doh-url", global = true)]
+    doh_url: Option<String>,
+
+    /// Redirects to follow per request (0 follows none)
+    #[arg(long = "max-redirects", value_name = "N", global = true)]
+    max_redirects: Option<usize>,
+
+    /// Keep the Authorization header when a download is redirected to another host
+    /// (credentials are still dropped on a redirect from https to plain http)
+    #[arg(long = "forward-auth", global = true)]
+    forward_auth: bool,
+
+    /// Connection settings built from the options above
+    #[arg(skip)]
+    network: NetworkOptions,
+
+    /// HTTP version for downloads: auto, 1.1, 2 or 3 (QUIC, needs a build with the http3 feature)
+    #[arg(long = "http-version", value_enum, default_value = "auto")]
+    http_version: HttpVersion,
+
+    /// Only keep fetched (or mirrored) files whose repo path matches this glob (repeatable), e.g. "*.safetensors"
+    #[arg(long, value_parser = parse_glob, global = true)]
+    include: Vec<glob::Pattern>,
+
+    /// Drop fetched (or mirrored) files whose repo path matches this glob (repeatable), e.g. "*.bin"
+    #[arg(long, value_parser = parse_glob, global = true)]
+    exclude: Vec<glob::Pattern>,
+
+    /// Pick the fetched files to download interactively before writing the tasks file
+    #[arg(long)]
+    select: bool,
+
+    /// Repository type for --fetch-list, revisions and search (huggingface: model/dataset/space, modelscope: model/dataset)
+    #[arg(long = "repo-type", value_enum, default_value = "model", global = true)]
+    repo_type: RepoType,
+
+    /// Base URL of a mirror or self-hosted instance of the provider, used for its API and download
+    /// URLs (e.g. https://hf-mirror.com for -P huggingface, https://gitlab.example.com for -P gitlab)
+    #[arg(long, global = true)]
+    endpoint: Option<String>,
+
+    /// Extra request header sent with every download and HEAD request (repeatable), e.g. "Referer: https://example.com"
+    #[arg(short = 'H', long = "header", value_parser = parse_header)]
+    headers: Vec<(reqwest::header::HeaderName, reqwest::header::HeaderValue)>,
+
+    /// Netscape-format cookies.txt applied to every download and HEAD request
+    #[arg(long = "cookie-file")]
+    cookie_file: Option<PathBuf>,
+
+    /// Reconnect a segment that receives no data for this long, e.g. "30s" ("0" disables)
+    #[arg(long = "stall-timeout", value_parser = parse_duration, default_value = "30s")]
+    stall_timeout: Duration,
+
+    /// Time allowed to establish a connection, e.g. "10s"
+    #[arg(long = "connect-timeout", value_parser = parse_duration, default_value = "10s")]
+    connect_timeout: Duration,
+
+    /// Longest wait for a response or for the next chunk of its body (no limit by default)
+    #[arg(long = "read-timeout", value_parser = parse_duration)]
+    read_timeout: Option<Duration>,
+
+    /// Limit on a whole request including its body (no limit by default)
+    #[arg(long = "request-timeout", value_parser = parse_duration)]
+    request_timeout: Option<Duration>,
+
+    /// Reserve each file's full size on disk up front (fallocate) instead of a sparse file
+    #[arg(long)]
+    preallocate: bool,
+
+    /// When a file is downloaded again over an older copy, reuse its unchanged blocks
+    /// (metalink piece hashes or a `<url>.zsync` control file) and fetch only the changed ranges
+    #[arg(long)]
+    delta: bool,
+
+    /// Ask for gzip, deflate or zstd transfers of text-like files (JSON, CSV, TXT, ...)
+    /// and decode them while writing; such files use a single connection
+    #[arg(long, conflicts_with = "no_auto_decompress")]
+    compress: bool,
+
+    /// Keep compressed bodies exactly as the server sent them instead of decoding them,
+    /// e.g. when the expected hash is that of the compressed bytes
+    #[arg(long = "no-auto-decompress")]
+    no_auto_decompress: bool,
+
+    /// Unpack downloaded .zip, .tar, .tar.gz and .tar.zst archives into their directory
+    /// once their hash checked out
+    #[arg(long)]
+    extract: bool,
+
+    /// Delete each archive after --extract unpacked it
+    #[arg(long = "remove-archive", requires = "extract")]
+    remove_archive: bool,
+
+    /// Decompress downloaded .gz, .xz and .zst files (e.g. dataset shards) once their hash,
+    /// which is that of the compressed file, checked out; `--decompress=keep` also keeps the
+    /// compressed file
+    #[arg(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "replace")]
+    decompress: Option<DecompressMode>,
+
+    /// What to do when a file already exists: skip, overwrite, resume, rename, check-size or check-hash
+    #[arg(long = "if-exists", value_enum, default_value = "skip")]
+    if_exists: IfExists,
+
+    /// Shell command run when a file or the batch completes (JSON payload on stdin, RDL_FILE etc. in the env)
+    #[arg(long = "on-complete-cmd")]
+    on_complete_cmd: Option<String>,
+
+    /// Shell command run when a file or the batch fails
+    #[arg(long = "on-error-cmd")]
+    on_error_cmd: Option<String>,
+
+    /// POST a JSON payload to this URL for every finished file and batch
+    #[arg(long)]
+    webhook: Option<String>,
+
+    /// Show a desktop notification when the batch completes or fails
+    #[arg(long)]
+    notify: bool,
+
+    /// After the batch, describe it (paths, sizes, hashes, mirrors) in this Metalink (.meta4) file for sharing
+    #[arg(long = "export-metalink")]
+    export_metalink: Option<PathBuf>,
+
+    /// Show what would be downloaded, skipped, resumed or overwritten (names, sizes, paths) without downloading
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Write the end-of-batch report (counts, bytes, speed, failed URLs) to this JSON file
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Start even if the batch does not fit in the free disk space or exceeds --max-total-size (only warn)
+    #[arg(long)]
+    force: bool,
+
+    /// Stop before downloading a batch larger than this (e.g. 200G); asks on a terminal
+    #[arg(long = "max-total-size", value_parser = parse_size)]
+    max_total_size: Option<u64>,
+
+    /// Leave files larger than this out of the batch (e.g. 20G)
+    #[arg(long = "max-file-size", value_parser = parse_size)]
+    max_file_size: Option<u64>,
+
+    /// Saved path below the download dir: a file name for a single URL, or a template for
+    /// batches using {provider}, {model}, {host}, {path} and {filename}, e.g. "{provider}/{model}/{path}"
+    #[arg(short = 'o', long = "out", value_parser = parse_output_template)]
+    out: Option<String>,
+
+    /// Keep file names ASCII-only (letters, digits, '.', '-', '_'), replacing everything else with '_'
+    #[arg(long = "ascii-names")]
+    ascii_names: bool,
+
+    /// Verify the detached .asc/.sig signature of every downloaded file against --trusted-keys
+    #[arg(long = "verify-sig")]
+    verify_sig: bool,
+
+    /// Keyring of trusted OpenPGP public keys for --verify-sig (default: ~/.config/rdl/trusted-keys.asc)
+    #[arg(long = "trusted-keys")]
+    trusted_keys: Option<PathBuf>,
+
+    /// Job database path (default: ~/.local/state/rdl/jobs.db)
+    #[arg(long, env = "RDL_DB")]
+    db: Option<PathBuf>,
+
+    /// Skip URLs the job database already has completed in another directory
+    #[arg(long)]
+    dedup: bool,
+
+    /// Config file path (default: ~/.config/rdl/config.toml)
+    #[arg(long, env = "RDL_CONFIG")]
+    config: Option<PathBuf>,
+}
+
+impl Args {
+    /// Fill in settings not given on the command line (or via env) from the config file.
+    fn merge_config(&mut self, config: &Config, matches: &clap::ArgMatches) {
+        let from_default = |id: &str| !matches!(
+            matches.value_source(id),
+            Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+        );
+
+        if from_default("download_dir") {
+            if let Some(dir) = &config.download_dir {
+                self.download_dir = dir.clone();
+            }
+        }
+        if from_default("split") {
+            if let Some(split) = config.split {
+                self.split = Split::Fixed(split);
+            }
+        }
+        if from_default("provider") {
+            if let Some(provider) = &config.provider {
+                self.provider = provider.clone();
+            }
+        }
+        if from_default("verify_hash") {
+            if let Some(mode) = &config.verify_hash {
+                self.verify_hash = mode.clone();
+            }
+        }
+        self.concurrency = self.concurrency.or(config.concurrency);
+        self.rate_limit = self.rate_limit.or(config.rate_limit);
+        self.token = self.token.take().or_else(|| config.token.clone());
+        self.proxy = self.proxy.take().or_else(|| config.proxy.clone());
+    }
+
+    /// Load the certificates named by the TLS options and pick the local address.
+    fn network_options(&self) -> Result<NetworkOptions> {
+        let identity = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => Some(rdl_core::utils::load_identity(cert, key)?),
+            _ => None,
+        };
+        let local_address = match &self.interface {
+            Some(interface) => {
+                let address = match interface.parse::<IpAddr>() {
+                    Ok(address) => address,
+                    Err(_) => rdl_core::utils::interface_address(interface, self.ipv6)?,
+                };
+                if (self.ipv4 && address.is_ipv6()) || (self.ipv6 && address.is_ipv4()) {
+                    anyhow::bail!("--interface {} does not match the address family requested", interface);
+                }
+                Some(address)
+            }
+            None if self.ipv4 => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            None if self.ipv6 => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            None => None,
+        };
+        let mut network = NetworkOptions {
+            proxy: self.proxy.clone(),
+            ca_certs: self.ca_certs.iter().map(|path| rdl_core::utils::load_ca_cert(path)).collect::<Result<_>>()?,
+            identity,
+            insecure: self.insecure,
+            local_address,
+            resolve: self.resolve.clone(),
+            doh: None,
+            max_redirects: self.max_redirects,
+            forward_auth: self.forward_auth,
+        };
+        if let Some(url) = &self.doh_url {
+            reqwest::Url::parse(url).map_err(|e| anyhow::anyhow!("Invalid --doh-url {}: {}", url, e))?;
+            // Queries go out with the same proxy, TLS and local address, resolved by the system
+            let client = network.client_builder().build()?;
+            network.doh = Some(std::sync::Arc::new(rdl_core::dns::DohResolver::new(url.clone(), client)));
+        }
+        Ok(network)
+    }
+
+    fn fetch_options(&self) -> rdl_core::providers::FetchOptions {
+        rdl_core::providers::FetchOptions {
+            token: self.token.clone(),
+            network: self.network.clone(),
+            endpoint: self.endpoint.clone(),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            repo_type: self.repo_type,
+        }
+    }
+
+    fn effective_config(&self) -> Config {
+        Config {
+            download_dir: Some(self.download_dir.clone()),
+            concurrency: Some(self.concurrency.unwrap_or_else(num_cpus::get)),
+            split: match self.split {
+                Split::Fixed(n) => Some(n),
+                Split::Auto => None,
+            },
+            rate_limit: self.rate_limit,
+            provider: Some(self.provider.clone()),
+            token: self.token.clone(),
+            proxy: self.proxy.clone(),
+            verify_hash: Some(self.verify_hash.clone()),
+        }
+    }
 }
 
 fn main() -> Result<()> {
-    let mut args = Args::parse();
-    let input_is_default = args.tasks_file == PathBuf::from("download.txt");
-    let output_is_default = args.download_dir == PathBuf::from("downloads");
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (config, config_path) = Config::load(args.config.as_deref())?;
+    args.merge_config(&config, &matches);
+
+    if let Some(session) = &args.session {
+        crate::session::set_session(session)?;
+    }
+    if let Some(dir) = &args.state_dir {
+        crate::session::set_state_dir(dir);
+    }
+    if let Some(Command::Sessions) = &args.command {
+        return crate::session::list_sessions();
+    }
+
+    if let Some(Command::Config) = &args.command {
+        return crate::config::show_config(&args.effective_config(), config_path.as_deref());
+    }
+    if let Some(Command::Clip { patterns }) = &args.command {
+        return crate::clip::run(&args.tasks_file, patterns);
+    }
+
+    args.db = args.db.take().or_else(crate::session::default_db_path);
+    if let Some(Command::History { status, limit }) = &args.command {
+        return crate::commands::show_history(args.db.clone(), *status, *limit);
+    }
+
+    if let Some(proxy) = &args.proxy {
+        reqwest::Proxy::all(proxy).map_err(|e| anyhow::anyhow!("Invalid proxy {}: {}", proxy, e))?;
+    }
+    args.network = args.network_options()?;
+    if let Some(endpoint) = &args.endpoint {
+        rdl_core::providers::set_endpoint(&args.provider, endpoint)?;
+    }
+    if args.insecure {
+        eprintln!("Warning: --insecure disables TLS certificate verification");
+    }
+    if args.http_version == HttpVersion::Http3 && !cfg!(feature = "http3") {
+        anyhow::bail!("--http-version 3 needs rdl built with `--features http3`");
+    }
+
+    if let Some(Command::Revisions { model }) = &args.command {
+        let rt = tokio::runtime::Runtime::new()?;
+        return rt.block_on(crate::commands::show_revisions(&args.provider, model, &args.fetch_options()));
+    }
+    if let Some(Command::Search { query, limit }) = &args.command {
+        let rt = tokio::runtime::Runtime::new()?;
+        return rt.block_on(crate::commands::search(&args.provider, query, *limit, &args.fetch_options()));
+    }
+
+    let cookies = match &args.cookie_file {
+        Some(path) => Some(std::sync::Arc::new(rdl_core::utils::load_cookie_file(path)?)),
+        None => None,
+    };
+    let signature_keyring = if args.verify_sig {
+        let Some(path) = args.trusted_keys.clone().or_else(rdl_core::signatures::default_keyring_path) else {
+            anyhow::bail!("--verify-sig needs a keyring (--trusted-keys)");
+        };
+        Some(std::sync::Arc::new(rdl_core::signatures::Keyring::load(&path)?))
+    } else {
+        None
+    };
+
+    let input_is_default = args.tasks_file == Path::new("download.txt");
+    let output_is_default = args.download_dir == Path::new("downloads");
 
     // Resolve paths to absolute before daemonizing to avoid issues with working directory
     // Only if we are NOT in single URL mode (because in single URL mode, tasks_file might be default but unused)
@@ -99,6 +606,57 @@ fn main() -> Result<()> {
         }
     }
 
+    if let Some(Command::Cancel { target, purge }) = &args.command {
+        #[cfg(unix)]
+        return crate::daemon::cancel_task(target, *purge);
+        #[cfg(not(unix))]
+        {
+            let _ = (target, purge);
+            anyhow::bail!("rdl cancel needs the daemon, which is only available on Unix");
+        }
+    }
+    if let Some(Command::Daemon { action: DaemonCommand::InstallService { force } }) = &args.command {
+        #[cfg(unix)]
+        return crate::systemd::install_service(crate::systemd::ServiceOptions {
+            tasks_file: &args.tasks_file,
+            download_dir: &args.download_dir,
+            config: args.config.as_deref(),
+            state_dir: args.state_dir.as_deref(),
+            force: *force,
+            dry_run: args.dry_run,
+        });
+        #[cfg(not(unix))]
+        {
+            let _ = force;
+            anyhow::bail!("rdl daemon install-service needs systemd, which is only available on Unix");
+        }
+    }
+    if let Some(Command::Clean { older_than, abandoned }) = &args.command {
+        let rt = tokio::runtime::Runtime::new()?;
+        return rt.block_on(crate::commands::clean_partials(
+            &args.tasks_file,
+            args.input_format,
+            &args.download_dir,
+            args.db.clone(),
+            args.temp_dir.as_deref(),
+            args.layout,
+            *older_than,
+            *abandoned,
+            args.dry_run,
+        ));
+    }
+    if let Some(Command::Remove { target }) = &args.command {
+        let rt = tokio::runtime::Runtime::new()?;
+        return rt.block_on(crate::commands::remove_task(
+            target,
+            &args.tasks_file,
+            &args.download_dir,
+            args.db.clone(),
+            args.temp_dir.as_deref(),
+            args.layout,
+        ));
+    }
+
     // Handle synchronous commands (list, stop, pause, resume, follow) BEFORE starting runtime
     if args.list || args.stop || args.pause || args.resume || args.follow {
         let rt = tokio::runtime::Runtime::new()?;
@@ -115,59 +673,181 @@ fn main() -> Result<()> {
                 args.tasks_file.clone(),
                 input_is_default,
                 args.branch.clone(),
-                args.provider.clone()
+                args.provider.clone(),
+                args.fetch_options(),
+                args.select,
             ).await
         })?;
 
         // Use the generated list as the new input (absolute if possible)
+        args.input_format = InputFormat::Rdl;
         if let Ok(abs_input) = std::fs::canonicalize(&generated_input) {
             args.tasks_file = abs_input;
         } else {
             args.tasks_file = generated_input;
         }
 
-        // If user didn't override --output, store downloads alongside the generated list
-        if output_is_default {
+        // If user didn't override --output, store downloads alongside the generated list;
+        // the provider tree below the download dir already leads there
+        if output_is_default && args.layout == Layout::Flat {
             if let Some(parent) = args.tasks_file.parent() {
                 args.download_dir = parent.to_path_buf();
             }
         }
     }
 
+    // Files checked against a repository live where --fetch-list would have put them
+    if let Some(Command::Verify { model: Some(model), .. }) = &args.command {
+        if output_is_default && args.layout == Layout::Flat {
+            if let Ok(cwd) = std::env::current_dir() {
+                args.download_dir = cwd.join(crate::commands::default_list_dir(&args.provider, args.repo_type, model));
+            }
+        }
+    }
+
     #[cfg(unix)]
-    if args.daemon {
-        crate::daemon::start_daemon()?;
+    if args.daemon && !args.dry_run {
+        crate::daemon::start_daemon(crate::daemon::LogRotation {
+            max_size: args.log_max_size,
+            max_age: args.log_max_age.filter(|d| !d.is_zero()),
+            keep: args.log_keep,
+        })?;
+    }
+    // A Type=notify service stays in the foreground but is otherwise the session's daemon
+    #[cfg(unix)]
+    if !args.daemon && !args.dry_run && crate::systemd::under_systemd() {
+        crate::daemon::write_pid_file()?;
+        args.daemon = true;
     }
 
+    let fetch_options = args.fetch_options();
+
+    // Redrawn bars only bloat the daemon log
+    let progress = args.progress.unwrap_or(if args.daemon { ProgressMode::Summary } else { ProgressMode::Bars });
+
+    let options = DownloadOptions {
+        output_dir: args.download_dir,
+        concurrency: args.concurrency,
+        rate_limit: args.rate_limit,
+        split: match args.split {
+            Split::Fixed(n) => n,
+            Split::Auto => AUTO_SPLIT_MAX,
+        },
+        adaptive_split: args.split == Split::Auto,
+        min_split_size: args.min_split_size,
+        verify_mode: args.verify_hash,
+        token: args.token,
+        retries: args.retries,
+        retry_wait: args.retry_wait,
+        network: args.network,
+        rate_limit_per_file: args.rate_limit_per_file,
+        race_mirrors: args.race_mirrors,
+        hash_algo: args.hash_algo,
+        db_path: args.db.clone(),
+        dedup: args.dedup,
+        headers: args.headers.into_iter().collect(),
+        cookies,
+        progress_bars: progress == ProgressMode::Bars,
+        max_visible_bars: args.max_visible_bars,
+        summary_interval: (progress == ProgressMode::Summary).then_some(args.summary_interval),
+        json_progress: progress == ProgressMode::Json,
+        progress_to: args.progress_to,
+        checksum_file: args.checksum_file,
+        stall_timeout: Some(args.stall_timeout).filter(|d| !d.is_zero()),
+        connect_timeout: args.connect_timeout,
+        read_timeout: args.read_timeout.filter(|d| !d.is_zero()),
+        request_timeout: args.request_timeout.filter(|d| !d.is_zero()),
+        preallocate: args.preallocate,
+        if_exists: args.if_exists,
+        http_version: args.http_version,
+        delta: args.delta,
+        compress: args.compress,
+        auto_decompress: !args.no_auto_decompress,
+        extract: args.extract,
+        remove_archive: args.remove_archive,
+        decompress: args.decompress,
+        temp_dir: args.temp_dir.clone(),
+        sync: args.sync,
+        layout: args.layout,
+        schedule: args.at,
+        force: args.force,
+        max_total_size: args.max_total_size,
+        max_file_size: args.max_file_size,
+        output_template: args.out,
+        ascii_names: args.ascii_names,
+        signature_keyring,
+        on_complete_cmd: args.on_complete_cmd,
+        on_error_cmd: args.on_error_cmd,
+        webhook: args.webhook,
+        notify: args.notify,
+        report: args.report,
+        dry_run: args.dry_run,
+        export_metalink: args.export_metalink,
+        checksum_manifest: args.checksums,
+    };
+
+    #[cfg(unix)]
+    let daemonized = args.daemon && !args.dry_run;
+
     // Now start the runtime for the actual download task
     let rt = tokio::runtime::Runtime::new()?;
-    rt.block_on(async {
-        if let Some(url) = args.url {
-            crate::commands::run_single_download(
-                url,
-                args.download_dir,
-                args.concurrency,
-                args.rate_limit,
-                args.split,
-                args.verify_hash,
-            ).await
+    let result = rt.block_on(async {
+        #[cfg(unix)]
+        crate::systemd::spawn_lifecycle();
+        if let Some(Command::Retry) = args.command {
+            crate::commands::retry_failed(options, args.daemon).await
+        } else if let Some(Command::Verify { model, requeue }) = &args.command {
+            let items = match model {
+                Some(model) => rdl_core::fetch_urls(&args.provider, model, args.branch.as_deref(), &fetch_options).await?,
+                None => rdl_core::tasks::read_tasks_file_as(&args.tasks_file, args.input_format).await?,
+            };
+            crate::commands::verify_downloads(items, options, *requeue).await
+        } else if let Some(Command::Watch { target }) = &args.command {
+            crate::watch::run(target, args.input_format, options, args.daemon).await
+        } else if let Some(Command::Mirror { url, depth }) = &args.command {
+            let mirror_options = rdl_core::mirror::MirrorOptions {
+                depth: *depth,
+                include: fetch_options.include.clone(),
+                exclude: fetch_options.exclude.clone(),
+            };
+            crate::commands::mirror(url, &mirror_options, options, args.daemon).await
+        } else if let Some(url) = args.url {
+            if rdl_core::torrent::is_torrent_source(&url) {
+                // A torrent may expand to many files; it takes the batch path
+                let item = rdl_core::providers::DownloadItem { url, ..Default::default() };
+                crate::commands::run_items(vec![item], options, args.daemon).await
+            } else {
+                crate::commands::run_single_download(url, options).await
+            }
         } else {
-            crate::commands::run_downloads(
-                args.tasks_file,
-                args.download_dir,
-                args.concurrency,
-                args.rate_limit,
-                args.split,
-                args.daemon,
-                args.verify_hash,
-            ).await
+            crate::commands::run_downloads(args.tasks_file, args.input_format, options, args.daemon).await
         }
-    })
+    });
+
+    #[cfg(unix)]
+    if daemonized {
+        crate::daemon::cleanup_pid_file();
+    }
+
+    match result {
+        Err(e) if e.is::<rdl_core::control::Interrupted>() => {
+            eprintln!("Interrupted; progress saved, run the same command again to resume.");
+            std::process::exit(rdl_core::control::INTERRUPTED_EXIT_CODE);
+        }
+        result => result,
+    }
 }
 
 async fn handle_sync_commands(args: &Args) -> Result<()> {
     if args.list {
-        return crate::commands::list_downloads(args.download_dir.clone(), args.tasks_file.clone()).await;
+        let format = if args.json { OutputFormat::Json } else { args.format };
+        return crate::commands::list_downloads(
+                args.download_dir.clone(),
+                args.tasks_file.clone(),
+                args.db.clone(),
+                args.temp_dir.clone(),
+                format,
+            ).await;
     }
 
     #[cfg(unix)]
@@ -185,7 +865,7 @@ async fn handle_sync_commands(args: &Args) -> Result<()> {
         }
 
         if args.follow {
-            return crate::commands::follow_log(args.download_dir.clone(), args.tasks_file.clone()).await;
+            return crate::tui::run(args.download_dir.clone(), args.tasks_file.clone(), args.db.clone(), args.temp_dir.clone()).await;
         }
     }
     Ok(())
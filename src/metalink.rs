@@ -0,0 +1,181 @@
+//! Metalink files (RFC 5854 `.meta4` and the older 3.0 `.metalink`) as a task
+//! source, and `.meta4` export of a batch.
+//!
+//! Each `<file>` becomes one [`DownloadItem`]: its `<url>`s in priority order are
+//! the source and mirrors, `name` the saved path, and `<size>`, the strongest
+//! whole-file `<hash>` rdl can check, the strongest `<pieces>` (for `--delta`)
+//! and an OpenPGP `<signature>` are kept. `<metaurl>`s (torrents) are ignored.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::delta::{PieceHashes, MAX_BLOCK_SIZE};
+use crate::downloader::item_relative_path;
+use crate::hashing::{parse_expected_hash, HashAlgorithm};
+use crate::providers::DownloadItem;
+
+/// Whether `path` looks like a metalink file by its extension.
+pub fn is_metalink_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("meta4") || ext.eq_ignore_ascii_case("metalink"))
+}
+
+pub fn parse_metalink(content: &str) -> Result<Vec<DownloadItem>> {
+    let doc = roxmltree::Document::parse(content).context("Invalid metalink XML")?;
+    let mut items = vec![];
+    for file in doc.descendants().filter(|n| n.tag_name().name() == "file") {
+        let mut urls: Vec<(i64, String)> = vec![];
+        let mut hashes: Vec<(HashAlgorithm, String)> = vec![];
+        let mut size = None;
+        let mut signature = None;
+        let mut pieces: Vec<PieceHashes> = vec![];
+        for node in file.descendants().filter(|n| n.is_element()) {
+            let text = node.text().unwrap_or_default().trim();
+            let parent = node.parent_element().map(|p| p.tag_name().name()).unwrap_or_default();
+            match node.tag_name().name() {
+                "url" if text.contains("://") => {
+                    // 3.0 marks torrents and the like with `type`
+                    if matches!(node.attribute("type"), Some("bittorrent" | "ed2k" | "magnet")) {
+                        continue;
+                    }
+                    // 4.0: `priority` 1 is best; 3.0: `preference` 100 is best
+                    let rank = match (node.attribute("priority"), node.attribute("preference")) {
+                        (Some(priority), _) => priority.parse().unwrap_or(i64::MAX),
+                        (None, Some(preference)) => -preference.parse::<i64>().unwrap_or(0),
+                        (None, None) => i64::MAX,
+                    };
+                    urls.push((rank, text.to_string()));
+                }
+                "size" => size = text.parse().ok(),
+                "pieces" => {
+                    let algo = node.attribute("type").and_then(hash_algorithm);
+                    // A piece is read into memory whole, so absurd lengths are left out
+                    let length = node
+                        .attribute("length")
+                        .and_then(|l| l.parse().ok())
+                        .filter(|l| (1..=MAX_BLOCK_SIZE).contains(l));
+                    if let (Some(algo), Some(length)) = (algo, length) {
+                        let hashes = node
+                            .children()
+                            .filter(|n| n.tag_name().name() == "hash")
+                            .map(|n| n.text().unwrap_or_default().trim().to_string())
+                            .collect();
+                        pieces.push(PieceHashes { algo, length, hashes });
+                    }
+                }
+                // Piece hashes sit below <pieces>
+                "hash" if parent == "file" || parent == "verification" => {
+                    if let Some(algo) = node.attribute("type").and_then(hash_algorithm) {
+                        hashes.push((algo, text.to_string()));
+                    }
+                }
+                "signature" if !text.is_empty() => {
+                    let kind = node.attribute("mediatype").or(node.attribute("type")).unwrap_or_default();
+                    if kind.contains("pgp") {
+                        signature = Some(text.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        urls.sort_by_key(|(rank, _)| *rank);
+        let mut urls = urls.into_iter().map(|(_, url)| url);
+        let Some(url) = urls.next() else {
+            continue;
+        };
+        hashes.sort_by_key(|(algo, _)| hash_strength(*algo));
+        pieces.sort_by_key(|p| hash_strength(p.algo));
+        items.push(DownloadItem {
+            url,
+            mirrors: urls.collect(),
+            hash: hashes.pop().map(|(algo, digest)| format!("{}:{}", algo.to_string().to_lowercase(), digest)),
+            size,
+            name: file.attribute("name").map(str::to_string),
+            signature,
+            pieces: pieces.pop(),
+            ..Default::default()
+        });
+    }
+    Ok(items)
+}
+
+/// A `.meta4` document for `items`; hashes without a prefix are taken to be `default_algo`.
+pub fn format_metalink(items: &[DownloadItem], default_algo: HashAlgorithm) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<metalink xmlns=\"urn:ietf:params:xml:ns:metalink\">\n");
+    xml.push_str(&format!("  <generator>rdl/{}</generator>\n", env!("CARGO_PKG_VERSION")));
+    xml.push_str(&format!("  <published>{}</published>\n", chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)));
+    for item in items {
+        let Ok(path) = item_relative_path(item) else {
+            continue;
+        };
+        let name = path.to_string_lossy().replace('\\', "/");
+        xml.push_str(&format!("  <file name=\"{}\">\n", escape(&name)));
+        if let Some(size) = item.size {
+            xml.push_str(&format!("    <size>{}</size>\n", size));
+        }
+        if let Some(hash) = &item.hash {
+            let (algo, digest) = parse_expected_hash(hash, default_algo);
+            if let Some(kind) = metalink_hash_type(algo) {
+                xml.push_str(&format!("    <hash type=\"{}\">{}</hash>\n", kind, escape(&digest)));
+            }
+        }
+        if let Some(pieces) = &item.pieces {
+            if let Some(kind) = metalink_hash_type(pieces.algo) {
+                xml.push_str(&format!("    <pieces length=\"{}\" type=\"{}\">\n", pieces.length, kind));
+                for hash in &pieces.hashes {
+                    xml.push_str(&format!("      <hash>{}</hash>\n", escape(hash)));
+                }
+                xml.push_str("    </pieces>\n");
+            }
+        }
+        if let Some(signature) = &item.signature {
+            xml.push_str(&format!(
+                "    <signature mediatype=\"application/pgp-signature\">{}</signature>\n",
+                escape(signature)
+            ));
+        }
+        for (priority, url) in item.sources().iter().enumerate() {
+            xml.push_str(&format!("    <url priority=\"{}\">{}</url>\n", priority + 1, escape(url)));
+        }
+        xml.push_str("  </file>\n");
+    }
+    xml.push_str("</metalink>\n");
+    xml
+}
+
+/// Hash type names of the IANA registry (4.0) and of 3.0
+fn hash_algorithm(kind: &str) -> Option<HashAlgorithm> {
+    match kind.to_ascii_lowercase().as_str() {
+        "sha-256" | "sha256" => Some(HashAlgorithm::Sha256),
+        "sha-1" | "sha1" => Some(HashAlgorithm::Sha1),
+        "md5" => Some(HashAlgorithm::Md5),
+        _ => None,
+    }
+}
+
+fn metalink_hash_type(algo: HashAlgorithm) -> Option<&'static str> {
+    match algo {
+        HashAlgorithm::Sha256 => Some("sha-256"),
+        HashAlgorithm::Sha1 => Some("sha-1"),
+        HashAlgorithm::Md5 => Some("md5"),
+        // Not in the registry
+        HashAlgorithm::Blake3 => None,
+    }
+}
+
+fn hash_strength(algo: HashAlgorithm) -> u8 {
+    match algo {
+        HashAlgorithm::Md5 => 0,
+        HashAlgorithm::Sha1 => 1,
+        HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 2,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,166 @@
+//! `rdl mirror`: download a directory tree served as Apache / nginx auto-index
+//! pages, a lightweight `wget -r` for dataset mirrors.
+//!
+//! The listing at the root URL is read for `href` links. Links ending in `/` are
+//! subdirectories, listed in turn up to `depth` levels down; all others are
+//! files. Only links below the root on the same host count, which leaves out
+//! the parent directory, absolute links elsewhere and the column sorting links
+//! (`?C=N;O=D`). Each file is named by its path below the root, so the tree is
+//! recreated in the output directory.
+
+use anyhow::{bail, Context, Result};
+use futures::{stream, StreamExt};
+use glob::Pattern;
+use reqwest::header;
+use reqwest::Client;
+use std::collections::HashSet;
+use url::Url;
+
+use crate::providers::DownloadItem;
+use crate::utils::percent_decode;
+
+/// Directory listings fetched at once.
+const LIST_CONCURRENCY: usize = 8;
+
+#[derive(Clone, Debug, Default)]
+pub struct MirrorOptions {
+    /// Subdirectory levels followed below the root (0: only the files listed at the root)
+    pub depth: usize,
+    /// Only keep files whose path below the root matches one of these (all when empty)
+    pub include: Vec<Pattern>,
+    /// Drop files whose path below the root matches one of these, over `include`
+    pub exclude: Vec<Pattern>,
+}
+
+/// The files of the tree below `root`, in listing order, named by their path below it.
+pub async fn crawl(client: &Client, root: &str, options: &MirrorOptions) -> Result<Vec<DownloadItem>> {
+    let mut root = Url::parse(root).with_context(|| format!("Invalid URL: {}", root))?;
+    if !matches!(root.scheme(), "http" | "https") {
+        bail!("rdl mirror needs an http(s) directory listing: {}", root);
+    }
+    root.set_query(None);
+    root.set_fragment(None);
+    // `dir` and `dir/` list the same directory, but only `dir/` is the base of its links
+    if !root.path().ends_with('/') {
+        root.set_path(&format!("{}/", root.path()));
+    }
+
+    let mut items = vec![];
+    let mut seen = HashSet::from([root.clone()]);
+    let mut level = vec![root.clone()];
+    for depth in 0..=options.depth {
+        let listings: Vec<_> = stream::iter(level.iter().cloned())
+            .map(|dir| async move {
+                let links = list_directory(client, &dir).await;
+                (dir, links)
+            })
+            .buffered(LIST_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut next = vec![];
+        for (dir, links) in listings {
+            let links = match links {
+                Ok(links) => links,
+                // Without its root listing there is nothing to mirror
+                Err(e) if dir == root => return Err(e),
+                Err(e) => {
+                    eprintln!("Skipping {}: {:#}", dir, e);
+                    continue;
+                }
+            };
+            for link in links.into_iter().filter(|link| is_below(&dir, link)) {
+                if link.path().ends_with('/') {
+                    if depth < options.depth && seen.insert(link.clone()) {
+                        next.push(link);
+                    }
+                    continue;
+                }
+                let path = relative_path(&root, &link);
+                let included = options.include.is_empty() || options.include.iter().any(|p| p.matches(&path));
+                if included && !options.exclude.iter().any(|p| p.matches(&path)) && seen.insert(link.clone()) {
+                    items.push(DownloadItem { url: link.to_string(), name: Some(path), ..Default::default() });
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        level = next;
+    }
+    Ok(items)
+}
+
+/// The links of the listing page at `dir`, resolved against the address it ended up at.
+async fn list_directory(client: &Client, dir: &Url) -> Result<Vec<Url>> {
+    let response = client
+        .get(dir.as_str())
+        .send()
+        .await
+        .with_context(|| format!("Failed to list {}", dir))?
+        .error_for_status()?;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_ascii_lowercase().contains("html"));
+    if !is_html {
+        bail!("{} is not a directory listing (no HTML page)", dir);
+    }
+    let base = response.url().clone();
+    let html = response.text().await.with_context(|| format!("Failed to read {}", dir))?;
+    Ok(hrefs(&html)
+        .iter()
+        .filter_map(|href| base.join(href).ok())
+        .map(|mut link| {
+            link.set_fragment(None);
+            link
+        })
+        // Sorting links of the listing itself carry a query; files and directories do not
+        .filter(|link| link.query().is_none())
+        .collect())
+}
+
+/// Whether `link` lies strictly below the directory `dir`, on the same server.
+fn is_below(dir: &Url, link: &Url) -> bool {
+    link.scheme() == dir.scheme()
+        && link.host_str() == dir.host_str()
+        && link.port_or_known_default() == dir.port_or_known_default()
+        && link.path().len() > dir.path().len()
+        && link.path().starts_with(dir.path())
+}
+
+/// The path of `link` below `root`, percent-decoded.
+fn relative_path(root: &Url, link: &Url) -> String {
+    link.path()[root.path().len()..]
+        .split('/')
+        .map(percent_decode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The `href` values of an HTML page. Auto-index pages are simple enough that a
+/// scan for the attribute does, quoted or not.
+fn hrefs(html: &str) -> Vec<String> {
+    // ASCII lowercasing keeps every byte offset
+    let lower = html.to_ascii_lowercase();
+    let mut hrefs = vec![];
+    let mut from = 0;
+    while let Some(found) = lower[from..].find("href=") {
+        let start = from + found + "href=".len();
+        let rest = &html[start..];
+        let (value, len) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => match rest[1..].find(quote) {
+                Some(end) => (&rest[1..1 + end], end + 2),
+                None => break,
+            },
+            _ => {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(rest.len());
+                (&rest[..end], end)
+            }
+        };
+        hrefs.push(value.replace("&amp;", "&"));
+        from = start + len;
+    }
+    hrefs
+}
@@ -0,0 +1,226 @@
+//! `s3://bucket/key` and `gs://bucket/key` sources.
+//!
+//! Object URLs stay as they are in tasks, the job history and part states; each
+//! request goes to an HTTPS URL made from them at the last moment
+//! ([`request_url`]), so presigned URLs never expire in the middle of a batch.
+//!
+//! S3 requests are presigned (SigV4, query string) with credentials from
+//! `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` (and `AWS_SESSION_TOKEN`), or
+//! from the `AWS_PROFILE` (else `default`) profile of `~/.aws/credentials`;
+//! without credentials they are sent unsigned, for public buckets. The region
+//! comes from `AWS_REGION` / `AWS_DEFAULT_REGION` or `~/.aws/config`, and
+//! `AWS_ENDPOINT_URL` points at S3-compatible stores (MinIO, R2, ...).
+//! GCS objects are read from `storage.googleapis.com`, with
+//! `GOOGLE_OAUTH_ACCESS_TOKEN` as bearer token when set.
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Lifetime of a presigned URL; every request signs anew
+const PRESIGN_EXPIRES_SECS: u64 = 3600;
+
+/// Characters SigV4 leaves unencoded: letters, digits and `-._~`
+const SIGV4_UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+pub fn is_object_url(url: &str) -> bool {
+    url.starts_with("s3://") || url.starts_with("gs://")
+}
+
+/// The HTTPS URL to send a `method` request for `url` to; other URLs are returned as they are.
+pub fn request_url<'a>(url: &'a str, method: &str) -> Result<Cow<'a, str>> {
+    if let Some(rest) = url.strip_prefix("s3://") {
+        let (bucket, key) = split_object(rest, url)?;
+        return Ok(Cow::Owned(s3_url(bucket, key, method)));
+    }
+    if let Some(rest) = url.strip_prefix("gs://") {
+        let (bucket, key) = split_object(rest, url)?;
+        return Ok(Cow::Owned(format!("https://storage.googleapis.com/{}/{}", bucket, encode_key(key))));
+    }
+    Ok(Cow::Borrowed(url))
+}
+
+/// Bearer token for `gs://` objects (`GOOGLE_OAUTH_ACCESS_TOKEN`).
+pub fn auth_headers(url: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if url.starts_with("gs://") {
+        let token = std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN").ok().filter(|t| !t.is_empty());
+        if let Some(value) = token.and_then(|t| HeaderValue::from_str(&format!("Bearer {}", t)).ok()) {
+            headers.insert(header::AUTHORIZATION, value);
+        }
+    }
+    headers
+}
+
+/// The MD5 an object store reports for `url` in a HEAD response, as a prefixed hash.
+/// S3 ETags are the MD5 only for single-part uploads without KMS encryption.
+pub fn reported_hash(url: &str, headers: &HeaderMap) -> Option<String> {
+    let value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if url.starts_with("s3://") {
+        if value("x-amz-server-side-encryption") == Some("aws:kms") {
+            return None;
+        }
+        let etag = value("etag")?.trim_matches('"');
+        let is_md5 = etag.len() == 32 && etag.bytes().all(|b| b.is_ascii_hexdigit());
+        return is_md5.then(|| format!("md5:{}", etag.to_ascii_lowercase()));
+    }
+    if url.starts_with("gs://") {
+        // `x-goog-hash: crc32c=..., md5=...` (composite objects have no MD5)
+        let md5 = headers
+            .get_all("x-goog-hash")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .find_map(|part| part.trim().strip_prefix("md5="))?;
+        let bytes = base64::engine::general_purpose::STANDARD.decode(md5).ok()?;
+        return Some(format!("md5:{}", hex::encode(bytes)));
+    }
+    None
+}
+
+fn split_object<'a>(rest: &'a str, url: &str) -> Result<(&'a str, &'a str)> {
+    match rest.split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Ok((bucket, key)),
+        _ => Err(anyhow!("Expected <scheme>://bucket/key: {}", url)),
+    }
+}
+
+/// Percent-encode every segment of an object key, keeping the `/`s.
+fn encode_key(key: &str) -> String {
+    key.split('/')
+        .map(|segment| utf8_percent_encode(segment, SIGV4_UNRESERVED).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+struct AwsConfig {
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    session_token: Option<String>,
+    region: String,
+    endpoint: Option<String>,
+}
+
+fn aws_config() -> &'static AwsConfig {
+    static CONFIG: OnceLock<AwsConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let profile = env("AWS_PROFILE").unwrap_or_else(|| "default".to_string());
+        let aws_dir = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws"));
+        let credentials = aws_dir
+            .as_ref()
+            .and_then(|dir| read_ini_section(&dir.join("credentials"), &profile))
+            .unwrap_or_default();
+        let config_section = if profile == "default" { profile.clone() } else { format!("profile {}", profile) };
+        let config = aws_dir
+            .as_ref()
+            .and_then(|dir| read_ini_section(&dir.join("config"), &config_section))
+            .unwrap_or_default();
+        let (access_key, secret_key, session_token) = match (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key), Some(secret_key)) => (Some(access_key), Some(secret_key), env("AWS_SESSION_TOKEN")),
+            _ => (
+                credentials.get("aws_access_key_id").cloned(),
+                credentials.get("aws_secret_access_key").cloned(),
+                credentials.get("aws_session_token").cloned(),
+            ),
+        };
+        AwsConfig {
+            access_key,
+            secret_key,
+            session_token,
+            region: env("AWS_REGION")
+                .or_else(|| env("AWS_DEFAULT_REGION"))
+                .or_else(|| config.get("region").cloned())
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            endpoint: env("AWS_ENDPOINT_URL").map(|e| e.trim_end_matches('/').to_string()),
+        }
+    })
+}
+
+/// `key = value` pairs of one `[section]` of an AWS ini file.
+fn read_ini_section(path: &std::path::Path, section: &str) -> Option<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let mut current = None;
+    let mut values = HashMap::new();
+    for line in content.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name.trim().to_string());
+        } else if current.as_deref() == Some(section) {
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+    Some(values)
+}
+
+fn s3_url(bucket: &str, key: &str, method: &str) -> String {
+    let config = aws_config();
+    // Virtual-hosted style, except for custom endpoints and bucket names TLS certificates cannot cover
+    let (host, path) = match &config.endpoint {
+        Some(endpoint) => (endpoint.clone(), format!("/{}/{}", bucket, encode_key(key))),
+        None if bucket.contains('.') => (
+            format!("https://s3.{}.amazonaws.com", config.region),
+            format!("/{}/{}", bucket, encode_key(key)),
+        ),
+        None => (
+            format!("https://{}.s3.{}.amazonaws.com", bucket, config.region),
+            format!("/{}", encode_key(key)),
+        ),
+    };
+    let (Some(access_key), Some(secret_key)) = (&config.access_key, &config.secret_key) else {
+        return format!("{}{}", host, path);
+    };
+
+    let now = Utc::now();
+    let date = now.format("%Y%m%d").to_string();
+    let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let mut query = vec![
+        ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+        ("X-Amz-Credential", format!("{}/{}", access_key, scope)),
+        ("X-Amz-Date", timestamp.clone()),
+        ("X-Amz-Expires", PRESIGN_EXPIRES_SECS.to_string()),
+        ("X-Amz-SignedHeaders", "host".to_string()),
+    ];
+    if let Some(token) = &config.session_token {
+        query.push(("X-Amz-Security-Token", token.clone()));
+    }
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, utf8_percent_encode(value, SIGV4_UNRESERVED)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let host_header = host.split_once("://").map_or(host.as_str(), |(_, h)| h);
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD",
+        method, path, canonical_query, host_header
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let mut signing_key = format!("AWS4{}", secret_key).into_bytes();
+    for part in [date.as_str(), config.region.as_str(), "s3", "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part.as_bytes());
+    }
+    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+    format!("{}{}?{}&X-Amz-Signature={}", host, path, canonical_query, signature)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
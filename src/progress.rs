@@ -0,0 +1,104 @@
+//! `--progress json`: stream every download event as one JSON object per line,
+//! for wrappers, GUIs and CI jobs that would otherwise scrape the progress bars.
+//!
+//! Each line is a serialized [`DownloadEvent`] (`{"event":"progress","url":...}`),
+//! plus `batch_started` / `batch_finished` lines around the batch. The stream goes
+//! to stdout unless `--progress-to` names a file, FIFO or Unix socket; human
+//! readable status lines always go to stderr.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+
+use rdl_core::downloader::DownloadOptions;
+use rdl_core::events::DownloadEvent;
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum BatchEvent {
+    BatchStarted { files: usize, total: u64 },
+    BatchFinished { completed: usize, failed: usize },
+}
+
+type Sink = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Open the `--progress-to` target: a Unix socket is connected to, anything else
+/// (a regular file or a FIFO) is opened for writing.
+async fn open_sink(target: Option<&Path>) -> Result<Sink> {
+    let Some(path) = target else {
+        return Ok(Box::new(tokio::io::stdout()));
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            let stream = tokio::net::UnixStream::connect(path)
+                .await
+                .with_context(|| format!("Failed to connect to {:?}", path))?;
+            return Ok(Box::new(stream));
+        }
+    }
+    // Opening a FIFO waits until a reader shows up
+    let file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    Ok(Box::new(file))
+}
+
+/// Writer of one batch's event stream.
+pub struct JsonProgress {
+    sink: Sink,
+}
+
+impl JsonProgress {
+    /// `None` unless `--progress json` was requested.
+    pub async fn open(options: &DownloadOptions) -> Result<Option<Self>> {
+        if !options.json_progress {
+            return Ok(None);
+        }
+        let sink = open_sink(options.progress_to.as_deref()).await?;
+        Ok(Some(Self { sink }))
+    }
+
+    /// Announce the batch, then write every event until `files` files have
+    /// completed or failed (or the downloader is gone), and announce the end.
+    pub async fn run(mut self, mut events: broadcast::Receiver<DownloadEvent>, files: usize, total: u64) {
+        if let Err(e) = self.stream(&mut events, files, total).await {
+            // A reader that went away should not fail the downloads
+            eprintln!("Progress stream closed: {:#}", e);
+        }
+    }
+
+    async fn stream(&mut self, events: &mut broadcast::Receiver<DownloadEvent>, files: usize, total: u64) -> Result<()> {
+        self.write(&BatchEvent::BatchStarted { files, total }).await?;
+        let (mut completed, mut failed) = (0, 0);
+        while completed + failed < files {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            match event {
+                DownloadEvent::Completed { .. } => completed += 1,
+                DownloadEvent::Failed { .. } => failed += 1,
+                _ => {}
+            }
+            self.write(&event).await?;
+        }
+        self.write(&BatchEvent::BatchFinished { completed, failed }).await
+    }
+
+    async fn write(&mut self, event: &impl Serialize) -> Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        self.sink.write_all(&line).await?;
+        self.sink.flush().await?;
+        Ok(())
+    }
+}
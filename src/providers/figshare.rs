@@ -0,0 +1,130 @@
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde::Deserialize;
+use url::Url;
+
+use super::{DownloadItem, FetchOptions, Provider};
+use crate::utils::NetworkOptions;
+
+/// Figshare 数据集。名称可以是文章 ID、DOI (`10.6084/m9.figshare.1234567.v2`) 或文章页面链接；
+/// DOI/链接中的版本号优先，否则使用 `--branch`（默认 `latest`，即最新版本）。
+pub struct Figshare;
+
+#[async_trait]
+impl Provider for Figshare {
+    fn name(&self) -> &str {
+        "figshare"
+    }
+
+    fn default_revision(&self) -> &str {
+        "latest"
+    }
+
+    fn owns_url(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|h| h == "figshare.com" || h.ends_with(".figshare.com"))
+    }
+
+    fn relative_path(&self, url: &Url) -> Option<String> {
+        // 下载链接只含文件 ID（/files/<id>），文件名放在不会发给服务器的 fragment 中
+        url.fragment()
+            .and_then(|name| percent_encoding::percent_decode_str(name).decode_utf8().ok())
+            .map(|name| name.into_owned())
+    }
+
+    fn auth_headers(&self, token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(&format!("token {}", token)) {
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        headers
+    }
+
+    async fn list_files(&self, model: &str, revision: &str, opts: &FetchOptions) -> Result<Vec<DownloadItem>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        fetch_figshare_urls(model, revision, headers, &opts.network).await
+    }
+}
+
+#[derive(Deserialize)]
+struct FigshareArticle {
+    #[serde(default)]
+    files: Vec<FigshareFile>,
+}
+
+#[derive(Deserialize)]
+struct FigshareFile {
+    name: String,
+    download_url: String,
+    size: Option<u64>,
+    computed_md5: Option<String>,
+    supplied_md5: Option<String>,
+    /// 外部链接文件，没有可下载的内容
+    #[serde(default)]
+    is_link_only: bool,
+}
+
+/// 从文章 ID、DOI 或链接中取出文章 ID 与可选的版本号
+fn article_id(input: &str) -> Option<(&str, Option<&str>)> {
+    let input = input.trim().trim_end_matches('/');
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+    // DOI：10.6084/m9.figshare.1234567[.v2]
+    if let Some((_, rest)) = input.rsplit_once("figshare.").filter(|(_, rest)| !rest.contains('/')) {
+        let (id, version) = match rest.split_once(".v") {
+            Some((id, version)) => (id, Some(version)),
+            None => (rest, None),
+        };
+        return (is_number(id) && version.iter().all(|v| is_number(v))).then_some((id, version));
+    }
+
+    // 链接：https://figshare.com/articles/dataset/<title>/1234567[/2]
+    let numbers: Vec<&str> = input.rsplit('/').take(2).take_while(|s| is_number(s)).collect();
+    match numbers.as_slice() {
+        [version, id] if input.contains("/articles/") => Some((id, Some(version))),
+        [id, ..] => Some((id, None)),
+        [] => None,
+    }
+}
+
+pub async fn fetch_figshare_urls(article: &str, revision: &str, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<DownloadItem>> {
+    let (id, version) = article_id(article).ok_or_else(|| anyhow!("无法识别的 Figshare 文章: {}", article))?;
+    let version = version.or(Some(revision).filter(|r| *r != "latest"));
+    let api_url = match version {
+        Some(v) => format!("https://api.figshare.com/v2/articles/{}/versions/{}", id, v),
+        None => format!("https://api.figshare.com/v2/articles/{}", id),
+    };
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let resp = client.get(&api_url).headers(headers).send().await.context("请求 Figshare 文章失败")?;
+    if !resp.status().is_success() {
+        bail!("请求失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 Figshare 响应失败")?;
+    let parsed: FigshareArticle = serde_json::from_str(&body).context("解析 Figshare 响应 JSON 失败")?;
+
+    let mut items = Vec::with_capacity(parsed.files.len());
+    for file in parsed.files.into_iter().filter(|f| !f.is_link_only) {
+        let mut url = Url::parse(&file.download_url).context("无效的 Figshare 下载链接")?;
+        url.set_fragment(Some(&file.name));
+        let md5 = file.computed_md5.or(file.supplied_md5).filter(|h| !h.is_empty());
+        items.push(DownloadItem {
+            url: url.to_string(),
+            hash: md5.map(|h| format!("md5:{}", h)),
+            size: file.size,
+            ..Default::default()
+        });
+    }
+
+    if items.is_empty() {
+        bail!("文件列表为空");
+    }
+    Ok(items)
+}
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use super::{DownloadItem, FetchOptions, Provider, Revision, RevisionKind};
+use crate::utils::NetworkOptions;
+
+const DEFAULT_ENDPOINT: &str = "https://gitlab.com";
+
+/// 同时查询文件 sha256 的请求数
+const HEAD_CONCURRENCY: usize = 8;
+
+/// GitLab 仓库文件与 Release 附件（gitlab.com 或通过 `--endpoint` 指定的自建实例）。
+///
+/// - `group/project`：列出指定 ref 下的全部仓库文件，通过 API raw 链接下载；
+/// - `group/project/-/releases/<tag>`：列出该 Release 的附件链接。
+///
+/// token 以 `PRIVATE-TOKEN` 请求头发送。
+pub struct GitLab;
+
+#[async_trait]
+impl Provider for GitLab {
+    fn name(&self) -> &str {
+        "gitlab"
+    }
+
+    fn default_revision(&self) -> &str {
+        "main"
+    }
+
+    fn owns_url(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|h| h == "gitlab.com" || h.ends_with(".gitlab.com"))
+            || super::on_endpoint(self.name(), url)
+    }
+
+    fn relative_path(&self, url: &Url) -> Option<String> {
+        // /api/v4/projects/<id>/repository/files/<编码后的路径>/raw
+        let segments: Vec<&str> = url.path_segments()?.collect();
+        match segments.as_slice() {
+            ["api", "v4", "projects", _, "repository", "files", path, "raw"] => {
+                percent_decode_str(path).decode_utf8().ok().map(|p| p.into_owned())
+            }
+            _ => None,
+        }
+    }
+
+    fn auth_headers(&self, token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(token) {
+            headers.insert("PRIVATE-TOKEN", value);
+        }
+        headers
+    }
+
+    async fn list_files(&self, model: &str, revision: &str, opts: &FetchOptions) -> Result<Vec<DownloadItem>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        let endpoint = super::endpoint_or(opts, DEFAULT_ENDPOINT);
+        fetch_gitlab_urls(endpoint, model, revision, headers, &opts.network).await
+    }
+
+    async fn list_revisions(&self, model: &str, opts: &FetchOptions) -> Result<Vec<Revision>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        let endpoint = super::endpoint_or(opts, DEFAULT_ENDPOINT);
+        fetch_gitlab_revisions(endpoint, model, headers, &opts.network).await
+    }
+}
+
+#[derive(Deserialize)]
+struct TreeEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct GitRef {
+    name: String,
+    commit: Option<GitCommit>,
+}
+
+#[derive(Deserialize)]
+struct GitCommit {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    assets: ReleaseAssets,
+}
+
+#[derive(Deserialize)]
+struct ReleaseAssets {
+    #[serde(default)]
+    links: Vec<ReleaseLink>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseLink {
+    url: String,
+    direct_asset_url: Option<String>,
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+fn api_client(endpoint: &str, headers: HeaderMap, network: &NetworkOptions) -> (Client, String) {
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .default_headers(headers)
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+    (client, format!("{}/api/v4", endpoint.trim_end_matches('/')))
+}
+
+/// 读取分页接口的全部结果；分页信息通过 X-Next-Page 头返回，最后一页为空
+async fn get_all_pages<T: DeserializeOwned>(client: &Client, url: &str, query: &[(&str, &str)]) -> Result<Vec<T>> {
+    let mut results = Vec::new();
+    let mut page = Some("1".to_string());
+    while let Some(current) = page.take() {
+        let resp = client
+            .get(url)
+            .query(query)
+            .query(&[("per_page", "100"), ("page", current.as_str())])
+            .send()
+            .await
+            .context("请求 GitLab API 失败")?;
+        if !resp.status().is_success() {
+            bail!("请求失败，状态码：{}", resp.status());
+        }
+        page = resp
+            .headers()
+            .get("x-next-page")
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string);
+
+        let body = resp.text().await.context("读取 GitLab 响应失败")?;
+        let entries: Vec<T> = serde_json::from_str(&body).context("解析 GitLab 响应 JSON 失败")?;
+        results.extend(entries);
+    }
+    Ok(results)
+}
+
+pub async fn fetch_gitlab_revisions(endpoint: &str, model: &str, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<Revision>> {
+    let (client, api) = api_client(endpoint, headers, network);
+    let project_api = format!("{}/projects/{}/repository", api, encode(model));
+
+    let branches: Vec<GitRef> = get_all_pages(&client, &format!("{}/branches", project_api), &[]).await?;
+    let tags: Vec<GitRef> = get_all_pages(&client, &format!("{}/tags", project_api), &[]).await?;
+    Ok(branches
+        .into_iter()
+        .map(|r| (r, RevisionKind::Branch))
+        .chain(tags.into_iter().map(|r| (r, RevisionKind::Tag)))
+        .map(|(r, kind)| Revision { name: r.name, kind, commit: r.commit.map(|c| c.id) })
+        .collect())
+}
+
+pub async fn fetch_gitlab_urls(
+    endpoint: &str,
+    model: &str,
+    revision: &str,
+    headers: HeaderMap,
+    network: &NetworkOptions,
+) -> Result<Vec<DownloadItem>> {
+    let (client, api) = api_client(endpoint, headers, network);
+
+    let items = match model.split_once("/-/releases/") {
+        Some((project, tag)) => fetch_release_assets(&client, &api, project, tag).await?,
+        None => fetch_repository_files(&client, &api, model, revision).await?,
+    };
+    if items.is_empty() {
+        bail!("文件列表为空");
+    }
+    Ok(items)
+}
+
+async fn fetch_release_assets(client: &Client, api: &str, project: &str, tag: &str) -> Result<Vec<DownloadItem>> {
+    let api_url = format!("{}/projects/{}/releases/{}", api, encode(project), encode(tag));
+    let resp = client.get(&api_url).send().await.context("请求 GitLab Release 失败")?;
+    if !resp.status().is_success() {
+        bail!("请求失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 GitLab 响应失败")?;
+    let release: Release = serde_json::from_str(&body).context("解析 GitLab Release JSON 失败")?;
+
+    Ok(release
+        .assets
+        .links
+        .into_iter()
+        .map(|link| DownloadItem {
+            url: link.direct_asset_url.unwrap_or(link.url),
+            ..Default::default()
+        })
+        .collect())
+}
+
+async fn fetch_repository_files(client: &Client, api: &str, project: &str, revision: &str) -> Result<Vec<DownloadItem>> {
+    let project_api = format!("{}/projects/{}", api, encode(project));
+
+    let tree_url = format!("{}/repository/tree", project_api);
+    let entries: Vec<TreeEntry> =
+        get_all_pages(client, &tree_url, &[("ref", revision), ("recursive", "true")]).await?;
+    let paths: Vec<String> = entries.into_iter().filter(|e| e.kind == "blob").map(|e| e.path).collect();
+
+    // tree 接口只有 git blob id，sha256 与大小需逐个通过 HEAD 文件接口获取
+    futures::stream::iter(paths)
+        .map(|path| {
+            let file_api = format!("{}/repository/files/{}", project_api, encode(&path));
+            async move {
+                let resp = client
+                    .head(&file_api)
+                    .query(&[("ref", revision)])
+                    .send()
+                    .await
+                    .context("请求 GitLab 文件信息失败")?;
+                if !resp.status().is_success() {
+                    return Err(anyhow!("请求 {} 失败，状态码：{}", path, resp.status()));
+                }
+                let hash = resp
+                    .headers()
+                    .get("x-gitlab-content-sha256")
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                let size = resp
+                    .headers()
+                    .get("x-gitlab-size")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok());
+                Ok(DownloadItem {
+                    url: format!("{}/raw?ref={}", file_api, encode(revision)),
+                    hash,
+                    size,
+                    ..Default::default()
+                })
+            }
+        })
+        .buffered(HEAD_CONCURRENCY)
+        .try_collect()
+        .await
+}
@@ -0,0 +1,283 @@
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::header::{self, HeaderMap};
+use serde::Deserialize;
+use url::Url;
+
+use super::{DownloadItem, FetchOptions, Provider, RepoType, Revision, RevisionKind, SearchResult};
+use crate::utils::NetworkOptions;
+
+const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+
+/// 链接中单个路径段需要转义的字符 (含 `/`，使 `refs/pr/1` 这样的 revision 保持为一段)
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'`').add(b'{').add(b'}').add(b'/');
+
+fn encode_segment(segment: &str) -> String {
+    utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+}
+
+/// Hugging Face Hub，可通过 `--endpoint` 指向 hf-mirror.com 等镜像或内部部署
+pub struct HuggingFace;
+
+#[async_trait]
+impl Provider for HuggingFace {
+    fn name(&self) -> &str {
+        "huggingface"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &["hf"]
+    }
+
+    fn default_revision(&self) -> &str {
+        "main"
+    }
+
+    fn repo_types(&self) -> &[RepoType] {
+        &[RepoType::Model, RepoType::Dataset, RepoType::Space]
+    }
+
+    fn owns_url(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|h| h == "huggingface.co" || h == "hf.co") || super::on_endpoint(self.name(), url)
+    }
+
+    fn relative_path(&self, url: &Url) -> Option<String> {
+        super::path_after_resolve(url)
+    }
+
+    fn repo_id(&self, url: &Url) -> Option<String> {
+        super::repo_before_resolve(url)
+    }
+
+    async fn list_files(&self, model: &str, revision: &str, opts: &FetchOptions) -> Result<Vec<DownloadItem>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        fetch_huggingface_urls(super::endpoint_or(opts, DEFAULT_ENDPOINT), model, revision, opts.repo_type, headers, &opts.network).await
+    }
+
+    async fn list_revisions(&self, model: &str, opts: &FetchOptions) -> Result<Vec<Revision>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        fetch_huggingface_revisions(super::endpoint_or(opts, DEFAULT_ENDPOINT), model, opts.repo_type, headers, &opts.network).await
+    }
+
+    async fn search(&self, query: &str, limit: usize, opts: &FetchOptions) -> Result<Vec<SearchResult>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        search_huggingface(super::endpoint_or(opts, DEFAULT_ENDPOINT), query, limit, opts.repo_type, headers, &opts.network).await
+    }
+
+    /// `resolve` 链接跳转到带签名的 CDN 地址，过期后再次请求 `resolve` 即可得到新的地址
+    async fn refresh_url(&self, url: &Url, opts: &FetchOptions) -> Result<Option<String>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        super::resolve_redirect(url, headers, &opts.network).await
+    }
+}
+
+#[derive(Deserialize)]
+struct HuggingFaceEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+    /// LFS 文件为实际大小，普通文件为 blob 大小（两者一致）
+    size: Option<u64>,
+    lfs: Option<HuggingFaceLfs>,
+}
+
+#[derive(Deserialize)]
+struct HuggingFaceLfs {
+    /// LFS 对象的 sha256（普通 git 文件只有 blob sha1，无法用于校验）
+    oid: String,
+}
+
+/// 下载链接中仓库名前的前缀：模型没有前缀，数据集与 space 为 `datasets/`、`spaces/`
+fn url_prefix(repo_type: RepoType) -> &'static str {
+    match repo_type {
+        RepoType::Model => "",
+        RepoType::Dataset => "datasets/",
+        RepoType::Space => "spaces/",
+    }
+}
+
+pub async fn fetch_huggingface_urls(
+    endpoint: &str,
+    model: &str,
+    revision: &str,
+    repo_type: RepoType,
+    headers: HeaderMap,
+    network: &NetworkOptions,
+) -> Result<Vec<DownloadItem>> {
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let mut next_url = Some(format!(
+        "{}/api/{}/{}/tree/{}?recursive=true",
+        endpoint, repo_type.plural(), model, encode_segment(revision)
+    ));
+    let mut items = Vec::new();
+
+    while let Some(api_url) = next_url.take() {
+        let resp = client
+            .get(&api_url)
+            .headers(headers.clone())
+            .send()
+            .await
+            .context("请求 HuggingFace 文件列表失败")?;
+        if !resp.status().is_success() {
+            bail!("请求失败，状态码：{}", resp.status());
+        }
+
+        // 分页信息通过 Link 头返回：<https://...&cursor=...>; rel="next"
+        next_url = resp
+            .headers()
+            .get(header::LINK)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_next_link);
+
+        let body = resp.text().await.context("读取 HuggingFace 响应失败")?;
+        let entries: Vec<HuggingFaceEntry> =
+            serde_json::from_str(&body).context("解析 HuggingFace 响应 JSON 失败")?;
+
+        for entry in entries {
+            if entry.kind != "file" {
+                continue;
+            }
+            let url = format!(
+                "{}/{}{}/resolve/{}/{}",
+                endpoint,
+                url_prefix(repo_type),
+                model,
+                encode_segment(revision),
+                entry.path.split('/').map(encode_segment).collect::<Vec<_>>().join("/")
+            );
+            items.push(DownloadItem {
+                url,
+                hash: entry.lfs.map(|lfs| lfs.oid),
+                size: entry.size,
+                ..Default::default()
+            });
+        }
+    }
+
+    if items.is_empty() {
+        bail!("文件列表为空");
+    }
+
+    Ok(items)
+}
+
+#[derive(Deserialize)]
+struct HuggingFaceRefs {
+    #[serde(default)]
+    branches: Vec<HuggingFaceRef>,
+    #[serde(default)]
+    tags: Vec<HuggingFaceRef>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HuggingFaceRef {
+    name: String,
+    target_commit: Option<String>,
+}
+
+pub async fn fetch_huggingface_revisions(endpoint: &str, model: &str, repo_type: RepoType, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<Revision>> {
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let api_url = format!("{}/api/{}/{}/refs", endpoint, repo_type.plural(), model);
+    let resp = client.get(&api_url).headers(headers).send().await.context("请求 HuggingFace 分支列表失败")?;
+    if !resp.status().is_success() {
+        bail!("请求失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 HuggingFace 响应失败")?;
+    let refs: HuggingFaceRefs = serde_json::from_str(&body).context("解析 HuggingFace 响应 JSON 失败")?;
+
+    let branches = refs.branches.into_iter().map(|r| (r, RevisionKind::Branch));
+    let tags = refs.tags.into_iter().map(|r| (r, RevisionKind::Tag));
+    Ok(branches
+        .chain(tags)
+        .map(|(r, kind)| Revision { name: r.name, kind, commit: r.target_commit })
+        .collect())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HuggingFaceModel {
+    id: String,
+    downloads: Option<u64>,
+    likes: Option<u64>,
+    used_storage: Option<u64>,
+}
+
+pub async fn search_huggingface(
+    endpoint: &str,
+    query: &str,
+    limit: usize,
+    repo_type: RepoType,
+    headers: HeaderMap,
+    network: &NetworkOptions,
+) -> Result<Vec<SearchResult>> {
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let limit = limit.to_string();
+    // space 没有下载量，按点赞数排序
+    let (sort, expand): (&str, &[&str]) = match repo_type {
+        RepoType::Space => ("likes", &["likes"]),
+        _ => ("downloads", &["downloads", "likes", "usedStorage"]),
+    };
+    let mut params = vec![("search", query), ("sort", sort), ("direction", "-1"), ("limit", limit.as_str())];
+    params.extend(expand.iter().map(|field| ("expand[]", *field)));
+    let resp = client
+        .get(format!("{}/api/{}", endpoint, repo_type.plural()))
+        .query(&params)
+        .headers(headers)
+        .send()
+        .await
+        .context("请求 HuggingFace 搜索失败")?;
+    if !resp.status().is_success() {
+        bail!("请求失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 HuggingFace 响应失败")?;
+    let models: Vec<HuggingFaceModel> = serde_json::from_str(&body).context("解析 HuggingFace 响应 JSON 失败")?;
+
+    Ok(models
+        .into_iter()
+        .map(|m| SearchResult { id: m.id, downloads: m.downloads, likes: m.likes, size: m.used_storage })
+        .collect())
+}
+
+fn parse_next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let mut segments = part.split(';');
+        let target = segments.next()?.trim();
+        let is_next = segments.any(|s| s.trim() == "rel=\"next\"");
+        if is_next {
+            Some(target.trim_start_matches('<').trim_end_matches('>').to_string())
+        } else {
+            None
+        }
+    })
+}
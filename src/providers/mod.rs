@@ -1,18 +1,504 @@
 pub mod modelscope;
+pub mod huggingface;
+pub mod gitlab;
+pub mod zenodo;
+pub mod figshare;
+pub mod oci;
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
+use glob::Pattern;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use url::Url;
 
-#[derive(Clone, Debug)]
+use crate::utils::{get_filename_from_url, percent_decode, sanitize_relative_path, NetworkOptions};
+
+#[derive(Clone, Debug, Default)]
 pub struct DownloadItem {
     pub url: String,
+    /// 备用下载地址，主地址失败时按顺序尝试
+    pub mirrors: Vec<String>,
     pub hash: Option<String>,
+    /// 该文件单独的限速 (字节/秒)，覆盖 `--rate-limit-per-file`
+    pub rate_limit: Option<u32>,
+    /// provider 已知的文件大小 (字节)，存在时下载前不再发送 HEAD 请求
+    pub size: Option<u64>,
+    /// 下载目录下的保存路径，覆盖根据 URL 推导的文件名 (来自任务文件或 Content-Disposition)
+    pub name: Option<String>,
+    /// 随任务提供的 OpenPGP 分离签名 (ASCII armor，来自 Metalink)，`--verify-sig` 时优先于下载 `.asc`/`.sig`
+    pub signature: Option<String>,
+    /// Metalink 提供的分块哈希，`--delta` 时用于复用旧版本中未变化的块
+    pub pieces: Option<crate::delta::PieceHashes>,
+    /// 计划开始时间 (任务文件中的 `at=`)，到达之前不开始下载
+    pub schedule: Option<crate::schedule::Schedule>,
+    /// 调度优先级 (任务文件中的 `priority=`)，数值大的先开始下载，未设置时为 0
+    pub priority: Option<i32>,
+}
+
+impl DownloadItem {
+    /// 主地址加所有镜像地址
+    pub fn sources(&self) -> Vec<String> {
+        std::iter::once(&self.url).chain(self.mirrors.iter()).cloned().collect()
+    }
+}
+
+/// 仓库类型，对应 `--repo-type`
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoType {
+    #[default]
+    Model,
+    Dataset,
+    Space,
+}
+
+impl RepoType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Model => "model",
+            Self::Dataset => "dataset",
+            Self::Space => "space",
+        }
+    }
+
+    /// API 与链接中的复数形式，如 `datasets`
+    pub fn plural(&self) -> &'static str {
+        match self {
+            Self::Model => "models",
+            Self::Dataset => "datasets",
+            Self::Space => "spaces",
+        }
+    }
+}
+
+/// 版本类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevisionKind {
+    Branch,
+    Tag,
+}
+
+/// 仓库的一个分支或 tag，可作为 `--branch` 使用
+#[derive(Clone, Debug, Serialize)]
+pub struct Revision {
+    pub name: String,
+    pub kind: RevisionKind,
+    /// 指向的提交，provider 未提供时为空
+    pub commit: Option<String>,
+}
+
+/// `rdl search` 的一条结果
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SearchResult {
+    /// 可直接用于 `--fetch-list` 的仓库名
+    pub id: String,
+    pub downloads: Option<u64>,
+    pub likes: Option<u64>,
+    /// 仓库占用的存储空间（字节），provider 未提供时为空
+    pub size: Option<u64>,
+}
+
+/// 调用 provider 时的通用参数
+#[derive(Clone, Debug, Default)]
+pub struct FetchOptions {
+    /// 访问私有/受限仓库所需的 token
+    pub token: Option<String>,
+    /// 请求 provider API 时使用的代理与 TLS 设置
+    pub network: NetworkOptions,
+    /// 镜像或自建实例的地址（如 `https://hf-mirror.com`、`https://gitlab.example.com`），
+    /// 替换 API 与下载链接的主机；不支持的 provider 会忽略
+    pub endpoint: Option<String>,
+    /// 只保留相对路径匹配任一模式的文件（为空时保留全部）
+    pub include: Vec<Pattern>,
+    /// 去掉相对路径匹配任一模式的文件，优先于 `include`
+    pub exclude: Vec<Pattern>,
+    /// 仓库类型（模型、数据集或 space）
+    pub repo_type: RepoType,
+}
+
+/// 模型仓库适配接口。新增仓库只需实现该 trait 并注册到 `ProviderRegistry`。
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// provider 名称（小写），用于 `--provider` 匹配
+    fn name(&self) -> &str;
+
+    /// 额外的别名，例如 huggingface 的 `hf`
+    fn aliases(&self) -> &[&str] {
+        &[]
+    }
+
+    /// 未指定 `--branch` 时使用的默认分支
+    fn default_revision(&self) -> &str {
+        "master"
+    }
+
+    /// 支持的仓库类型，`--repo-type` 不在其中时直接报错
+    fn repo_types(&self) -> &[RepoType] {
+        &[RepoType::Model]
+    }
+
+    /// 判断下载链接是否属于该 provider，用于决定是否附加 token
+    fn owns_url(&self, _url: &Url) -> bool {
+        false
+    }
+
+    /// 从下载链接中解析文件在仓库内的相对路径（如 `tokenizer/vocab.json`）
+    fn relative_path(&self, _url: &Url) -> Option<String> {
+        None
+    }
+
+    /// 从下载链接中解析仓库 ID（如 `Qwen/Qwen2.5-7B-Instruct`），供 `--out` 模板的 `{model}` 使用
+    fn repo_id(&self, _url: &Url) -> Option<String> {
+        None
+    }
+
+    /// 根据 token 生成认证请求头，默认使用 `Authorization: Bearer <token>`
+    fn auth_headers(&self, token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        headers
+    }
+
+    /// 列出仓库在指定 revision 下的所有文件
+    async fn list_files(&self, model: &str, revision: &str, opts: &FetchOptions) -> Result<Vec<DownloadItem>>;
+
+    /// 列出仓库的分支与 tag，供 `rdl revisions` 使用
+    async fn list_revisions(&self, _model: &str, _opts: &FetchOptions) -> Result<Vec<Revision>> {
+        bail!("{} 不支持列出分支/版本", self.name())
+    }
+
+    /// 按关键字搜索仓库，最多返回 `limit` 条，供 `rdl search` 使用
+    async fn search(&self, _query: &str, _limit: usize, _opts: &FetchOptions) -> Result<Vec<SearchResult>> {
+        bail!("{} 不支持搜索", self.name())
+    }
+
+    /// 未提供 `--token` 时下载该链接所需的认证请求头（如镜像仓库的匿名拉取 token），
+    /// 不适用时返回空
+    async fn anonymous_headers(&self, _url: &Url, _opts: &FetchOptions) -> Result<HeaderMap> {
+        Ok(HeaderMap::new())
+    }
+
+    /// 下载中途收到 403（预签名地址过期）时重新解析出新的下载地址，下载器从同一偏移继续；
+    /// 返回 None 表示不支持，按普通失败重试
+    async fn refresh_url(&self, _url: &Url, _opts: &FetchOptions) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// provider 注册表，按名称或别名查找
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn Provider>>,
+}
+
+impl ProviderRegistry {
+    /// 创建空注册表
+    pub fn new() -> Self {
+        Self { providers: Vec::new() }
+    }
+
+    /// 创建包含内置 provider 的注册表
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(modelscope::ModelScope));
+        registry.register(Arc::new(huggingface::HuggingFace));
+        registry.register(Arc::new(oci::Oci));
+        registry.register(Arc::new(gitlab::GitLab));
+        registry.register(Arc::new(zenodo::Zenodo));
+        registry.register(Arc::new(figshare::Figshare));
+        registry
+    }
+
+    /// 注册 provider；同名 provider 会覆盖已有的注册
+    pub fn register(&mut self, provider: Arc<dyn Provider>) {
+        self.providers.retain(|p| p.name() != provider.name());
+        self.providers.push(provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Provider>> {
+        let name = name.to_lowercase();
+        self.providers
+            .iter()
+            .find(|p| p.name() == name || p.aliases().contains(&name.as_str()))
+            .cloned()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::with_builtin()
+    }
+}
+
+/// 按名称或别名查找内置 provider，并检查其是否支持 `opts.repo_type`
+pub fn find_provider(name: &str, opts: &FetchOptions) -> Result<Arc<dyn Provider>> {
+    let registry = ProviderRegistry::default();
+    let Some(p) = registry.get(name) else {
+        bail!("暂不支持的 provider: {}（可用：{}）", name, registry.names().join(", "));
+    };
+    if !p.repo_types().contains(&opts.repo_type) {
+        bail!("{} 不支持仓库类型 {}", p.name(), opts.repo_type.as_str());
+    }
+    Ok(p)
 }
 
 /// 根据 provider 名称获取下载链接列表
-/// 当前仅支持 modelscope，后续可在此扩展 huggingface 等。
-pub async fn fetch_urls(provider: &str, model: &str, revision: &str) -> Result<Vec<DownloadItem>> {
-    match provider.to_lowercase().as_str() {
-        "modelscope" => modelscope::fetch_modelscope_urls(model, revision).await,
-        _ => bail!("暂不支持的 provider: {}", provider),
+/// 未指定 revision 时使用各 provider 的默认分支（modelscope: master，huggingface: main，oci: latest，gitlab: main）。
+pub async fn fetch_urls(provider: &str, model: &str, revision: Option<&str>, opts: &FetchOptions) -> Result<Vec<DownloadItem>> {
+    let p = find_provider(provider, opts)?;
+    let items = p.list_files(model, revision.unwrap_or(p.default_revision()), opts).await?;
+    if opts.include.is_empty() && opts.exclude.is_empty() {
+        return Ok(items);
+    }
+
+    let total = items.len();
+    let items = filter_items(items, &opts.include, &opts.exclude);
+    if items.is_empty() {
+        bail!("{} 个文件中没有符合 --include/--exclude 的文件", total);
+    }
+    Ok(items)
+}
+
+/// 按仓库内相对路径（无法解析时用文件名）过滤文件。`*` 可以跨目录匹配，
+/// 因此 `*.safetensors` 也会匹配子目录中的文件。
+pub fn filter_items(items: Vec<DownloadItem>, include: &[Pattern], exclude: &[Pattern]) -> Vec<DownloadItem> {
+    items
+        .into_iter()
+        .filter(|item| {
+            let path = repo_path(&item.url)
+                .or_else(|| get_filename_from_url(&item.url).ok())
+                .unwrap_or_default();
+            (include.is_empty() || include.iter().any(|p| p.matches(&path)))
+                && !exclude.iter().any(|p| p.matches(&path))
+        })
+        .collect()
+}
+
+/// `--endpoint` 指定的镜像或自建实例：(provider 名称, 地址)
+static ENDPOINT: OnceLock<(String, Url)> = OnceLock::new();
+
+/// 记录 `-P <provider> --endpoint <url>`，使镜像生成的下载链接同样归属该 provider
+/// （附加 token、保留目录结构、`--out` 模板）。进程内只需调用一次
+pub fn set_endpoint(provider: &str, endpoint: &str) -> Result<()> {
+    let Some(p) = ProviderRegistry::default().get(provider) else {
+        bail!("暂不支持的 provider: {}", provider);
+    };
+    let Ok(url) = Url::parse(endpoint) else {
+        bail!("无效的 --endpoint：{}", endpoint);
+    };
+    if url.host_str().is_none() {
+        bail!("--endpoint 缺少主机名：{}", endpoint);
+    }
+    let _ = ENDPOINT.set((p.name().to_string(), url));
+    Ok(())
+}
+
+/// `url` 是否位于为 `provider` 指定的 `--endpoint` 上（主机与端口相同）
+pub(crate) fn on_endpoint(provider: &str, url: &Url) -> bool {
+    ENDPOINT.get().is_some_and(|(name, endpoint)| {
+        name == provider
+            && endpoint.host_str() == url.host_str()
+            && endpoint.port_or_known_default() == url.port_or_known_default()
+    })
+}
+
+/// 未指定 `--endpoint` 时使用 `default`；去掉末尾的 `/`
+pub(crate) fn endpoint_or<'a>(opts: &'a FetchOptions, default: &'a str) -> &'a str {
+    opts.endpoint.as_deref().unwrap_or(default).trim_end_matches('/')
+}
+
+/// 为属于某个 provider 的下载链接生成认证请求头；无 token 或不匹配时返回空。
+/// `gs://` 对象使用环境变量中的 GCS 访问令牌
+pub fn auth_headers_for(url: &str, token: Option<&str>) -> HeaderMap {
+    if crate::object_store::is_object_url(url) {
+        return crate::object_store::auth_headers(url);
+    }
+    let (Some(token), Ok(parsed)) = (token, Url::parse(url)) else {
+        return HeaderMap::new();
+    };
+    ProviderRegistry::default()
+        .providers
+        .iter()
+        .find(|p| p.owns_url(&parsed))
+        .map(|p| p.auth_headers(token))
+        .unwrap_or_default()
+}
+
+/// 下载请求使用的认证请求头：有 `--token` 时同 [`auth_headers_for`]，否则由 provider
+/// 申请匿名凭据（见 [`Provider::anonymous_headers`]），申请失败时为空
+pub async fn download_headers_for(url: &str, token: Option<&str>, network: &NetworkOptions) -> HeaderMap {
+    let headers = auth_headers_for(url, token);
+    if !headers.is_empty() || crate::object_store::is_object_url(url) {
+        return headers;
+    }
+    let Ok(parsed) = Url::parse(url) else {
+        return headers;
+    };
+    let opts = FetchOptions { network: network.clone(), ..Default::default() };
+    for provider in &ProviderRegistry::default().providers {
+        if let Ok(anonymous) = provider.anonymous_headers(&parsed, &opts).await {
+            if !anonymous.is_empty() {
+                return anonymous;
+            }
+        }
+    }
+    headers
+}
+
+/// 由下载链接所属的 provider 重新解析出新的下载地址（见 [`Provider::refresh_url`]），
+/// 非 provider 链接或 provider 不支持时返回 None
+pub async fn refresh_url_for(url: &str, token: Option<&str>, network: &NetworkOptions) -> Result<Option<String>> {
+    let Ok(parsed) = Url::parse(url) else {
+        return Ok(None);
+    };
+    let registry = ProviderRegistry::default();
+    let Some(provider) = registry.providers.iter().find(|p| p.owns_url(&parsed)) else {
+        return Ok(None);
+    };
+    let opts = FetchOptions {
+        token: token.map(str::to_string),
+        network: network.clone(),
+        ..Default::default()
+    };
+    provider.refresh_url(&parsed, &opts).await
+}
+
+/// 请求 `resolve` 链接但不跟随跳转，返回其指向的预签名地址；直接返回文件内容
+/// (未存放在 CDN 的小文件) 时为 None
+pub(crate) async fn resolve_redirect(url: &Url, headers: HeaderMap, network: &NetworkOptions) -> Result<Option<String>> {
+    let client = network
+        .client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+    // 只取第一个字节，避免在不跳转时下载整个文件
+    let resp = client
+        .get(url.as_str())
+        .headers(headers)
+        .header(header::RANGE, "bytes=0-0")
+        .send()
+        .await
+        .context("重新解析下载地址失败")?;
+    if !resp.status().is_redirection() {
+        if !resp.status().is_success() {
+            bail!("重新解析下载地址失败，状态码：{}", resp.status());
+        }
+        return Ok(None);
+    }
+    let location = resp
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .and_then(|l| url.join(l).ok())
+        .filter(|l| matches!(l.scheme(), "http" | "https"));
+    Ok(location.map(String::from))
+}
+
+/// 对属于某个 provider 的下载链接，返回其在仓库内的相对路径（已清理），用于保留目录结构
+pub fn relative_path_for(url: &str) -> Option<PathBuf> {
+    repo_path(url).and_then(|path| sanitize_relative_path(&path))
+}
+
+/// provider 给出的原始相对路径（未清理）
+fn repo_path(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let registry = ProviderRegistry::default();
+    let provider = registry.providers.iter().find(|p| p.owns_url(&parsed))?;
+    provider.relative_path(&parsed)
+}
+
+/// `--layout provider-tree` 下 provider 链接的保存目录：`<provider>/[datasets|spaces/]<model>`，
+/// 与 `--fetch-list` 默认生成清单的目录一致；解析不出仓库 ID 时只按 provider 分目录，
+/// 非 provider 链接返回 None
+pub fn layout_dir(url: &str) -> Option<PathBuf> {
+    let parsed = Url::parse(url).ok()?;
+    let registry = ProviderRegistry::default();
+    let provider = registry.providers.iter().find(|p| p.owns_url(&parsed))?;
+    let mut dir = PathBuf::from(provider.name());
+    if let Some(model) = provider.repo_id(&parsed).and_then(|m| sanitize_relative_path(&m)) {
+        let kind = parsed.path_segments().and_then(|mut s| s.next());
+        if let Some(kind) = kind.filter(|k| matches!(*k, "datasets" | "spaces")) {
+            dir.push(kind);
+        }
+        dir.push(model);
+    }
+    Some(dir)
+}
+
+/// `--out` 模板支持的占位符
+pub const OUTPUT_PLACEHOLDERS: &[&str] = &["provider", "model", "host", "path", "filename"];
+
+/// 检查 `--out` 模板中的占位符是否都受支持
+pub fn check_output_template(template: &str) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            bail!("模板中的 `{{` 没有对应的 `}}`：{}", template);
+        };
+        let name = &rest[start + 1..start + len];
+        if !OUTPUT_PLACEHOLDERS.contains(&name) {
+            bail!("未知的占位符 {{{}}}，可用：{}", name, OUTPUT_PLACEHOLDERS.join(", "));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    Ok(())
+}
+
+/// 按 `--out` 模板生成保存路径。`path` 为不使用模板时的相对路径：
+/// `{path}` 即该路径，`{filename}` 为其最后一段；`{provider}`、`{model}` 只对
+/// provider 链接有值，`{host}` 为链接的主机名。为空的占位符留下的多余 `/`
+/// 会在清理路径时去掉
+pub fn render_output_template(template: &str, url: &str, path: &Path) -> String {
+    let parsed = Url::parse(url).ok();
+    let registry = ProviderRegistry::default();
+    let provider = parsed
+        .as_ref()
+        .and_then(|u| registry.providers.iter().find(|p| p.owns_url(u)));
+    let values = [
+        ("provider", provider.map(|p| p.name().to_string())),
+        ("model", provider.zip(parsed.as_ref()).and_then(|(p, u)| p.repo_id(u))),
+        ("host", parsed.as_ref().and_then(|u| u.host_str().map(str::to_string))),
+        ("path", Some(path.to_string_lossy().replace('\\', "/"))),
+        ("filename", path.file_name().map(|f| f.to_string_lossy().to_string())),
+    ];
+    values.iter().fold(template.to_string(), |acc, (name, value)| {
+        acc.replace(&format!("{{{}}}", name), value.as_deref().unwrap_or(""))
+    })
+}
+
+/// 解析 `[<类型>/]<org>/<name>/resolve/...` 形式链接中的仓库 ID，
+/// 去掉 `models/`、`datasets/`、`spaces/` 前缀
+pub(crate) fn repo_before_resolve(url: &Url) -> Option<String> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let pos = segments.iter().position(|s| *s == "resolve")?;
+    let repo = match &segments[..pos] {
+        ["models" | "datasets" | "spaces", rest @ ..] => rest,
+        rest => rest,
+    };
+    if repo.is_empty() {
+        return None;
+    }
+    Some(repo.iter().map(|s| percent_decode(s)).collect::<Vec<_>>().join("/"))
+}
+
+/// 解析 `.../resolve/<revision>/<path>` 形式链接中 `<path>` 部分
+pub(crate) fn path_after_resolve(url: &Url) -> Option<String> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let pos = segments.iter().position(|s| *s == "resolve")?;
+    let rest = segments.get(pos + 2..)?;
+    if rest.is_empty() {
+        return None;
     }
+    Some(rest.iter().map(|s| percent_decode(s)).collect::<Vec<_>>().join("/"))
 }
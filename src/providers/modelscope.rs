@@ -1,18 +1,95 @@
 use anyhow::{Context, Result, bail, anyhow};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
 use serde::Deserialize;
 
-use super::DownloadItem;
+use url::Url;
+
+use super::{DownloadItem, FetchOptions, Provider, RepoType, Revision, RevisionKind, SearchResult};
+use crate::utils::NetworkOptions;
+
+const DEFAULT_ENDPOINT: &str = "https://modelscope.cn";
+
+/// ModelScope 魔搭社区，可通过 `--endpoint` 指向其他区域的站点 (如 `https://www.modelscope.ai`) 或内部镜像
+pub struct ModelScope;
+
+#[async_trait]
+impl Provider for ModelScope {
+    fn name(&self) -> &str {
+        "modelscope"
+    }
+
+    fn repo_types(&self) -> &[RepoType] {
+        &[RepoType::Model, RepoType::Dataset]
+    }
+
+    fn owns_url(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|h| h == "modelscope.cn" || h.ends_with(".modelscope.cn"))
+            || super::on_endpoint(self.name(), url)
+    }
+
+    fn relative_path(&self, url: &Url) -> Option<String> {
+        super::path_after_resolve(url)
+    }
+
+    fn repo_id(&self, url: &Url) -> Option<String> {
+        super::repo_before_resolve(url)
+    }
+
+    async fn list_files(&self, model: &str, revision: &str, opts: &FetchOptions) -> Result<Vec<DownloadItem>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        let endpoint = super::endpoint_or(opts, DEFAULT_ENDPOINT);
+        match opts.repo_type {
+            RepoType::Dataset => fetch_modelscope_dataset_urls(endpoint, model, revision, headers, &opts.network).await,
+            _ => fetch_modelscope_urls(endpoint, model, revision, headers, &opts.network).await,
+        }
+    }
+
+    async fn list_revisions(&self, model: &str, opts: &FetchOptions) -> Result<Vec<Revision>> {
+        if opts.repo_type != RepoType::Model {
+            bail!("ModelScope 暂只支持列出模型的分支");
+        }
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        fetch_modelscope_revisions(super::endpoint_or(opts, DEFAULT_ENDPOINT), model, headers, &opts.network).await
+    }
+
+    async fn search(&self, query: &str, limit: usize, opts: &FetchOptions) -> Result<Vec<SearchResult>> {
+        if opts.repo_type != RepoType::Model {
+            bail!("ModelScope 暂只支持搜索模型");
+        }
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        search_modelscope(super::endpoint_or(opts, DEFAULT_ENDPOINT), query, limit, headers, &opts.network).await
+    }
+
+    /// `resolve` 链接跳转到带签名的 CDN 地址，过期后再次请求 `resolve` 即可得到新的地址
+    async fn refresh_url(&self, url: &Url, opts: &FetchOptions) -> Result<Option<String>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        super::resolve_redirect(url, headers, &opts.network).await
+    }
+}
 
 #[derive(Deserialize)]
 struct ModelScopeResponse {
-    #[serde(rename = "Code")]
-    code: Option<i32>,
     #[serde(rename = "Data")]
     data: Option<ModelScopeData>,
     #[serde(rename = "Message")]
     message: Option<String>,
-    #[serde(rename = "Success")]
-    success: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -27,16 +104,18 @@ struct ModelScopeFile {
     path: String,
     #[serde(rename = "Sha256")]
     sha256: String,
+    #[serde(rename = "Size")]
+    size: Option<u64>,
 }
 
-pub async fn fetch_modelscope_urls(model: &str, revision: &str) -> Result<Vec<DownloadItem>> {
-    let api_url = format!("https://modelscope.cn/api/v1/models/{}/repo/files", model);
-    let client = reqwest::Client::builder()
+pub async fn fetch_modelscope_urls(endpoint: &str, model: &str, revision: &str, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<DownloadItem>> {
+    let api_url = format!("{}/api/v1/models/{}/repo/files", endpoint, model);
+    let client = network.client_builder()
         .user_agent("RustDownloadTool/0.1.0")
         .build()
         .unwrap_or_else(|_| reqwest::Client::new());
 
-    let resp = client.get(&api_url).send().await.context("请求 ModelScope 文件列表失败")?;
+    let resp = client.get(&api_url).headers(headers).send().await.context("请求 ModelScope 文件列表失败")?;
     if !resp.status().is_success() {
         bail!("请求失败，状态码：{}", resp.status());
     }
@@ -44,7 +123,7 @@ pub async fn fetch_modelscope_urls(model: &str, revision: &str) -> Result<Vec<Do
     let body = resp.text().await.context("读取 ModelScope 响应失败")?;
     let parsed: ModelScopeResponse = serde_json::from_str(&body).context("解析 ModelScope 响应 JSON 失败")?;
 
-    let data = parsed.data.ok_or_else(|| anyhow!("响应缺少 Data 字段"))?;
+    let data = parsed.data.ok_or_else(|| anyhow!("响应缺少 Data 字段: {}", parsed.message.unwrap_or_default()))?;
     if data.files.is_empty() {
         bail!("文件列表为空");
     }
@@ -52,7 +131,8 @@ pub async fn fetch_modelscope_urls(model: &str, revision: &str) -> Result<Vec<Do
     let mut items = Vec::with_capacity(data.files.len());
     for file in data.files {
         let url = format!(
-            "https://modelscope.cn/models/{}/resolve/{}/{}",
+            "{}/models/{}/resolve/{}/{}",
+            endpoint,
             model,
             revision,
             file.path
@@ -60,8 +140,236 @@ pub async fn fetch_modelscope_urls(model: &str, revision: &str) -> Result<Vec<Do
         items.push(DownloadItem {
             url,
             hash: Some(file.sha256),
+            size: file.size,
+            ..Default::default()
         });
     }
 
     Ok(items)
 }
+
+#[derive(Deserialize)]
+struct ModelScopeTreeResponse {
+    #[serde(rename = "Data")]
+    data: Option<ModelScopeTreeData>,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeTreeData {
+    #[serde(rename = "Files", default)]
+    files: Vec<ModelScopeTreeEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeTreeEntry {
+    #[serde(rename = "Path")]
+    path: String,
+    /// `blob` 或 `tree`
+    #[serde(rename = "Type")]
+    kind: String,
+    #[serde(rename = "Sha256")]
+    sha256: Option<String>,
+    #[serde(rename = "Size")]
+    size: Option<u64>,
+}
+
+/// 数据集文件列表按页返回，直到某页不足该条数
+const DATASET_PAGE_SIZE: usize = 500;
+
+pub async fn fetch_modelscope_dataset_urls(endpoint: &str, dataset: &str, revision: &str, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<DownloadItem>> {
+    let api_url = format!("{}/api/v1/datasets/{}/repo/tree", endpoint, dataset);
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let page_size = DATASET_PAGE_SIZE.to_string();
+    let mut items = Vec::new();
+    for page in 1.. {
+        let page = page.to_string();
+        let resp = client
+            .get(&api_url)
+            .query(&[
+                ("Revision", revision),
+                ("Root", "/"),
+                ("Recursive", "True"),
+                ("PageNumber", page.as_str()),
+                ("PageSize", page_size.as_str()),
+            ])
+            .headers(headers.clone())
+            .send()
+            .await
+            .context("请求 ModelScope 数据集文件列表失败")?;
+        if !resp.status().is_success() {
+            bail!("请求失败，状态码：{}", resp.status());
+        }
+        let body = resp.text().await.context("读取 ModelScope 响应失败")?;
+        let parsed: ModelScopeTreeResponse = serde_json::from_str(&body).context("解析 ModelScope 响应 JSON 失败")?;
+        let data = parsed
+            .data
+            .ok_or_else(|| anyhow!("响应缺少 Data 字段: {}", parsed.message.unwrap_or_default()))?;
+
+        let count = data.files.len();
+        for entry in data.files.into_iter().filter(|e| e.kind == "blob") {
+            items.push(DownloadItem {
+                url: format!(
+                    "{}/datasets/{}/resolve/{}/{}",
+                    endpoint,
+                    dataset,
+                    revision,
+                    entry.path
+                ),
+                hash: entry.sha256.filter(|h| !h.is_empty()),
+                size: entry.size,
+                ..Default::default()
+            });
+        }
+        if count < DATASET_PAGE_SIZE {
+            break;
+        }
+    }
+
+    if items.is_empty() {
+        bail!("文件列表为空");
+    }
+    Ok(items)
+}
+
+#[derive(Deserialize)]
+struct ModelScopeRevisionsResponse {
+    #[serde(rename = "Data")]
+    data: Option<ModelScopeRevisionsData>,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeRevisionsData {
+    #[serde(rename = "RevisionMap")]
+    revision_map: ModelScopeRevisionMap,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeRevisionMap {
+    #[serde(rename = "Branches")]
+    branches: Option<Vec<ModelScopeRevision>>,
+    #[serde(rename = "Tags")]
+    tags: Option<Vec<ModelScopeRevision>>,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeRevision {
+    #[serde(rename = "Revision")]
+    revision: String,
+    #[serde(rename = "CommitId")]
+    commit_id: Option<String>,
+}
+
+pub async fn fetch_modelscope_revisions(endpoint: &str, model: &str, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<Revision>> {
+    let api_url = format!("{}/api/v1/models/{}/revisions", endpoint, model);
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let resp = client.get(&api_url).headers(headers).send().await.context("请求 ModelScope 分支列表失败")?;
+    if !resp.status().is_success() {
+        bail!("请求失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 ModelScope 响应失败")?;
+    let parsed: ModelScopeRevisionsResponse = serde_json::from_str(&body).context("解析 ModelScope 响应 JSON 失败")?;
+
+    let map = parsed
+        .data
+        .ok_or_else(|| anyhow!("响应缺少 Data 字段: {}", parsed.message.unwrap_or_default()))?
+        .revision_map;
+    let branches = map.branches.unwrap_or_default().into_iter().map(|r| (r, RevisionKind::Branch));
+    let tags = map.tags.unwrap_or_default().into_iter().map(|r| (r, RevisionKind::Tag));
+    Ok(branches
+        .chain(tags)
+        .map(|(r, kind)| Revision { name: r.revision, kind, commit: r.commit_id })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct ModelScopeSearchResponse {
+    #[serde(rename = "Data")]
+    data: Option<ModelScopeSearchData>,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeSearchData {
+    #[serde(rename = "Model")]
+    model: ModelScopeSearchModels,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeSearchModels {
+    #[serde(rename = "Models", default)]
+    models: Vec<ModelScopeSearchModel>,
+}
+
+#[derive(Deserialize)]
+struct ModelScopeSearchModel {
+    /// 组织名
+    #[serde(rename = "Path")]
+    path: String,
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Downloads")]
+    downloads: Option<u64>,
+    #[serde(rename = "Stars")]
+    stars: Option<u64>,
+    #[serde(rename = "StorageSize")]
+    storage_size: Option<u64>,
+}
+
+/// 使用网页端的模型检索接口 (`dolphin/models`) 按名称搜索，结果按下载量排序
+pub async fn search_modelscope(endpoint: &str, query: &str, limit: usize, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<SearchResult>> {
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let body = serde_json::json!({
+        "Name": query,
+        "PageNumber": 1,
+        "PageSize": limit,
+        "SortBy": "DownloadsCount",
+        "Target": "",
+        "Criterion": [],
+        "SingleCriterion": [],
+    });
+    let resp = client
+        .put(format!("{}/api/v1/dolphin/models", endpoint))
+        .headers(headers)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .context("请求 ModelScope 搜索失败")?;
+    if !resp.status().is_success() {
+        bail!("请求失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 ModelScope 响应失败")?;
+    let parsed: ModelScopeSearchResponse = serde_json::from_str(&body).context("解析 ModelScope 响应 JSON 失败")?;
+
+    let data = parsed
+        .data
+        .ok_or_else(|| anyhow!("响应缺少 Data 字段: {}", parsed.message.unwrap_or_default()))?;
+    Ok(data
+        .model
+        .models
+        .into_iter()
+        .map(|m| SearchResult {
+            id: format!("{}/{}", m.path, m.name),
+            downloads: m.downloads,
+            likes: m.stars,
+            size: m.storage_size,
+        })
+        .collect())
+}
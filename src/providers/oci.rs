@@ -0,0 +1,382 @@
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use url::Url;
+
+use super::{DownloadItem, FetchOptions, Provider, Revision, RevisionKind};
+use crate::utils::NetworkOptions;
+
+/// Ollama 的 registry 地址（`ollama.com` 只是网页，API 在这里）
+const OLLAMA_REGISTRY: &str = "registry.ollama.ai";
+
+/// 只有这些镜像仓库（以及 `-P oci --endpoint` 指定的仓库）的 blob 链接会附加 `--token`
+const KNOWN_REGISTRIES: &[&str] = &[OLLAMA_REGISTRY, "ghcr.io", "registry-1.docker.io", "quay.io"];
+
+/// 未说明有效期的 token 按规范视为 60 秒有效；提前这么久换新的
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(60);
+const TOKEN_MARGIN: Duration = Duration::from_secs(10);
+
+/// 匿名拉取 token：`<registry>/<repository>` -> (Authorization 值, 失效时间)
+static PULL_TOKENS: OnceLock<Mutex<HashMap<String, (HeaderValue, Instant)>>> = OnceLock::new();
+
+/// 请求 manifest 时接受的媒体类型：OCI / Docker 单平台 manifest 以及多平台 index
+const MANIFEST_ACCEPT: &str = "application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.v2+json, \
+    application/vnd.oci.image.index.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// OCI 镜像仓库（Ollama、ghcr.io、Docker Hub 等）。
+///
+/// 模型写法为 `[registry/]repository[:tag]`，省略 registry 时视为 Ollama 官方库，
+/// 如 `llama3:8b` 即 `registry.ollama.ai/library/llama3:8b`。每个 layer（含 config）
+/// 作为一个 blob 下载，带 sha256 校验，保存为 `blobs/sha256-<hex>`（与 Ollama 本地目录一致）。
+pub struct Oci;
+
+#[async_trait]
+impl Provider for Oci {
+    fn name(&self) -> &str {
+        "oci"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &["ollama"]
+    }
+
+    fn default_revision(&self) -> &str {
+        "latest"
+    }
+
+    fn owns_url(&self, url: &Url) -> bool {
+        blob_digest(url).is_some()
+            && (url.host_str().is_some_and(|host| KNOWN_REGISTRIES.contains(&host)) || super::on_endpoint(self.name(), url))
+    }
+
+    fn relative_path(&self, url: &Url) -> Option<String> {
+        blob_digest(url).map(|digest| format!("blobs/{}", digest.replace(':', "-")))
+    }
+
+    fn repo_id(&self, url: &Url) -> Option<String> {
+        blob_repository(url)
+    }
+
+    async fn list_files(&self, model: &str, revision: &str, opts: &FetchOptions) -> Result<Vec<DownloadItem>> {
+        fetch_oci_urls(model, revision, opts.token.as_deref(), &opts.network).await
+    }
+
+    async fn list_revisions(&self, model: &str, opts: &FetchOptions) -> Result<Vec<Revision>> {
+        fetch_oci_tags(model, opts.token.as_deref(), &opts.network).await
+    }
+
+    /// 公开镜像的 blob 同样需要 token：按 registry 的认证质询申请该仓库的匿名拉取 token。
+    /// 不要求链接属于已知仓库，因为这里不会发送用户的 `--token`
+    async fn anonymous_headers(&self, url: &Url, opts: &FetchOptions) -> Result<HeaderMap> {
+        if blob_digest(url).is_none() {
+            return Ok(HeaderMap::new());
+        }
+        blob_auth(url, &opts.network).await
+    }
+}
+
+/// `https://<registry>/v2/<repository>/blobs/<digest>` 中的 digest
+fn blob_digest(url: &Url) -> Option<String> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    match segments.as_slice() {
+        ["v2", .., "blobs", digest] if digest.starts_with("sha256:") => Some(digest.to_string()),
+        _ => None,
+    }
+}
+
+/// `https://<registry>/v2/<repository>/blobs/<digest>` 中的 repository
+fn blob_repository(url: &Url) -> Option<String> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    match segments.as_slice() {
+        ["v2", repository @ .., "blobs", _] if !repository.is_empty() => Some(repository.join("/")),
+        _ => None,
+    }
+}
+
+/// 下载 blob 所需的匿名认证头；registry 不要求认证时为空。token 按仓库缓存到失效前
+async fn blob_auth(url: &Url, network: &NetworkOptions) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    let (Some(host), Some(repository)) = (url.host_str(), blob_repository(url)) else {
+        return Ok(headers);
+    };
+    let key = format!("{}/{}", host, repository);
+    let cache = PULL_TOKENS.get_or_init(Default::default);
+    if let Some((value, expires)) = cache.lock().unwrap().get(&key) {
+        if *expires > Instant::now() {
+            headers.insert(header::AUTHORIZATION, value.clone());
+            return Ok(headers);
+        }
+    }
+
+    // 未认证的 HEAD 得到 401 及带仓库 scope 的质询
+    let client = registry_client(network);
+    let resp = client.head(url.as_str()).send().await.context("请求 registry 失败")?;
+    if resp.status() != StatusCode::UNAUTHORIZED {
+        return Ok(headers);
+    }
+    let Some(challenge) = resp.headers().get(header::WWW_AUTHENTICATE).and_then(|v| v.to_str().ok()) else {
+        return Ok(headers);
+    };
+    let (token, lifetime) = anonymous_token(&client, challenge).await?;
+    let value = HeaderValue::from_str(&format!("Bearer {}", token)).context("无效的 registry token")?;
+    let expires = Instant::now() + lifetime.saturating_sub(TOKEN_MARGIN);
+    cache.lock().unwrap().insert(key, (value.clone(), expires));
+    headers.insert(header::AUTHORIZATION, value);
+    Ok(headers)
+}
+
+/// 镜像引用：registry、repository 与 tag（或 digest）
+struct Reference {
+    registry: String,
+    repository: String,
+    tag: String,
+}
+
+impl Reference {
+    fn parse(model: &str, revision: &str) -> Result<Self> {
+        let model = model.trim().trim_start_matches("https://").trim_start_matches("http://");
+        let (name, tag) = match model.split_once('@') {
+            Some((name, digest)) => (name, digest.to_string()),
+            None => match model.rsplit_once(':') {
+                // 冒号在最后一个 `/` 之后才是 tag，否则是 registry 端口
+                Some((name, tag)) if !tag.contains('/') => (name, tag.to_string()),
+                _ => (model, revision.to_string()),
+            },
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, rest)) if host.contains('.') || host.contains(':') || host == "localhost" => {
+                (host.to_string(), rest.to_string())
+            }
+            Some(_) => (OLLAMA_REGISTRY.to_string(), name.to_string()),
+            None => (OLLAMA_REGISTRY.to_string(), format!("library/{}", name)),
+        };
+        let registry = match registry.as_str() {
+            "ollama.com" | "www.ollama.com" => OLLAMA_REGISTRY.to_string(),
+            "docker.io" => "registry-1.docker.io".to_string(),
+            _ => registry,
+        };
+        if repository.is_empty() {
+            bail!("无效的镜像名称: {}", model);
+        }
+        Ok(Self { registry, repository, tag })
+    }
+
+    fn url(&self, kind: &str, reference: &str) -> String {
+        format!("https://{}/v2/{}/{}/{}", self.registry, self.repository, kind, reference)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    media_type: Option<String>,
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    /// 多平台 index 中的各平台 manifest
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Deserialize)]
+struct Descriptor {
+    digest: String,
+    size: Option<u64>,
+    platform: Option<Platform>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+}
+
+#[derive(Deserialize)]
+struct TagList {
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+    /// 有效期（秒）
+    expires_in: Option<u64>,
+}
+
+fn registry_client(network: &NetworkOptions) -> Client {
+    network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+fn bearer(token: Option<&str>) -> Option<HeaderValue> {
+    token.and_then(|t| HeaderValue::from_str(&format!("Bearer {}", t)).ok())
+}
+
+pub async fn fetch_oci_tags(model: &str, token: Option<&str>, network: &NetworkOptions) -> Result<Vec<Revision>> {
+    let reference = Reference::parse(model, "latest")?;
+    let url = format!("https://{}/v2/{}/tags/list", reference.registry, reference.repository);
+    let list: TagList = get_json(&registry_client(network), &url, &mut bearer(token)).await?;
+    Ok(list
+        .tags
+        .into_iter()
+        .map(|name| Revision { name, kind: RevisionKind::Tag, commit: None })
+        .collect())
+}
+
+pub async fn fetch_oci_urls(model: &str, revision: &str, token: Option<&str>, network: &NetworkOptions) -> Result<Vec<DownloadItem>> {
+    let reference = Reference::parse(model, revision)?;
+    let client = registry_client(network);
+
+    let mut auth = bearer(token);
+    let mut manifest = get_manifest(&client, &reference, &reference.tag, &mut auth).await?;
+
+    // 多平台镜像：优先 linux/amd64，否则取第一个
+    if !manifest.manifests.is_empty() {
+        let chosen = manifest
+            .manifests
+            .iter()
+            .find(|m| m.platform.as_ref().is_some_and(|p| p.os == "linux" && p.architecture == "amd64"))
+            .or_else(|| manifest.manifests.first())
+            .map(|m| m.digest.clone())
+            .unwrap_or_default();
+        manifest = get_manifest(&client, &reference, &chosen, &mut auth).await?;
+    }
+
+    let items: Vec<DownloadItem> = manifest
+        .config
+        .iter()
+        .chain(manifest.layers.iter())
+        .filter(|d| d.digest.starts_with("sha256:"))
+        .map(|d| DownloadItem {
+            url: reference.url("blobs", &d.digest),
+            hash: Some(d.digest.clone()),
+            size: d.size,
+            ..Default::default()
+        })
+        .collect();
+
+    if items.is_empty() {
+        bail!(
+            "manifest 中没有可下载的 layer（mediaType: {}）",
+            manifest.media_type.as_deref().unwrap_or("unknown")
+        );
+    }
+    Ok(items)
+}
+
+async fn get_manifest(
+    client: &Client,
+    reference: &Reference,
+    tag: &str,
+    auth: &mut Option<HeaderValue>,
+) -> Result<Manifest> {
+    get_json(client, &reference.url("manifests", tag), auth).await
+}
+
+/// 请求 registry API；首次请求 401 时申请 token 并保存到 `auth` 供后续请求复用
+async fn get_json<T: DeserializeOwned>(client: &Client, url: &str, auth: &mut Option<HeaderValue>) -> Result<T> {
+    let mut resp = send_registry_request(client, url, auth.as_ref()).await?;
+
+    // 未带 token 时按 WWW-Authenticate 申请匿名拉取 token 后重试
+    if resp.status() == StatusCode::UNAUTHORIZED && auth.is_none() {
+        let challenge = resp
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| anyhow!("镜像仓库要求认证，请使用 --token"))?
+            .to_string();
+        let (token, _) = anonymous_token(client, &challenge).await?;
+        *auth = Some(HeaderValue::from_str(&format!("Bearer {}", token)).context("无效的 registry token")?);
+        resp = send_registry_request(client, url, auth.as_ref()).await?;
+    }
+
+    if !resp.status().is_success() {
+        bail!("请求 registry 失败，状态码：{}（{}）", resp.status(), url);
+    }
+    let body = resp.text().await.context("读取 registry 响应失败")?;
+    serde_json::from_str(&body).context("解析 registry 响应 JSON 失败")
+}
+
+async fn send_registry_request(client: &Client, url: &str, auth: Option<&HeaderValue>) -> Result<reqwest::Response> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ACCEPT, HeaderValue::from_static(MANIFEST_ACCEPT));
+    if let Some(auth) = auth {
+        headers.insert(header::AUTHORIZATION, auth.clone());
+    }
+    client
+        .get(url)
+        .headers(headers)
+        .send()
+        .await
+        .context("请求 registry 失败")
+}
+
+/// 解析 `Bearer realm="...",service="...",scope="..."` 并申请匿名 token，同时返回其有效期
+async fn anonymous_token(client: &Client, challenge: &str) -> Result<(String, Duration)> {
+    let params = challenge
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| anyhow!("不支持的认证方式: {}", challenge))?;
+    let mut realm = None;
+    let mut query = Vec::new();
+    for param in split_unquoted(params) {
+        let Some((key, value)) = param.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim_matches('"').to_string();
+        if key == "realm" {
+            realm = Some(value);
+        } else {
+            query.push((key.to_string(), value));
+        }
+    }
+    let realm = realm.ok_or_else(|| anyhow!("认证信息中缺少 realm: {}", challenge))?;
+
+    let resp = client
+        .get(&realm)
+        .query(&query)
+        .send()
+        .await
+        .context("申请 registry token 失败")?;
+    if !resp.status().is_success() {
+        bail!("申请 registry token 失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 registry token 失败")?;
+    let body: TokenResponse = serde_json::from_str(&body).context("解析 registry token 失败")?;
+    let lifetime = body.expires_in.map_or(DEFAULT_TOKEN_LIFETIME, Duration::from_secs);
+    let token = body.token
+        .or(body.access_token)
+        .ok_or_else(|| anyhow!("registry 未返回 token"))?;
+    Ok((token, lifetime))
+}
+
+/// 按引号外的逗号切分（scope 可能是 `"repository:x:pull,push"`）
+fn split_unquoted(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use url::Url;
+
+use super::{DownloadItem, FetchOptions, Provider};
+use crate::utils::NetworkOptions;
+
+/// Zenodo 数据集。名称可以是记录 ID、DOI (`10.5281/zenodo.1234567`) 或记录页面链接；
+/// 每个 Zenodo 版本都有独立的记录 ID，因此 `--branch` 不起作用。
+pub struct Zenodo;
+
+#[async_trait]
+impl Provider for Zenodo {
+    fn name(&self) -> &str {
+        "zenodo"
+    }
+
+    fn default_revision(&self) -> &str {
+        "latest"
+    }
+
+    fn owns_url(&self, url: &Url) -> bool {
+        url.host_str().is_some_and(|h| h == "zenodo.org" || h.ends_with(".zenodo.org"))
+    }
+
+    fn relative_path(&self, url: &Url) -> Option<String> {
+        // /api/records/<id>/files/<key>/content 或 /records/<id>/files/<key>
+        let segments: Vec<&str> = url.path_segments()?.collect();
+        let pos = segments.iter().position(|s| *s == "files")?;
+        let key = segments.get(pos + 1)?;
+        percent_encoding::percent_decode_str(key).decode_utf8().ok().map(|k| k.into_owned())
+    }
+
+    async fn list_files(&self, model: &str, _revision: &str, opts: &FetchOptions) -> Result<Vec<DownloadItem>> {
+        let headers = opts
+            .token
+            .as_deref()
+            .map(|t| self.auth_headers(t))
+            .unwrap_or_default();
+        fetch_zenodo_urls(model, headers, &opts.network).await
+    }
+}
+
+#[derive(Deserialize)]
+struct ZenodoRecord {
+    #[serde(default)]
+    files: Vec<ZenodoFile>,
+}
+
+#[derive(Deserialize)]
+struct ZenodoFile {
+    key: String,
+    /// 形如 `md5:<hex>`
+    checksum: Option<String>,
+    size: Option<u64>,
+}
+
+/// 从记录 ID、DOI 或链接中取出数字记录 ID
+fn record_id(input: &str) -> Option<&str> {
+    let input = input.trim().trim_end_matches('/');
+    let id = match input.rsplit_once("zenodo.") {
+        // DOI：10.5281/zenodo.1234567
+        Some((_, id)) if !id.contains('/') => id,
+        // 链接：https://zenodo.org/records/1234567
+        _ => input.rsplit('/').next()?,
+    };
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then_some(id)
+}
+
+pub async fn fetch_zenodo_urls(record: &str, headers: HeaderMap, network: &NetworkOptions) -> Result<Vec<DownloadItem>> {
+    let id = record_id(record).ok_or_else(|| anyhow!("无法识别的 Zenodo 记录: {}", record))?;
+    let api_url = format!("https://zenodo.org/api/records/{}", id);
+    let client = network.client_builder()
+        .user_agent("RustDownloadTool/0.1.0")
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    let resp = client.get(&api_url).headers(headers).send().await.context("请求 Zenodo 记录失败")?;
+    if !resp.status().is_success() {
+        bail!("请求失败，状态码：{}", resp.status());
+    }
+    let body = resp.text().await.context("读取 Zenodo 响应失败")?;
+    let parsed: ZenodoRecord = serde_json::from_str(&body).context("解析 Zenodo 响应 JSON 失败")?;
+
+    if parsed.files.is_empty() {
+        bail!("文件列表为空（记录可能受限或仅有外链）");
+    }
+
+    let mut items = Vec::with_capacity(parsed.files.len());
+    for file in parsed.files {
+        let mut url = Url::parse(&api_url)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("无效的 Zenodo 链接"))?
+            .extend(["files", file.key.as_str(), "content"]);
+        items.push(DownloadItem {
+            url: url.to_string(),
+            hash: file.checksum,
+            size: file.size,
+            ..Default::default()
+        });
+    }
+    Ok(items)
+}
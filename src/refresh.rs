@@ -0,0 +1,62 @@
+//! Pre-signed download addresses that expire during a long download.
+//!
+//! Hugging Face and ModelScope `resolve` links redirect to a signed CDN address
+//! that only stays valid for a while. When a request answers 403, the provider
+//! resolves a fresh address, later requests of that file go there directly
+//! (without the token, which the signature replaces) and the download continues
+//! from the same byte offset. Another 403 resolves again.
+
+use anyhow::Result;
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::utils::NetworkOptions;
+
+/// Fresh addresses resolved for one request before it counts as failed.
+pub(crate) const MAX_URL_REFRESHES: u32 = 3;
+
+pub(crate) struct FreshUrls {
+    token: Option<String>,
+    network: NetworkOptions,
+    /// The last address resolved for each source that answered 403
+    urls: Mutex<HashMap<String, String>>,
+}
+
+impl FreshUrls {
+    pub(crate) fn new(token: Option<String>, network: NetworkOptions) -> Self {
+        Self { token, network, urls: Mutex::new(HashMap::new()) }
+    }
+
+    /// The address resolved for `source` after a 403 and the auth headers it takes,
+    /// or `None` while requests still go to `source` itself.
+    pub(crate) fn target(&self, source: &str) -> Option<(String, HeaderMap)> {
+        let url = self.urls.lock().unwrap().get(source).cloned()?;
+        let headers = crate::providers::auth_headers_for(&url, self.token.as_deref());
+        Some((url, headers))
+    }
+
+    /// Resolve a fresh address for `source` after a 403. `false` when its provider
+    /// cannot, and the request fails like any other.
+    pub(crate) async fn refresh(&self, source: &str) -> Result<bool> {
+        let fresh = crate::providers::refresh_url_for(source, self.token.as_deref(), &self.network).await?;
+        let Some(fresh) = fresh else {
+            return Ok(false);
+        };
+        self.urls.lock().unwrap().insert(source.to_string(), fresh);
+        Ok(true)
+    }
+
+    /// Forget the address resolved for `source` once its file is done.
+    pub(crate) fn forget(&self, source: &str) {
+        self.urls.lock().unwrap().remove(source);
+    }
+}
+
+/// Whether `error` is a 403 answer, as an expired signature gives.
+pub(crate) fn is_forbidden(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|status| status == reqwest::StatusCode::FORBIDDEN)
+}
@@ -0,0 +1,192 @@
+//! Start times of scheduled downloads (`at=` in tasks files, `--at`), e.g. for
+//! metered connections with an off-peak window:
+//!
+//! - a clock time such as `01:00`: the next time the clock shows it;
+//! - a date and time such as `2026-10-20 01:00`: once (already past means now);
+//! - a five-field cron expression such as `0 1 * * 1-5` (minute, hour, day of
+//!   month, month, day of week) with `*`, lists, ranges and `/step`: its next match.
+
+use anyhow::{anyhow, bail, Error, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+use std::fmt;
+use std::str::FromStr;
+
+/// How far ahead a cron expression is searched for its next match.
+const CRON_HORIZON_DAYS: i64 = 366 * 5;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every day at this time
+    Daily(NaiveTime),
+    /// Once, at this local date and time
+    At(NaiveDateTime),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// When a download scheduled like this and queued at `now` may start; a time
+    /// in the past means right away. `None` if the time never comes.
+    pub fn next_start(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let now = now.naive_local();
+        let start = match self {
+            Self::Daily(time) => {
+                let today = now.date().and_time(*time);
+                if today > now { today } else { today + Duration::days(1) }
+            }
+            Self::At(at) => *at,
+            Self::Cron(cron) => cron.next_after(now)?,
+        };
+        local(start)
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.split_whitespace().count() == 5 {
+            return Ok(Self::Cron(value.parse()?));
+        }
+        for format in ["%H:%M", "%H:%M:%S"] {
+            if let Ok(time) = NaiveTime::parse_from_str(value, format) {
+                return Ok(Self::Daily(time));
+            }
+        }
+        for format in ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"] {
+            if let Ok(at) = NaiveDateTime::parse_from_str(value, format) {
+                return Ok(Self::At(at));
+            }
+        }
+        bail!(
+            "Invalid start time {:?} (use HH:MM, YYYY-MM-DD HH:MM or a cron expression such as \"0 1 * * *\")",
+            value
+        )
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Daily(time) if time.second() == 0 => write!(f, "{}", time.format("%H:%M")),
+            Self::Daily(time) => write!(f, "{}", time.format("%H:%M:%S")),
+            Self::At(at) if at.second() == 0 => write!(f, "{}", at.format("%Y-%m-%d %H:%M")),
+            Self::At(at) => write!(f, "{}", at.format("%Y-%m-%d %H:%M:%S")),
+            Self::Cron(cron) => write!(f, "{}", cron.source),
+        }
+    }
+}
+
+/// A five-field cron expression; every field is a bit set of the values it matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// As in cron, day of month and day of week only both restrict when neither is `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// First minute after `now` the expression matches.
+    fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut t = now.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let horizon = t + Duration::days(CRON_HORIZON_DAYS);
+        while t < horizon {
+            if self.months & (1 << t.month()) == 0 || !self.day_matches(t.date()) {
+                t = (t.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("Invalid cron expression {:?} (expected 5 fields: minute hour day month weekday)", value);
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        let cron = Self {
+            source: fields.join(" "),
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        };
+        if cron.next_after(Local::now().naive_local()).is_none() {
+            bail!("Cron expression {:?} never matches", value);
+        }
+        Ok(cron)
+    }
+}
+
+/// One cron field, e.g. `*/15`, `1-5` or `0,30`, as a bit set of the values in `min..=max`.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let number = |value: &str| {
+        value
+            .parse::<u32>()
+            .map_err(|_| anyhow!("Invalid cron field {:?}", field))
+    };
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(number(step)?)),
+            None => (part, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `5/10` means from 5 to the end in steps of 10, and `5/1` every value from 5
+            None if step.is_some() => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        let step = step.unwrap_or(1);
+        if step == 0 || start < min || end > max || start > end {
+            bail!("Invalid cron field {:?} (values are {}-{})", field, min, max);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// `naive` on the local clock; a time skipped by a DST change moves forward an hour.
+fn local(naive: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| Local.from_local_datetime(&(naive + Duration::hours(1))).earliest())
+}
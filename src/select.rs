@@ -0,0 +1,162 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use indicatif::HumanBytes;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{Frame, Terminal};
+use std::collections::HashMap;
+use std::io::{stdout, Stdout};
+
+use rdl_core::providers::{relative_path_for, DownloadItem};
+use rdl_core::utils::get_filename_from_url;
+
+type Term = Terminal<CrosstermBackend<Stdout>>;
+
+struct Entry {
+    item: DownloadItem,
+    name: String,
+    size: Option<u64>,
+    selected: bool,
+}
+
+struct Picker {
+    entries: Vec<Entry>,
+    table: TableState,
+}
+
+impl Picker {
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.entries.len() as isize;
+        let current = self.table.selected().unwrap_or(0) as isize;
+        self.table.select(Some((current + delta).clamp(0, len - 1) as usize));
+    }
+
+    fn toggle_current(&mut self) {
+        if let Some(entry) = self.table.selected().and_then(|i| self.entries.get_mut(i)) {
+            entry.selected = !entry.selected;
+        }
+    }
+
+    /// Select everything, or nothing if everything is already selected.
+    fn toggle_all(&mut self) {
+        let select = !self.entries.iter().all(|e| e.selected);
+        self.entries.iter_mut().for_each(|e| e.selected = select);
+    }
+}
+
+/// Let the user pick which fetched files go into the tasks file.
+/// Returns `None` if the selection was cancelled.
+pub fn select_items(items: Vec<DownloadItem>, sizes: &HashMap<String, u64>) -> Result<Option<Vec<DownloadItem>>> {
+    if items.is_empty() {
+        return Ok(Some(items));
+    }
+    let entries = items
+        .into_iter()
+        .map(|item| Entry {
+            name: relative_path_for(&item.url)
+                .map(|p| p.to_string_lossy().to_string())
+                .or_else(|| get_filename_from_url(&item.url).ok())
+                .unwrap_or_else(|| item.url.clone()),
+            size: sizes.get(&item.url).copied(),
+            item,
+            selected: true,
+        })
+        .collect();
+    let mut picker = Picker { entries, table: TableState::default() };
+    picker.table.select(Some(0));
+
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let result = event_loop(&mut terminal, &mut picker);
+
+    // Always give the terminal back, even if the loop failed
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    let confirmed = result?;
+    Ok(confirmed.then(|| {
+        picker
+            .entries
+            .into_iter()
+            .filter(|e| e.selected)
+            .map(|e| e.item)
+            .collect()
+    }))
+}
+
+/// Returns true when the selection was confirmed.
+fn event_loop(terminal: &mut Term, picker: &mut Picker) -> Result<bool> {
+    loop {
+        terminal.draw(|f| draw(f, picker))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => return Ok(true),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                // Raw mode turns Ctrl+C into a key press
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(false),
+                KeyCode::Down | KeyCode::Char('j') => picker.move_selection(1),
+                KeyCode::Up | KeyCode::Char('k') => picker.move_selection(-1),
+                KeyCode::PageDown => picker.move_selection(10),
+                KeyCode::PageUp => picker.move_selection(-10),
+                KeyCode::Char(' ') => {
+                    picker.toggle_current();
+                    picker.move_selection(1);
+                }
+                KeyCode::Char('a') => picker.toggle_all(),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn draw(f: &mut Frame, picker: &mut Picker) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(5), Constraint::Length(3)])
+        .split(f.size());
+
+    let chosen: Vec<&Entry> = picker.entries.iter().filter(|e| e.selected).collect();
+    let chosen_bytes: u64 = chosen.iter().filter_map(|e| e.size).sum();
+    let header = Paragraph::new(format!(
+        "Selected: {}/{} files | {}",
+        chosen.len(),
+        picker.entries.len(),
+        HumanBytes(chosen_bytes)
+    ))
+    .block(Block::default().borders(Borders::ALL).title("rdl --select"));
+    f.render_widget(header, chunks[0]);
+
+    let rows: Vec<Row> = picker
+        .entries
+        .iter()
+        .map(|entry| {
+            let (mark, color) = if entry.selected { ("[x]", Color::Green) } else { ("[ ]", Color::DarkGray) };
+            Row::new(vec![
+                Cell::from(mark).style(Style::default().fg(color)),
+                Cell::from(entry.name.clone()),
+                Cell::from(entry.size.map(|s| HumanBytes(s).to_string()).unwrap_or_else(|| "-".to_string())),
+            ])
+        })
+        .collect();
+    let widths = [Constraint::Length(4), Constraint::Min(20), Constraint::Length(12)];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["", "File", "Size"]).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(Block::default().borders(Borders::ALL).title("Files"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(table, chunks[1], &mut picker.table);
+
+    let footer = Paragraph::new("↑/↓ move  space toggle  a all/none  enter confirm  q cancel")
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(footer, chunks[2]);
+}
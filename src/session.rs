@@ -0,0 +1,109 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Session chosen with `--session`; unset means the unnamed (default) session.
+static SESSION: OnceLock<String> = OnceLock::new();
+
+/// Directory chosen with `--state-dir`; replaces both the state and the runtime directory.
+static STATE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Select the named session whose runtime files this process uses.
+pub fn set_session(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("Invalid session name {:?} (use letters, digits, '-' and '_')", name);
+    }
+    let _ = SESSION.set(name.to_string());
+    Ok(())
+}
+
+pub fn session_name() -> Option<&'static str> {
+    SESSION.get().map(String::as_str)
+}
+
+/// Keep the job database, pid files, sockets and logs under `dir` instead of the defaults.
+pub fn set_state_dir(dir: &Path) {
+    let _ = STATE_DIR.set(dir.to_path_buf());
+}
+
+/// Where logs live (see [`rdl_core::utils::state_dir`]); the working directory
+/// when not even `HOME` is set.
+fn state_base() -> PathBuf {
+    STATE_DIR
+        .get()
+        .cloned()
+        .or_else(rdl_core::utils::state_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Where pid files and control sockets live: `$XDG_RUNTIME_DIR/rdl`, falling back
+/// to the state directory.
+fn runtime_base() -> PathBuf {
+    if STATE_DIR.get().is_some() {
+        return state_base();
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("rdl"),
+        None => state_base(),
+    }
+}
+
+/// Default job database: `jobs.db` in `--state-dir` when given.
+pub fn default_db_path() -> Option<PathBuf> {
+    match STATE_DIR.get() {
+        Some(dir) => Some(dir.join("jobs.db")),
+        None => rdl_core::jobs::default_db_path(),
+    }
+}
+
+/// `file` in the directory of the current session, created on first use: `base` itself
+/// for the unnamed session, `base/sessions/<name>` for named ones.
+fn session_file(base: PathBuf, file: &str) -> PathBuf {
+    let dir = match session_name() {
+        Some(name) => base.join("sessions").join(name),
+        None => base,
+    };
+    let _ = std::fs::create_dir_all(&dir);
+    dir.join(file)
+}
+
+pub fn pid_file() -> PathBuf {
+    session_file(runtime_base(), "rdl.pid")
+}
+
+pub fn socket_path() -> PathBuf {
+    session_file(runtime_base(), "rdl.sock")
+}
+
+pub fn log_file() -> PathBuf {
+    session_file(state_base(), "rdl.log")
+}
+
+/// `rdl sessions`: every named session with a pid file, and whether its daemon still runs.
+pub fn list_sessions() -> Result<()> {
+    let base = runtime_base().join("sessions");
+    let mut sessions = vec![];
+    if base.exists() {
+        for entry in std::fs::read_dir(&base).context(format!("Failed to read {:?}", base))?.flatten() {
+            let pid = std::fs::read_to_string(entry.path().join("rdl.pid"))
+                .ok()
+                .and_then(|content| content.trim().parse::<u32>().ok());
+            if let Some(pid) = pid {
+                sessions.push((entry.file_name().to_string_lossy().to_string(), pid));
+            }
+        }
+    }
+    sessions.sort();
+
+    if sessions.is_empty() {
+        println!("No named sessions.");
+        return Ok(());
+    }
+    println!("{:<20} {:<10} {:<10} Log", "Session", "PID", "Status");
+    for (name, pid) in sessions {
+        let status = if crate::daemon::process_alive(pid) { "running" } else { "stale" };
+        let log = state_base().join("sessions").join(&name).join("rdl.log");
+        println!("{:<20} {:<10} {:<10} {}", name, pid, status, log.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! Detached OpenPGP signature verification (`--verify-sig`).
+//!
+//! Release artifacts often ship a `file.asc` or `file.sig` next to `file`. After
+//! a file is downloaded, its signature is fetched from the same place and checked
+//! against the keys of a trusted keyring; a missing or bad signature fails the file.
+
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{header::HeaderMap, Client, StatusCode};
+use sequoia_openpgp as openpgp;
+use openpgp::cert::{Cert, CertParser};
+use openpgp::parse::stream::{DetachedVerifierBuilder, MessageLayer, MessageStructure, VerificationHelper};
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::KeyHandle;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::utils::NetworkOptions;
+
+/// Extensions of detached signatures, tried in this order.
+const SIGNATURE_EXTENSIONS: &[&str] = &[".asc", ".sig"];
+
+/// Sidecar files that are never signed themselves.
+const UNSIGNED_EXTENSIONS: &[&str] = &[".asc", ".sig", ".sha256", ".sha1", ".md5"];
+
+/// Default keyring location: `$XDG_CONFIG_HOME/rdl/trusted-keys.asc`, falling back to `~/.config/rdl/trusted-keys.asc`.
+pub fn default_keyring_path() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("rdl").join("trusted-keys.asc"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("rdl").join("trusted-keys.asc"))
+}
+
+/// Public keys whose signatures are accepted.
+#[derive(Debug)]
+pub struct Keyring {
+    certs: Vec<Cert>,
+}
+
+impl Keyring {
+    /// Load every certificate of an armored or binary keyring file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let certs = CertParser::from_file(path)
+            .context(format!("Failed to read trusted keys: {:?}", path))?
+            .collect::<openpgp::Result<Vec<_>>>()
+            .context(format!("Failed to parse trusted keys: {:?}", path))?;
+        if certs.is_empty() {
+            bail!("No keys found in {:?}", path);
+        }
+        Ok(Self { certs })
+    }
+
+    pub fn len(&self) -> usize {
+        self.certs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certs.is_empty()
+    }
+}
+
+/// Whether `url` needs a signature: signatures and checksum files themselves do not.
+pub fn needs_signature(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    !UNSIGNED_EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+}
+
+/// Fetch the detached signature of `url`, trying `.asc` then `.sig`.
+/// Returns the signature URL and its bytes, or `None` if neither exists.
+pub async fn fetch_signature(
+    client: &Client,
+    network: &NetworkOptions,
+    url: &str,
+    headers: &HeaderMap,
+) -> Result<Option<(String, Vec<u8>)>> {
+    // Signatures sit next to the file, before any query string
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, format!("?{}", query)),
+        None => (url, String::new()),
+    };
+    for ext in SIGNATURE_EXTENSIONS {
+        let sig_url = format!("{}{}{}", base, ext, query);
+        let resp = network
+            .send(client, client.get(&sig_url).headers(headers.clone()))
+            .await
+            .context(format!("Failed to fetch signature {}", sig_url))?;
+        match resp.status() {
+            StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => continue,
+            status if !status.is_success() => bail!("Failed to fetch signature {}: HTTP {}", sig_url, status),
+            _ => {}
+        }
+        let bytes = resp.bytes().await.context(format!("Failed to read signature {}", sig_url))?;
+        return Ok(Some((sig_url, bytes.to_vec())));
+    }
+    Ok(None)
+}
+
+struct Helper {
+    certs: Vec<Cert>,
+    signer: Option<String>,
+}
+
+impl VerificationHelper for Helper {
+    fn get_certs(&mut self, _ids: &[KeyHandle]) -> openpgp::Result<Vec<Cert>> {
+        Ok(self.certs.clone())
+    }
+
+    fn check(&mut self, structure: MessageStructure) -> openpgp::Result<()> {
+        for layer in structure.into_iter() {
+            if let MessageLayer::SignatureGroup { results } = layer {
+                // One good signature from a trusted key is enough
+                if let Some(good) = results.iter().find_map(|r| r.as_ref().ok()) {
+                    self.signer = Some(good.ka.cert().fingerprint().to_hex());
+                    return Ok(());
+                }
+                return Err(anyhow!("no valid signature from a trusted key"));
+            }
+        }
+        Err(anyhow!("no signature found"))
+    }
+}
+
+/// Check `signature` over the file at `path`. Returns the fingerprint of the signing key.
+pub async fn verify_detached(keyring: Arc<Keyring>, path: PathBuf, signature: Vec<u8>) -> Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let policy = StandardPolicy::new();
+        let helper = Helper { certs: keyring.certs.clone(), signer: None };
+        let mut verifier = DetachedVerifierBuilder::from_bytes(&signature)?.with_policy(&policy, None, helper)?;
+        verifier.verify_file(&path)?;
+        verifier
+            .into_helper()
+            .signer
+            .ok_or_else(|| anyhow!("no valid signature from a trusted key"))
+    })
+    .await?
+}
@@ -1,17 +1,276 @@
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Current `.part.json` format version
+pub const STATE_VERSION: u32 = 1;
+
+/// Bytes at the end of each part's downloaded range covered by its `tail_checksum`
+pub const TAIL_CHECK_BYTES: u64 = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct DownloadState {
+    /// Format version; 0 for files written before versioning
+    #[serde(default)]
+    pub version: u32,
+    /// SHA256 of the state serialized without this field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
     pub url: String,
     pub total_size: u64,
     pub parts: Vec<PartState>,
+    /// Validators captured when the download started, sent as `If-Range` on resume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// Download speed at the last save, for status reporting from other processes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_per_sec: Option<u64>,
+    /// Unix time of the last save
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_at: Option<u64>,
+}
+
+impl DownloadState {
+    /// Value for the `If-Range` header: a strong ETag, else Last-Modified.
+    pub fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|e| !e.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    fn compute_checksum(&self) -> Result<String> {
+        let mut unsigned = self.clone();
+        unsigned.checksum = None;
+        let bytes = serde_json::to_vec(&unsigned)?;
+        Ok(hex::encode(Sha256::digest(&bytes)))
+    }
+
+    /// Treat the first `len` bytes of the file as downloaded (an existing file being resumed).
+    pub fn mark_downloaded(&mut self, len: u64) {
+        for part in &mut self.parts {
+            part.current_byte = len.clamp(part.start_byte, part.end_byte + 1);
+            part.completed = part.current_byte > part.end_byte;
+            part.tail_checksum = None;
+        }
+    }
+
+    /// Record the checksum of every part's tail as it is in `file` now. Called before
+    /// each save, so the state never claims more than the file held at that moment.
+    pub fn record_tails(&mut self, file: &std::fs::File) -> std::io::Result<()> {
+        for part in &mut self.parts {
+            part.tail_checksum = match part.tail() {
+                Some((offset, len)) => {
+                    let mut buf = vec![0; len];
+                    read_exact_at(file, &mut buf, offset)?;
+                    Some(adler32(&buf))
+                }
+                None => None,
+            };
+        }
+        Ok(())
+    }
+
+    /// Compare each part's tail in `file` with the checksum recorded at the last save
+    /// and restart the parts whose data changed since (e.g. lost in a crash before it
+    /// reached the disk). Returns the indices of the restarted parts.
+    pub fn check_tails(&mut self, file: &std::fs::File) -> std::io::Result<Vec<usize>> {
+        let mut restarted = vec![];
+        for part in &mut self.parts {
+            let (Some(expected), Some((offset, len))) = (part.tail_checksum, part.tail()) else {
+                continue;
+            };
+            let mut buf = vec![0; len];
+            let intact = match read_exact_at(file, &mut buf, offset) {
+                Ok(()) => adler32(&buf) == expected,
+                // A truncated file lost the tail as well
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => false,
+                Err(e) => return Err(e),
+            };
+            if !intact {
+                part.current_byte = part.start_byte;
+                part.completed = false;
+                part.tail_checksum = None;
+                restarted.push(part.index);
+            }
+        }
+        Ok(restarted)
+    }
+
+    /// Bytes downloaded so far across all parts.
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.parts.iter().map(|p| p.current_byte - p.start_byte).sum()
+    }
+
+    /// Parse a state file, rejecting corrupted content and unknown versions.
+    /// Files written before versioning carry no checksum and are accepted as-is.
+    pub fn from_json(content: &str) -> Result<Self> {
+        let state: Self = serde_json::from_str(content)?;
+        if state.version > STATE_VERSION {
+            bail!("unsupported state version {}", state.version);
+        }
+        if let Some(expected) = &state.checksum {
+            if state.compute_checksum()? != *expected {
+                bail!("state checksum mismatch");
+            }
+        }
+        Ok(state)
+    }
+
+    /// Write the state to a temp file and rename it over `path`, so a crash
+    /// mid-write never leaves a truncated state file behind.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let mut state = self.clone();
+        state.version = STATE_VERSION;
+        state.saved_at = SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+        state.checksum = Some(state.compute_checksum()?);
+        let content = serde_json::to_string(&state)?;
+
+        let tmp = temp_path(path);
+        fs::write(&tmp, content).await?;
+        fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+/// `model.bin` -> `model.bin.part`, where the segments are written before the final rename.
+pub fn part_path(filepath: &Path) -> PathBuf {
+    let mut part_filepath = filepath.to_path_buf();
+    if let Some(extension) = filepath.extension() {
+        let mut ext = extension.to_os_string();
+        ext.push(".part");
+        part_filepath.set_extension(ext);
+    } else {
+        part_filepath.set_extension("part");
+    }
+    part_filepath
+}
+
+/// `model.bin` -> `model.bin.part.json`
+pub fn state_path(filepath: &Path) -> PathBuf {
+    part_path(filepath).with_extension("part.json")
+}
+
+/// `foo.part.json` -> `foo.part.json.tmp`
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// The path the partial files of `filepath` are named after: `filepath` itself, or
+/// with `--temp-dir` its path relative to `output_dir` below `temp_dir`.
+pub fn partial_base(filepath: &Path, output_dir: &Path, temp_dir: Option<&Path>) -> PathBuf {
+    match (temp_dir, filepath.strip_prefix(output_dir)) {
+        (Some(temp_dir), Ok(relative)) => temp_dir.join(relative),
+        _ => filepath.to_path_buf(),
+    }
+}
+
+/// Move a finished file into place. `rename` cannot cross filesystems (`--temp-dir`
+/// on another disk); then the file is copied next to `to` and flushed to disk, renamed
+/// over it, and only then removed from `from`, so a crash never loses both copies.
+pub async fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match fs::rename(from, to).await {
+        Err(e) if crosses_devices(&e) => {}
+        result => return result,
+    }
+    let tmp = temp_path(to);
+    let copied = async {
+        fs::copy(from, &tmp).await?;
+        fs::File::open(&tmp).await?.sync_all().await?;
+        fs::rename(&tmp, to).await?;
+        sync_dir(to).await
+    };
+    if let Err(e) = copied.await {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    fs::remove_file(from).await
+}
+
+/// Whether a `rename` failed because source and target are on different filesystems.
+fn crosses_devices(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    let code = nix::errno::Errno::EXDEV as i32;
+    // ERROR_NOT_SAME_DEVICE
+    #[cfg(windows)]
+    let code = 17;
+    e.raw_os_error() == Some(code)
+}
+
+/// Flush the directory entry of `path`, so a rename to it survives a crash.
+pub async fn sync_dir(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::File::open(dir).await?.sync_all().await?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct PartState {
     pub index: usize,
     pub start_byte: u64,
     pub end_byte: u64,
     pub current_byte: u64,
     pub completed: bool,
+    /// Adler-32 of the last `TAIL_CHECK_BYTES` before `current_byte`, checked on resume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tail_checksum: Option<u32>,
+}
+
+impl PartState {
+    /// Offset and length of the downloaded bytes covered by `tail_checksum`.
+    fn tail(&self) -> Option<(u64, usize)> {
+        let len = (self.current_byte - self.start_byte).min(TAIL_CHECK_BYTES);
+        (len > 0).then(|| (self.current_byte - len, len as usize))
+    }
+}
+
+/// Adler-32 (RFC 1950): cheap enough to run on every state save.
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the most that can be summed before `b` may overflow
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &std::fs::File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &std::fs::File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                let rest = buf;
+                buf = &mut rest[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
 }
\ No newline at end of file
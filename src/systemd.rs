@@ -0,0 +1,190 @@
+//! systemd integration: `rdl daemon install-service` writes a user unit that runs
+//! `rdl watch` as a `Type=notify` service, and [`notify`] reports readiness and
+//! live progress over `$NOTIFY_SOCKET` (the sd_notify protocol) while it runs.
+//!
+//! Under such a service rdl stays in the foreground but otherwise behaves like
+//! `--daemon`: it writes the session's pid file and serves the control socket,
+//! so `rdl --pause`, `rdl --follow` and `rdl cancel` work as usual.
+
+use anyhow::{bail, Context, Result};
+#[cfg(target_os = "linux")]
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rdl_core::control::{wait_for_signal, INTERRUPTED_EXIT_CODE};
+use rdl_core::downloader::Downloader;
+
+/// How often the status shown by `systemctl status` is refreshed.
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time systemd gives rdl to save part states after SIGTERM before killing it.
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Whether systemd started this process as a `Type=notify` service.
+pub fn under_systemd() -> bool {
+    std::env::var_os("NOTIFY_SOCKET").is_some_and(|s| !s.is_empty())
+}
+
+/// Send `state` (e.g. "READY=1") to systemd; does nothing outside a notify service.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET").filter(|s| !s.is_empty()) else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+    // A leading '@' names a socket in the abstract namespace
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.as_bytes().strip_prefix(b"@") {
+        use std::os::linux::net::SocketAddrExt;
+        if let Ok(addr) = std::os::unix::net::SocketAddr::from_abstract_name(name) {
+            let _ = socket.send_to_addr(state.as_bytes(), &addr);
+        }
+        return;
+    }
+    let _ = socket.send_to(state.as_bytes(), Path::new(&path));
+}
+
+/// Tell systemd the service is up, and that it is stopping as soon as SIGTERM
+/// arrives; the downloads then save their part states and rdl exits.
+pub fn spawn_lifecycle() {
+    if !under_systemd() {
+        return;
+    }
+    notify("READY=1\nSTATUS=Starting");
+    tokio::spawn(async {
+        wait_for_signal().await;
+        notify("STOPPING=1\nSTATUS=Saving progress");
+    });
+}
+
+/// Keep the service status at the batch's progress line until the returned task is aborted.
+pub fn spawn_status(downloader: Arc<Downloader>) -> Option<tokio::task::JoinHandle<()>> {
+    under_systemd().then(|| {
+        tokio::spawn(async move {
+            loop {
+                notify(&format!("STATUS={}", downloader.summary()));
+                tokio::time::sleep(STATUS_INTERVAL).await;
+            }
+        })
+    })
+}
+
+/// What the generated unit runs.
+pub struct ServiceOptions<'a> {
+    pub tasks_file: &'a Path,
+    pub download_dir: &'a Path,
+    pub config: Option<&'a Path>,
+    pub state_dir: Option<&'a Path>,
+    pub force: bool,
+    pub dry_run: bool,
+}
+
+/// `rdl daemon install-service`: write a systemd user unit that keeps `rdl watch`
+/// running on the tasks file, one unit per `--session`.
+pub fn install_service(options: ServiceOptions) -> Result<()> {
+    let unit_name = match crate::session::session_name() {
+        Some(session) => format!("rdl-{}.service", session),
+        None => "rdl.service".to_string(),
+    };
+    let Some(unit_dir) = unit_dir() else {
+        bail!("Cannot locate the systemd user unit directory (neither XDG_CONFIG_HOME nor HOME is set)");
+    };
+    let unit_path = unit_dir.join(&unit_name);
+    let tasks_file = absolute(options.tasks_file)?;
+    let unit = render_unit(&options, &tasks_file)?;
+
+    if options.dry_run {
+        println!("# Would write {}", unit_path.display());
+        print!("{}", unit);
+        return Ok(());
+    }
+    if unit_path.exists() && !options.force {
+        bail!("{} already exists; pass --force to replace it", unit_path.display());
+    }
+    std::fs::create_dir_all(&unit_dir).with_context(|| format!("Failed to create {:?}", unit_dir))?;
+    std::fs::write(&unit_path, unit).with_context(|| format!("Failed to write {:?}", unit_path))?;
+    // rdl watch needs the file to exist
+    if !tasks_file.exists() {
+        std::fs::write(&tasks_file, "").with_context(|| format!("Failed to create {:?}", tasks_file))?;
+        println!("Created empty tasks file {}", tasks_file.display());
+    }
+
+    println!("Wrote {}", unit_path.display());
+    println!("Start it with:");
+    println!("  systemctl --user daemon-reload");
+    println!("  systemctl --user enable --now {}", unit_name);
+    println!("Add URLs to {} and they are downloaded right away.", tasks_file.display());
+    Ok(())
+}
+
+fn render_unit(options: &ServiceOptions, tasks_file: &Path) -> Result<String> {
+    let exe = std::env::current_exe().context("Cannot locate the rdl executable")?;
+    let mut command = vec![exe, PathBuf::from("--download-dir"), absolute(options.download_dir)?];
+    if let Some(config) = options.config {
+        command.extend([PathBuf::from("--config"), absolute(config)?]);
+    }
+    if let Some(state_dir) = options.state_dir {
+        command.extend([PathBuf::from("--state-dir"), absolute(state_dir)?]);
+    }
+    if let Some(session) = crate::session::session_name() {
+        command.extend([PathBuf::from("--session"), PathBuf::from(session)]);
+    }
+    command.extend([PathBuf::from("watch"), tasks_file.to_path_buf()]);
+    let exec_start = command.iter().map(|arg| quote(&arg.to_string_lossy())).collect::<Vec<_>>().join(" ");
+
+    Ok(format!(
+        "[Unit]
+Description=rdl downloader watching {tasks}
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={exec_start}
+# SIGTERM makes rdl save every part state and exit with {code}
+KillMode=mixed
+TimeoutStopSec={timeout}
+SuccessExitStatus={code}
+Restart=on-failure
+RestartSec=10
+
+[Install]
+WantedBy=default.target
+",
+        tasks = tasks_file.display(),
+        exec_start = exec_start,
+        code = INTERRUPTED_EXIT_CODE,
+        timeout = STOP_TIMEOUT.as_secs(),
+    ))
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user`, falling back to `~/.config/systemd/user`.
+fn unit_dir() -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("systemd").join("user"))
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    Ok(std::env::current_dir().context("Cannot read the current directory")?.join(path))
+}
+
+/// Quote one `ExecStart=` argument; `%` and `$` would otherwise be expanded by systemd.
+fn quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
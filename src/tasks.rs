@@ -0,0 +1,299 @@
+//! Tasks file format: one task per line, fields separated by `|`.
+//!
+//! ```text
+//! url[|mirror_url...][|hash][|size][|limit=2M][|name=file.bin][|at=01:00][|priority=1]
+//! ```
+//!
+//! Every field containing `://` is a download source (the first one is the
+//! primary URL, the others are mirrors); `limit=` sets a per-file rate limit;
+//! `name=` (or `out=`, as in aria2) overrides the path the file is saved to below
+//! the output directory, subdirectories included (`out=weights/model-00001.safetensors`);
+//! `at=` holds the download until a start time (see [`crate::schedule`]);
+//! `priority=` lets files with a higher value start first;
+//! a plain number is the file size in bytes (known sizes skip the HEAD request
+//! before downloading); the first remaining field is the hash, optionally
+//! prefixed with its algorithm (`md5:`, `sha1:`, `sha256:`, `blake3:`).
+//!
+//! Lines starting with `#` and blank lines are skipped. A `[section]` header
+//! followed by `dir=`, `rate=` and `priority=` lines gives the tasks below it,
+//! up to the next header, a subdirectory, a per-file rate limit and a priority.
+//! A task's `name=` is taken below the section's `dir=`; its `limit=` and
+//! `priority=` win over the section's:
+//!
+//! ```text
+//! [weights]
+//! dir=models/qwen
+//! rate=20M
+//! priority=10
+//! https://example.com/model-00001.safetensors|sha256:...
+//! ```
+//!
+//! aria2 input files, plain URL lists and Metalink files are read as well, see [`InputFormat`].
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use std::path::Path;
+use tokio::fs;
+
+use crate::downloader::item_relative_path;
+use crate::metalink::{is_metalink_path, parse_metalink};
+use crate::providers::DownloadItem;
+use crate::schedule::Schedule;
+use crate::torrent::is_torrent_source;
+use crate::utils::{get_filename_from_url, parse_rate};
+
+/// Format of a tasks file (`--input-format`).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// rdl's own `url|mirror|hash|size|limit=|name=|at=|priority=` lines (`out=` is read as
+    /// `name=`), with `#` comments and `[section]` headers
+    #[default]
+    Rdl,
+    /// aria2 `--input-file`: tab-separated mirror URIs, followed by indented
+    /// `out=`, `dir=`, `checksum=` and `max-download-limit=` options
+    Aria2,
+    /// One URL per line (the first word of each line), as fed to wget/curl
+    Plain,
+    /// Metalink 4 (`.meta4`) or 3 (`.metalink`) XML; picked automatically for those extensions
+    Metalink,
+}
+
+pub fn parse_task_line(line: &str) -> Option<DownloadItem> {
+    let raw = line.trim();
+    if raw.is_empty() || raw.starts_with('#') {
+        return None;
+    }
+
+    let mut urls = vec![];
+    let mut hash = None;
+    let mut rate_limit = None;
+    let mut size = None;
+    let mut name = None;
+    let mut schedule = None;
+    let mut priority = None;
+    for field in raw.split('|').map(str::trim).filter(|f| !f.is_empty()) {
+        if field.contains("://") || field.starts_with("magnet:") {
+            urls.push(field.to_string());
+        } else if let Some(limit) = field.strip_prefix("limit=") {
+            rate_limit = parse_rate(limit).ok();
+        } else if let Some(value) = field.strip_prefix("name=").or_else(|| field.strip_prefix("out=")) {
+            name = Some(value.to_string());
+        } else if let Some(value) = field.strip_prefix("at=") {
+            // Reported, as the download then starts right away
+            schedule = value.parse::<Schedule>().map_err(|e| eprintln!("Ignoring {}: {:#}", field, e)).ok();
+        } else if let Some(value) = field.strip_prefix("priority=") {
+            priority = value.parse().map_err(|_| eprintln!("Ignoring {}: not a whole number", field)).ok();
+        } else if let Some(bytes) = parse_size(field) {
+            size = Some(bytes);
+        } else if hash.is_none() {
+            hash = Some(field.to_string());
+        }
+    }
+
+    if urls.is_empty() {
+        return None;
+    }
+    let url = urls.remove(0);
+    Some(DownloadItem {
+        url,
+        mirrors: urls,
+        hash,
+        rate_limit,
+        size,
+        name,
+        signature: None,
+        pieces: None,
+        schedule,
+        priority,
+    })
+}
+
+/// A size field: decimal digits only. Hashes are at least 32 hex digits, longer
+/// than any `u64`, so the two cannot be confused.
+fn parse_size(field: &str) -> Option<u64> {
+    if field.len() < 32 && field.bytes().all(|b| b.is_ascii_digit()) {
+        field.parse().ok()
+    } else {
+        None
+    }
+}
+
+pub fn format_task_line(item: &DownloadItem) -> String {
+    let mut fields = vec![item.url.clone()];
+    fields.extend(item.mirrors.iter().cloned());
+    if let Some(hash) = &item.hash {
+        fields.push(hash.clone());
+    }
+    if let Some(size) = item.size {
+        fields.push(size.to_string());
+    }
+    if let Some(limit) = item.rate_limit {
+        fields.push(format!("limit={}", limit));
+    }
+    if let Some(name) = &item.name {
+        fields.push(format!("name={}", name));
+    }
+    if let Some(schedule) = &item.schedule {
+        fields.push(format!("at={}", schedule));
+    }
+    if let Some(priority) = item.priority {
+        fields.push(format!("priority={}", priority));
+    }
+    fields.join("|")
+}
+
+pub async fn read_tasks_file(path: &Path) -> Result<Vec<DownloadItem>> {
+    read_tasks_file_as(path, InputFormat::Rdl).await
+}
+
+pub async fn read_tasks_file_as(path: &Path, format: InputFormat) -> Result<Vec<DownloadItem>> {
+    let content = fs::read_to_string(path)
+        .await
+        .context(format!("Failed to open input file: {:?}", path))?;
+    let format = match format {
+        InputFormat::Rdl if is_metalink_path(path) => InputFormat::Metalink,
+        format => format,
+    };
+    parse_tasks(&content, format).with_context(|| format!("Failed to parse {:?}", path))
+}
+
+pub fn parse_tasks(content: &str, format: InputFormat) -> Result<Vec<DownloadItem>> {
+    Ok(match format {
+        InputFormat::Rdl => parse_rdl_lines(content)?.into_iter().map(|(_, item)| item).collect(),
+        InputFormat::Aria2 => parse_aria2(content),
+        InputFormat::Metalink => parse_metalink(content)?,
+        InputFormat::Plain => content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|url| url.contains("://"))
+            .map(|url| DownloadItem { url: url.to_string(), ..Default::default() })
+            .collect(),
+    })
+}
+
+/// Options of a `[section]`, applied to the tasks below its header.
+#[derive(Default)]
+struct Section {
+    dir: Option<String>,
+    rate_limit: Option<u32>,
+    priority: Option<i32>,
+}
+
+impl Section {
+    fn apply(&self, item: &mut DownloadItem) {
+        // Torrents are saved by the names of the files they list
+        let path = Some(&item.url).filter(|url| !is_torrent_source(url)).and_then(|_| item_relative_path(item).ok());
+        if let (Some(dir), Some(path)) = (&self.dir, path) {
+            item.name = Some(format!("{}/{}", dir.trim_end_matches('/'), path.to_string_lossy().replace('\\', "/")));
+        }
+        item.rate_limit = item.rate_limit.or(self.rate_limit);
+        item.priority = item.priority.or(self.priority);
+    }
+}
+
+/// Parse rdl's own format: task lines, `#` comments and `[section]` headers with
+/// their `dir=`, `rate=` and `priority=` lines. Each task comes with the index of
+/// its line in `content`, for editing the file in place.
+pub fn parse_rdl_lines(content: &str) -> Result<Vec<(usize, DownloadItem)>> {
+    let mut items = vec![];
+    let mut section = Section::default();
+    // Options only follow a header, before its first task
+    let mut in_header = false;
+    for (index, line) in content.lines().enumerate() {
+        let (number, line) = (index + 1, line.trim());
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = Section::default();
+            in_header = true;
+            continue;
+        }
+        if in_header && !line.contains("://") {
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim();
+                match key.trim() {
+                    "dir" if Path::new(value).is_absolute() || value.split(['/', '\\']).any(|c| c == "..") => {
+                        bail!("line {}: dir= must stay below the output directory: {}", number, value)
+                    }
+                    "dir" => section.dir = Some(value.to_string()).filter(|d| !d.is_empty()),
+                    "rate" => section.rate_limit = Some(parse_rate(value).map_err(|e| anyhow!("line {}: {}", number, e))?),
+                    "priority" => {
+                        section.priority = Some(value.parse().with_context(|| format!("line {}: invalid priority={}", number, value))?)
+                    }
+                    other => bail!("line {}: unknown section option {}= (expected dir=, rate= or priority=)", number, other),
+                }
+                continue;
+            }
+        }
+        in_header = false;
+        if let Some(mut item) = parse_task_line(line) {
+            section.apply(&mut item);
+            items.push((index, item));
+        }
+    }
+    Ok(items)
+}
+
+/// Parse an aria2 input file. Options aria2 has no use for here are ignored, as
+/// are `dir=` values that are absolute (files always go below the output directory).
+fn parse_aria2(content: &str) -> Vec<DownloadItem> {
+    let mut items: Vec<DownloadItem> = vec![];
+    // `dir=` and `out=` of the current item, combined once all of its options are read
+    let mut paths: Vec<(Option<String>, Option<String>)> = vec![];
+    for line in content.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t']) {
+            let mut urls = line.split('\t').map(str::trim).filter(|u| !u.is_empty()).map(str::to_string);
+            if let Some(url) = urls.next() {
+                items.push(DownloadItem { url, mirrors: urls.collect(), ..Default::default() });
+                paths.push((None, None));
+            }
+            continue;
+        }
+        let (Some(item), Some((dir, out))) = (items.last_mut(), paths.last_mut()) else {
+            continue;
+        };
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "out" => *out = Some(value.to_string()),
+            "dir" if !Path::new(value).is_absolute() => *dir = Some(value.to_string()),
+            "checksum" => item.hash = aria2_checksum(value),
+            "max-download-limit" => item.rate_limit = parse_rate(value).ok().filter(|limit| *limit > 0),
+            _ => {}
+        }
+    }
+
+    for (item, (dir, out)) in items.iter_mut().zip(paths) {
+        let out = match (&dir, out) {
+            (_, Some(out)) => Some(out),
+            (Some(_), None) => get_filename_from_url(&item.url).ok(),
+            (None, None) => None,
+        };
+        item.name = match (dir, out) {
+            (Some(dir), Some(out)) => Some(format!("{}/{}", dir.trim_end_matches('/'), out)),
+            (None, out) => out,
+            (Some(_), None) => None,
+        };
+    }
+    items
+}
+
+/// aria2's `checksum=<type>=<digest>` as a prefixed hash; types rdl cannot check are dropped.
+fn aria2_checksum(value: &str) -> Option<String> {
+    let (kind, digest) = value.split_once('=')?;
+    let algo = match kind.to_ascii_lowercase().as_str() {
+        "sha-256" => "sha256",
+        "sha-1" => "sha1",
+        "md5" => "md5",
+        _ => return None,
+    };
+    Some(format!("{}:{}", algo, digest.trim()))
+}
@@ -0,0 +1,124 @@
+use reqwest::{header, Response, StatusCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+use crate::utils::parse_retry_after;
+
+/// How long a host keeps its reduced segment concurrency after a 429.
+const COOLDOWN: Duration = Duration::from_secs(60);
+/// Upper bound for a server-provided `Retry-After`.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(600);
+
+/// The server answered 429 (or 503 with `Retry-After`).
+#[derive(Debug)]
+pub struct Throttled {
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for Throttled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.retry_after {
+            Some(wait) => write!(f, "rate limited by server (retry after {:.0}s)", wait.as_secs_f64()),
+            None => write!(f, "rate limited by server"),
+        }
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// Turn a throttling response into a `Throttled` error; other responses pass through.
+pub fn check_throttled(response: Response) -> Result<Response, Throttled> {
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after)
+        .map(|d| d.min(MAX_RETRY_AFTER));
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => Err(Throttled { retry_after }),
+        StatusCode::SERVICE_UNAVAILABLE if retry_after.is_some() => Err(Throttled { retry_after }),
+        _ => Ok(response),
+    }
+}
+
+struct HostSlot {
+    segments: Arc<Semaphore>,
+    reduced: Arc<AtomicBool>,
+    blocked_until: Option<Instant>,
+}
+
+/// Segment concurrency per host, shared by every file of a batch.
+///
+/// A 429 blocks new requests to the host until its `Retry-After` has passed and
+/// halves the number of segments it may serve at once for [`COOLDOWN`].
+pub struct HostThrottle {
+    max_segments: usize,
+    hosts: Mutex<HashMap<String, HostSlot>>,
+}
+
+impl HostThrottle {
+    pub fn new(max_segments: usize) -> Self {
+        Self {
+            max_segments: max_segments.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn host_of(url: &str) -> String {
+        url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+
+    /// Wait until `url`'s host accepts another segment; the permit must be held while streaming.
+    pub async fn acquire(&self, url: &str) -> tokio::sync::OwnedSemaphorePermit {
+        let host = Self::host_of(url);
+        let (segments, blocked_until) = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let slot = hosts.entry(host).or_insert_with(|| HostSlot {
+                segments: Arc::new(Semaphore::new(self.max_segments)),
+                reduced: Arc::new(AtomicBool::new(false)),
+                blocked_until: None,
+            });
+            (slot.segments.clone(), slot.blocked_until)
+        };
+        if let Some(until) = blocked_until {
+            tokio::time::sleep_until(until).await;
+        }
+        segments.acquire_owned().await.expect("host semaphore is never closed")
+    }
+
+    /// Record a throttling response from `url`'s host and return how long to wait before retrying.
+    pub fn throttled(&self, url: &str, error: &Throttled, fallback: Duration) -> Duration {
+        let wait = error.retry_after.unwrap_or(fallback);
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(slot) = hosts.get_mut(&Self::host_of(url)) else {
+            return wait;
+        };
+
+        let until = Instant::now() + wait;
+        slot.blocked_until = Some(slot.blocked_until.map_or(until, |t| t.max(until)));
+
+        if self.max_segments > 1 && !slot.reduced.swap(true, Ordering::Relaxed) {
+            // Reserve half of the permits for the cooldown; dropping the
+            // reservation afterwards restores the original concurrency.
+            let reserved = (self.max_segments / 2) as u32;
+            let segments = slot.segments.clone();
+            let reduced = slot.reduced.clone();
+            tokio::spawn(async move {
+                if let Ok(permit) = segments.acquire_many_owned(reserved).await {
+                    tokio::time::sleep(COOLDOWN).await;
+                    drop(permit);
+                }
+                reduced.store(false, Ordering::Relaxed);
+            });
+        }
+        wait
+    }
+}
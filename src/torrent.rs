@@ -0,0 +1,362 @@
+//! BitTorrent sources: `magnet:` links and `.torrent` files (http(s):// or
+//! file:// URLs) in the same queue as everything else.
+//!
+//! A `.torrent` with web seeds (BEP 19 `url-list`) is turned into one plain HTTP
+//! task per file, with the web seeds as mirrors, when the batch is loaded
+//! ([`expand_torrents`]); those files get segmented downloads, resume and
+//! progress like any other URL and need no torrent engine. Magnet links and
+//! torrents without web seeds are downloaded from the swarm by the engine
+//! (librqbit), which is only built with the `torrent` cargo feature.
+
+use anyhow::{anyhow, bail, Context, Result};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::downloader::DownloadOptions;
+use crate::providers::DownloadItem;
+use crate::utils::percent_decode;
+
+/// Characters escaped in the file paths appended to a web seed
+const PATH_SEGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'/');
+
+/// Lists and dictionaries nested deeper than this are rejected; metainfo needs a handful
+const MAX_BENCODE_DEPTH: usize = 64;
+
+pub fn is_torrent_source(url: &str) -> bool {
+    if url.starts_with("magnet:") {
+        return true;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.len() > 8 && path[path.len() - 8..].eq_ignore_ascii_case(".torrent")
+}
+
+/// What rdl needs from a `.torrent` file.
+#[derive(Debug, Clone)]
+pub struct TorrentMeta {
+    pub name: String,
+    /// Path inside the torrent (below `name` for multi-file torrents) and length
+    pub files: Vec<(Vec<String>, u64)>,
+    /// Single-file torrents have their file directly at `name`
+    pub single_file: bool,
+    pub web_seeds: Vec<String>,
+}
+
+impl TorrentMeta {
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|(_, len)| len).sum()
+    }
+}
+
+/// The display name (`dn`) of a magnet link.
+pub fn magnet_name(url: &str) -> Option<String> {
+    let query = url.strip_prefix("magnet:?")?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "dn")
+        .map(|(_, value)| percent_decode(&value.replace('+', " ")))
+        .filter(|name| !name.is_empty())
+}
+
+/// Replace `.torrent` tasks that have web seeds by HTTP tasks for their files, and
+/// name the remaining torrent tasks after their torrent so they get a target path.
+pub async fn expand_torrents(items: Vec<DownloadItem>, options: &DownloadOptions) -> Result<Vec<DownloadItem>> {
+    if !items.iter().any(|item| is_torrent_source(&item.url)) {
+        return Ok(items);
+    }
+    let client = options.client_builder().build()?;
+    let mut expanded = vec![];
+    for mut item in items {
+        if item.url.starts_with("magnet:") {
+            if item.name.is_none() {
+                item.name = magnet_name(&item.url);
+            }
+            expanded.push(item);
+            continue;
+        }
+        if !is_torrent_source(&item.url) {
+            expanded.push(item);
+            continue;
+        }
+        let bytes = read_torrent(&client, &item.url).await?;
+        let meta = parse_torrent(&bytes).with_context(|| format!("Invalid torrent {}", item.url))?;
+        if meta.web_seeds.is_empty() {
+            item.name = item.name.or_else(|| Some(meta.name.clone()));
+            item.size = item.size.or(Some(meta.total_size()));
+            expanded.push(item);
+            continue;
+        }
+        // The task's own name, if any, replaces the torrent's top-level name
+        let root = item.name.clone().unwrap_or_else(|| meta.name.clone());
+        for (path, len) in &meta.files {
+            let mut sources = meta.web_seeds.iter().map(|seed| web_seed_url(seed, &meta, path));
+            let Some(url) = sources.next() else {
+                continue;
+            };
+            let name = match meta.single_file {
+                true => root.clone(),
+                false => format!("{}/{}", root, path.join("/")),
+            };
+            expanded.push(DownloadItem {
+                url,
+                mirrors: sources.collect(),
+                size: Some(*len),
+                name: Some(name),
+                rate_limit: item.rate_limit,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(expanded)
+}
+
+async fn read_torrent(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        let path = percent_decode(path);
+        return tokio::fs::read(&path).await.with_context(|| format!("Failed to read {}", path));
+    }
+    let response = client.get(url).send().await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// BEP 19: a seed URL ending in `/` (and every seed of a multi-file torrent) is a
+/// directory the torrent's paths are appended to.
+fn web_seed_url(seed: &str, meta: &TorrentMeta, path: &[String]) -> String {
+    if meta.single_file && !seed.ends_with('/') {
+        return seed.to_string();
+    }
+    let mut url = seed.trim_end_matches('/').to_string();
+    let name = std::iter::once(&meta.name).chain(path.iter().filter(|_| !meta.single_file));
+    for segment in name {
+        url.push('/');
+        url.extend(utf8_percent_encode(segment, PATH_SEGMENT));
+    }
+    url
+}
+
+pub fn parse_torrent(bytes: &[u8]) -> Result<TorrentMeta> {
+    let (root, _) = Bencode::parse(bytes)?;
+    let info = root.get("info").ok_or_else(|| anyhow!("no info dictionary"))?;
+    let name = info
+        .get("name.utf-8")
+        .or_else(|| info.get("name"))
+        .and_then(Bencode::as_str)
+        .ok_or_else(|| anyhow!("no name"))?;
+    let (files, single_file) = match info.get("files").and_then(Bencode::as_list) {
+        Some(files) => {
+            let files = files
+                .iter()
+                .map(|file| {
+                    let len = file.get("length").and_then(Bencode::as_int).ok_or_else(|| anyhow!("file without length"))?;
+                    let path = file
+                        .get("path.utf-8")
+                        .or_else(|| file.get("path"))
+                        .and_then(Bencode::as_list)
+                        .ok_or_else(|| anyhow!("file without path"))?
+                        .iter()
+                        .map(|segment| segment.as_str().ok_or_else(|| anyhow!("invalid path")))
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok((path, u64::try_from(len).map_err(|_| anyhow!("negative file length"))?))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (files, false)
+        }
+        None => {
+            let len = info.get("length").and_then(Bencode::as_int).ok_or_else(|| anyhow!("no length"))?;
+            (vec![(vec![], u64::try_from(len).map_err(|_| anyhow!("negative length"))?)], true)
+        }
+    };
+    let web_seeds = match root.get("url-list") {
+        Some(Bencode::List(seeds)) => seeds.iter().filter_map(Bencode::as_str).collect(),
+        Some(seed) => seed.as_str().into_iter().collect(),
+        None => vec![],
+    };
+    Ok(TorrentMeta {
+        name,
+        files,
+        single_file,
+        web_seeds: web_seeds.into_iter().filter(|s| s.starts_with("http")).collect(),
+    })
+}
+
+/// Just enough bencode to read a metainfo file.
+enum Bencode {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Bencode>),
+    Dict(BTreeMap<Vec<u8>, Bencode>),
+}
+
+impl Bencode {
+    /// The value at the start of `input` and the bytes after it.
+    fn parse(input: &[u8]) -> Result<(Bencode, &[u8])> {
+        Bencode::parse_nested(input, 0)
+    }
+
+    /// [`Bencode::parse`] for a value inside `depth` lists and dictionaries.
+    fn parse_nested(input: &[u8], depth: usize) -> Result<(Bencode, &[u8])> {
+        if depth > MAX_BENCODE_DEPTH {
+            bail!("bencode nested too deeply");
+        }
+        match input.first() {
+            Some(b'i') => {
+                let end = input.iter().position(|b| *b == b'e').ok_or_else(|| anyhow!("unterminated integer"))?;
+                let value = std::str::from_utf8(&input[1..end])?.parse()?;
+                Ok((Bencode::Int(value), &input[end + 1..]))
+            }
+            Some(b'l') => {
+                let mut rest = &input[1..];
+                let mut list = vec![];
+                while rest.first() != Some(&b'e') {
+                    let (value, after) = Bencode::parse_nested(rest, depth + 1)?;
+                    list.push(value);
+                    rest = after;
+                }
+                Ok((Bencode::List(list), &rest[1..]))
+            }
+            Some(b'd') => {
+                let mut rest = &input[1..];
+                let mut dict = BTreeMap::new();
+                while rest.first() != Some(&b'e') {
+                    let (Bencode::Bytes(key), after) = Bencode::parse_nested(rest, depth + 1)? else {
+                        bail!("dictionary key is not a string");
+                    };
+                    let (value, after) = Bencode::parse_nested(after, depth + 1)?;
+                    dict.insert(key, value);
+                    rest = after;
+                }
+                Ok((Bencode::Dict(dict), &rest[1..]))
+            }
+            Some(b'0'..=b'9') => {
+                let colon = input.iter().position(|b| *b == b':').ok_or_else(|| anyhow!("invalid string"))?;
+                let len: usize = std::str::from_utf8(&input[..colon])?.parse()?;
+                let end = (colon + 1).checked_add(len).ok_or_else(|| anyhow!("invalid string length"))?;
+                let data = input.get(colon + 1..end).ok_or_else(|| anyhow!("truncated string"))?;
+                Ok((Bencode::Bytes(data.to_vec()), &input[end..]))
+            }
+            _ => bail!("truncated or invalid bencode"),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Bencode> {
+        match self {
+            Bencode::Dict(dict) => dict.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<String> {
+        match self {
+            Bencode::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Bencode::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_list(&self) -> Option<&[Bencode]> {
+        match self {
+            Bencode::List(list) => Some(list),
+            _ => None,
+        }
+    }
+}
+
+/// Progress of a swarm download: bytes done and total (0 while the metadata of a
+/// magnet link is still being fetched).
+pub type SwarmProgress = (u64, u64);
+
+#[cfg(feature = "torrent")]
+mod engine {
+    use super::*;
+    use librqbit::{AddTorrent, AddTorrentOptions, Session, SessionOptions};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::OnceCell;
+
+    use crate::control::PauseToken;
+
+    /// How often the progress of a swarm download is read
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// One session (DHT, listening port) serves every torrent of the process
+    static SESSION: OnceCell<Arc<Session>> = OnceCell::const_new();
+
+    async fn session(output_dir: &Path) -> Result<&'static Arc<Session>> {
+        SESSION
+            .get_or_try_init(|| async {
+                Session::new_with_opts(output_dir.to_path_buf(), SessionOptions::default())
+                    .await
+                    .context("Failed to start the torrent session")
+            })
+            .await
+    }
+
+    /// Download the torrent `source` into `dir` (its files land at `dir/<torrent name>`),
+    /// calling `progress` as data comes in.
+    pub async fn download(
+        source: &str,
+        dir: &Path,
+        pause: &PauseToken,
+        mut progress: impl FnMut(SwarmProgress),
+    ) -> Result<()> {
+        let session = session(dir).await?;
+        let torrent = match source.strip_prefix("file://") {
+            Some(path) => AddTorrent::from_bytes(tokio::fs::read(percent_decode(path)).await?),
+            None => AddTorrent::from_url(source),
+        };
+        let options = AddTorrentOptions {
+            output_folder: Some(dir.to_string_lossy().into_owned()),
+            overwrite: true,
+            ..Default::default()
+        };
+        let handle = session
+            .add_torrent(torrent, Some(options))
+            .await
+            .with_context(|| format!("Failed to add torrent {}", source))?
+            .into_handle()
+            .ok_or_else(|| anyhow!("Torrent {} was not added", source))?;
+
+        let result = async {
+            loop {
+                let stats = handle.stats();
+                progress((stats.progress_bytes, stats.total_bytes));
+                if let Some(error) = stats.error {
+                    bail!("Torrent failed: {}", error);
+                }
+                if stats.finished {
+                    return Ok(());
+                }
+                if pause.is_paused() {
+                    session.pause(&handle).await?;
+                    pause.wait_resumed().await?;
+                    session.unpause(&handle).await?;
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+        .await;
+        // Stop seeding; the downloaded files stay
+        let _ = session.delete(handle.id().into(), false).await;
+        result
+    }
+}
+
+#[cfg(feature = "torrent")]
+pub use engine::download;
+
+#[cfg(not(feature = "torrent"))]
+pub async fn download(
+    source: &str,
+    _dir: &Path,
+    _pause: &crate::control::PauseToken,
+    _progress: impl FnMut(SwarmProgress),
+) -> Result<()> {
+    bail!("{} needs a torrent engine; rebuild rdl with `--features torrent`", source)
+}
@@ -0,0 +1,255 @@
+use anyhow::{bail, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use indicatif::{HumanBytes, HumanDuration};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{Frame, Terminal};
+use std::io::{stdout, Stdout};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::commands::{collect_status, FileStatus, ListReport};
+use crate::ipc::{IpcRequest, IpcResponse};
+
+const REFRESH: Duration = Duration::from_millis(500);
+const BAR_WIDTH: usize = 20;
+
+type Term = Terminal<CrosstermBackend<Stdout>>;
+
+struct App {
+    report: ListReport,
+    /// Daemon status, `None` when no daemon is reachable
+    daemon: Option<IpcResponse>,
+    table: TableState,
+    message: String,
+}
+
+impl App {
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.report.files.len();
+        if len == 0 {
+            self.table.select(None);
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        self.table.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+    }
+
+    fn selected(&self) -> Option<&FileStatus> {
+        self.table.selected().and_then(|i| self.report.files.get(i))
+    }
+
+    fn send(&mut self, request: IpcRequest) {
+        if self.daemon.is_none() {
+            self.message = "Daemon is not running; controls are unavailable.".to_string();
+            return;
+        }
+        self.message = match crate::ipc::send_request(&request) {
+            Ok(response) => response.message,
+            Err(e) => format!("{:#}", e),
+        };
+    }
+
+    fn send_for_selected(&mut self, request: impl FnOnce(String) -> IpcRequest) {
+        match self.selected() {
+            Some(file) => {
+                let request = request(file.url.clone());
+                self.send(request);
+            }
+            None => self.message = "No file selected.".to_string(),
+        }
+    }
+
+    /// Status shown for a file, taking pauses known only to the daemon into account.
+    fn status_of(&self, file: &FileStatus) -> &'static str {
+        if !matches!(file.status, "Downloading" | "Stalled") {
+            return file.status;
+        }
+        match &self.daemon {
+            Some(d) if d.paused || d.paused_tasks.contains(&file.url) => "Paused",
+            _ => file.status,
+        }
+    }
+}
+
+fn query_daemon() -> Option<IpcResponse> {
+    crate::daemon::get_daemon_pid().ok().flatten()?;
+    crate::ipc::send_request(&IpcRequest::Status).ok()
+}
+
+/// Live dashboard of every download in `output`, controlling the daemon over its socket.
+pub async fn run(output: PathBuf, input: PathBuf, db_path: Option<PathBuf>, temp_dir: Option<PathBuf>) -> Result<()> {
+    let Some(db_path) = db_path else {
+        bail!("No job database configured (set --db or RDL_DB)");
+    };
+
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    let result = event_loop(&mut terminal, &output, &input, &db_path, temp_dir.as_deref()).await;
+
+    // Always give the terminal back, even if the loop failed
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+async fn event_loop(terminal: &mut Term, output: &Path, input: &Path, db_path: &Path, temp_dir: Option<&Path>) -> Result<()> {
+    let mut app = App {
+        report: collect_status(output, input, db_path, temp_dir).await?,
+        daemon: query_daemon(),
+        table: TableState::default(),
+        message: String::new(),
+    };
+    app.move_selection(0);
+    let mut last_refresh = Instant::now();
+
+    loop {
+        terminal.draw(|f| draw(f, &mut app))?;
+
+        let timeout = REFRESH.saturating_sub(last_refresh.elapsed());
+        if tokio::task::block_in_place(|| event::poll(timeout))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        // Raw mode turns Ctrl+C into a key press
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                        KeyCode::Down | KeyCode::Char('j') => app.move_selection(1),
+                        KeyCode::Up | KeyCode::Char('k') => app.move_selection(-1),
+                        KeyCode::Char('p') => app.send_for_selected(|url| IpcRequest::PauseTask { url }),
+                        KeyCode::Char('r') => app.send_for_selected(|url| IpcRequest::ResumeTask { url }),
+                        KeyCode::Char('c') => app.send_for_selected(|url| IpcRequest::CancelTask { url, purge: false }),
+                        KeyCode::Char('P') => app.send(IpcRequest::Pause),
+                        KeyCode::Char('R') => app.send(IpcRequest::Resume),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if last_refresh.elapsed() >= REFRESH {
+            if let Ok(report) = collect_status(output, input, db_path, temp_dir).await {
+                app.report = report;
+            }
+            app.daemon = query_daemon();
+            app.move_selection(0);
+            last_refresh = Instant::now();
+        }
+    }
+}
+
+fn progress_bar(progress: f64) -> String {
+    let filled = ((progress / 100.0) * BAR_WIDTH as f64).round().clamp(0.0, BAR_WIDTH as f64) as usize;
+    format!("[{}{}] {:>6.2}%", "=".repeat(filled), "-".repeat(BAR_WIDTH - filled), progress)
+}
+
+fn draw(f: &mut Frame, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(5), Constraint::Length(3)])
+        .split(f.size());
+
+    let summary = &app.report.summary;
+    let daemon = match &app.daemon {
+        Some(d) if d.paused => "paused".to_string(),
+        Some(d) => match (d.bytes_per_sec, d.eta_secs) {
+            (Some(speed), Some(eta)) => format!(
+                "running | Speed: {}/s | ETA: {}",
+                HumanBytes(speed),
+                HumanDuration(Duration::from_secs(eta))
+            ),
+            (Some(speed), None) => format!("running | Speed: {}/s", HumanBytes(speed)),
+            _ => "running".to_string(),
+        },
+        None => "not running".to_string(),
+    };
+    let header = Paragraph::new(format!(
+        "Files: {}/{} | Active: {} | Paused: {} | Interrupted: {} | Pending: {} | Scheduled: {} | Failed: {} | Downloaded: {} / {} | Daemon: {}",
+        summary.completed,
+        summary.total,
+        summary.active,
+        summary.paused,
+        summary.interrupted,
+        summary.pending,
+        summary.scheduled,
+        summary.failed,
+        HumanBytes(summary.downloaded_bytes),
+        HumanBytes(summary.total_bytes),
+        daemon
+    ))
+    .block(Block::default().borders(Borders::ALL).title("rdl"));
+    f.render_widget(header, chunks[0]);
+
+    let rows: Vec<Row> = app
+        .report
+        .files
+        .iter()
+        .map(|file| {
+            let status = app.status_of(file);
+            let color = match status {
+                "Completed" => Color::Green,
+                "Failed" => Color::Red,
+                "Paused" | "Stalled" => Color::Yellow,
+                "Interrupted" => Color::Magenta,
+                "Pending" => Color::DarkGray,
+                "Scheduled" => Color::Blue,
+                _ => Color::Cyan,
+            };
+            let speed = file
+                .bytes_per_sec
+                .filter(|_| status == "Downloading")
+                .map(|s| format!("{}/s", HumanBytes(s)))
+                .unwrap_or_else(|| "-".to_string());
+            let eta = match &file.scheduled_for {
+                Some(at) => format!("starts {}", at),
+                None => file
+                    .eta_secs
+                    .filter(|_| status == "Downloading")
+                    .map(|s| format!("{}", HumanDuration(Duration::from_secs(s))))
+                    .unwrap_or_else(|| "-".to_string()),
+            };
+            Row::new(vec![
+                Cell::from(file.name.clone()),
+                Cell::from(status).style(Style::default().fg(color)),
+                Cell::from(progress_bar(file.progress)),
+                Cell::from(HumanBytes(file.total_bytes).to_string()),
+                Cell::from(speed),
+                Cell::from(eta),
+                Cell::from(file.error.clone().unwrap_or_default()).style(Style::default().fg(Color::Red)),
+            ])
+        })
+        .collect();
+    let widths = [
+        Constraint::Percentage(30),
+        Constraint::Length(12),
+        Constraint::Length(BAR_WIDTH as u16 + 10),
+        Constraint::Length(11),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Min(10),
+    ];
+    let table = Table::new(rows, widths)
+        .header(
+            Row::new(vec!["Filename", "Status", "Progress", "Size", "Speed", "ETA", "Error"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title("Downloads"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(table, chunks[1], &mut app.table);
+
+    let help = "↑/↓ select  p pause  r resume  c cancel  P pause all  R resume all  q quit";
+    let footer_text = if app.message.is_empty() {
+        help.to_string()
+    } else {
+        format!("{}  |  {}", help, app.message)
+    };
+    let footer = Paragraph::new(footer_text).block(Block::default().borders(Borders::ALL));
+    f.render_widget(footer, chunks[2]);
+}
@@ -1,14 +1,20 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use url::Url;
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::sync::Arc;
+use reqwest::redirect::Policy;
+
+use crate::dns::DohResolver;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub fn get_filename_from_url(url_str: &str) -> Result<String> {
     let url = Url::parse(url_str)?;
     
-    if let Some(segments) = url.path_segments() {
-        if let Some(filename) = segments.last() {
+    if let Some(mut segments) = url.path_segments() {
+        if let Some(filename) = segments.next_back() {
             if !filename.is_empty() {
-                return Ok(filename.to_string());
+                return Ok(percent_decode(filename));
             }
         }
     }
@@ -17,26 +23,423 @@ pub fn get_filename_from_url(url_str: &str) -> Result<String> {
     Ok(format!("download_{}", uuid::Uuid::new_v4()))
 }
 
+/// Percent-decode a URL path segment; invalid UTF-8 is replaced rather than rejected.
+pub fn percent_decode(segment: &str) -> String {
+    percent_encoding::percent_decode_str(segment).decode_utf8_lossy().into_owned()
+}
+
+/// Make `filename` safe as a single path component. Only path separators and
+/// control characters (plus the characters Windows rejects, on Windows) become
+/// `_`; spaces, `+`, CJK and other Unicode are kept as they are.
 pub fn sanitize_filename(filename: &str) -> String {
-    filename.replace(|c: char| !c.is_alphanumeric() && c != '.' && c != '-' && c != '_', "_")
+    let name = filename.replace(
+        |c: char| c == '/' || c == '\\' || c.is_control() || (cfg!(windows) && "<>:\"|?*".contains(c)),
+        "_",
+    );
+    match name.as_str() {
+        "." | ".." => name.replace('.', "_"),
+        _ => name,
+    }
 }
 
-pub fn get_unique_filepath(dir: &PathBuf, filename: &str) -> PathBuf {
-    let mut path = dir.join(filename);
-    let mut counter = 1;
+/// `--ascii-names`: additionally replace everything but ASCII letters, digits, `.`, `-` and `_`.
+pub fn ascii_filename(filename: &str) -> String {
+    filename.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-' && c != '_', "_")
+}
+
+/// Sanitize every component of a `/`-separated relative path, dropping empty,
+/// `.` and `..` components so the result can never escape the output directory.
+pub fn sanitize_relative_path(path: &str) -> Option<PathBuf> {
+    let mut result = PathBuf::new();
+    for component in path.split('/') {
+        if component.is_empty() || component == "." || component == ".." {
+            continue;
+        }
+        result.push(sanitize_filename(component));
+    }
+    if result.as_os_str().is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+pub fn get_unique_filepath(dir: &Path, filename: &str) -> PathBuf {
+    dir.join(unique_filename(filename, |name| dir.join(name).exists()))
+}
 
-    while path.exists() {
-        let file_stem = path.file_stem().unwrap().to_string_lossy();
-        let extension = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
-        
-        let new_filename = if extension.is_empty() {
-            format!("{}_{}", file_stem, counter)
+/// `filename`, or `stem_1.ext`, `stem_2.ext`, ... until `taken` returns false.
+/// A `/`-separated directory prefix is kept as is.
+pub fn unique_filename(filename: &str, taken: impl Fn(&str) -> bool) -> String {
+    let (dir, base) = match filename.rsplit_once('/') {
+        Some((dir, base)) => (format!("{}/", dir), base),
+        None => (String::new(), filename),
+    };
+    let path = Path::new(base);
+    let file_stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut name = filename.to_string();
+    let mut counter = 1;
+    while taken(&name) {
+        name = if extension.is_empty() {
+            format!("{}{}_{}", dir, file_stem, counter)
         } else {
-            format!("{}_{}.{}", file_stem, counter, extension)
+            format!("{}{}_{}.{}", dir, file_stem, counter, extension)
         };
-        
-        path = dir.join(new_filename);
         counter += 1;
     }
-    path
-}
\ No newline at end of file
+    name
+}
+
+/// File name from a `Content-Disposition` header (RFC 6266). `filename*=` (RFC 5987,
+/// `UTF-8''...` or `ISO-8859-1''...`) wins over a plain `filename=`. Directory parts
+/// are dropped so a server cannot choose where the file lands.
+pub fn parse_content_disposition(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+    for param in split_header_params(value).into_iter().skip(1) {
+        let Some((key, val)) = param.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => extended = decode_ext_value(val.trim()),
+            "filename" => plain = Some(unquote(val.trim())),
+            _ => {}
+        }
+    }
+    let name = extended.or(plain)?;
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(&name).trim().to_string();
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Split header parameters on `;`, ignoring separators inside quoted strings.
+fn split_header_params(value: &str) -> Vec<String> {
+    let mut params = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    params.push(current);
+    params
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => {
+            let mut result = String::new();
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                result.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+            }
+            result
+        }
+        None => value.to_string(),
+    }
+}
+
+/// Decode an RFC 5987 `charset'language'percent-encoded` value.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?.to_ascii_lowercase();
+    let _language = parts.next()?;
+    let bytes: Vec<u8> = percent_encoding::percent_decode_str(parts.next()?).collect();
+    match charset.as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        // Every ISO-8859-1 byte is the Unicode code point of the same value
+        "iso-8859-1" => Some(bytes.into_iter().map(char::from).collect()),
+        _ => None,
+    }
+}
+
+/// Exponential backoff (`base * 2^(attempt-1)`, capped at 60s) with up to 50% random jitter.
+pub fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let exp = base
+        .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+        .min(Duration::from_secs(60));
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let jitter = exp.mul_f64((nanos % 1000) as f64 / 2000.0);
+    exp + jitter
+}
+
+/// Byte count with an optional binary unit (`K`, `M`, `G`); `what` names the value in errors.
+fn parse_bytes(value: &str, what: &str) -> Result<f64, String> {
+    let value = value.trim();
+    let split_at = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split_at);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid {}: {}", what, value))?;
+    let multiplier = match unit.trim().trim_end_matches(['B', 'b']).to_ascii_uppercase().as_str() {
+        "" => 1.0,
+        "K" | "KI" => 1024.0,
+        "M" | "MI" => 1024.0 * 1024.0,
+        "G" | "GI" => 1024.0 * 1024.0 * 1024.0,
+        other => return Err(format!("unknown {} unit: {}", what, other)),
+    };
+    Ok(number * multiplier)
+}
+
+/// Parse a rate in bytes per second such as `1048576`, `512K` or `2M` (binary units).
+pub fn parse_rate(value: &str) -> Result<u32, String> {
+    let bytes = parse_bytes(value, "rate")?;
+    if bytes < 1.0 || bytes > u32::MAX as f64 {
+        return Err(format!("rate out of range: {}", value.trim()));
+    }
+    Ok(bytes as u32)
+}
+
+/// Parse a size such as `4M` or `512K` (binary units).
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let bytes = parse_bytes(value, "size")?;
+    if bytes < 1.0 || bytes > u64::MAX as f64 {
+        return Err(format!("size out of range: {}", value.trim()));
+    }
+    Ok(bytes as u64)
+}
+
+/// Parse a `Retry-After` header value: delay in seconds or an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.timestamp() - chrono::Utc::now().timestamp();
+    Some(Duration::from_secs(wait.max(0) as u64))
+}
+
+/// Redirects reqwest follows unless told otherwise
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Connection settings every HTTP client of rdl is built with (downloads, size
+/// probes, providers): `--proxy`, TLS, local address, DNS and redirect options.
+#[derive(Clone, Default)]
+pub struct NetworkOptions {
+    pub proxy: Option<String>,
+    /// Root certificates trusted in addition to the system ones (`--ca-cert`)
+    pub ca_certs: Vec<reqwest::Certificate>,
+    /// Client certificate for servers that require mutual TLS (`--client-cert`)
+    pub identity: Option<reqwest::Identity>,
+    /// Accept invalid and self-signed server certificates (`--insecure`)
+    pub insecure: bool,
+    /// Local address connections are made from (`--interface`); an unspecified address
+    /// (`0.0.0.0` or `::`) only picks the address family (`--ipv4`, `--ipv6`)
+    pub local_address: Option<IpAddr>,
+    /// Fixed addresses for single hosts (`--resolve`)
+    pub resolve: Vec<(String, std::net::SocketAddr)>,
+    /// Resolver for all other hosts (`--doh-url`), else the system resolver
+    pub doh: Option<Arc<DohResolver>>,
+    /// Redirects followed per request (`--max-redirects`), else reqwest's default of 10
+    pub max_redirects: Option<usize>,
+    /// Keep the Authorization header of a download when it is redirected to another host
+    /// (`--forward-auth`). By default it is dropped there, so a token never reaches a CDN.
+    pub forward_auth: bool,
+}
+
+impl NetworkOptions {
+    pub fn client_builder(&self) -> reqwest::ClientBuilder {
+        let mut builder = with_proxy(reqwest::Client::builder(), self.proxy.as_deref());
+        for cert in &self.ca_certs {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+        for (host, address) in &self.resolve {
+            builder = builder.resolve(host, *address);
+        }
+        if let Some(doh) = &self.doh {
+            builder = builder.dns_resolver(doh.clone());
+        }
+        builder
+            .danger_accept_invalid_certs(self.insecure)
+            .local_address(self.local_address)
+            .redirect(self.redirect_policy())
+    }
+
+    fn redirect_policy(&self) -> Policy {
+        match self.max_redirects {
+            Some(0) => Policy::none(),
+            Some(max) => Policy::limited(max),
+            None => Policy::default(),
+        }
+    }
+
+    /// Redirect policy of clients whose requests go through [`NetworkOptions::send`]:
+    /// with `--forward-auth` they follow no redirects themselves.
+    pub fn send_policy(&self) -> Policy {
+        if self.forward_auth {
+            Policy::none()
+        } else {
+            self.redirect_policy()
+        }
+    }
+
+    /// Send a download request. reqwest drops Authorization and Cookie headers on a
+    /// redirect to another host; with `--forward-auth` redirects are followed here
+    /// instead, keeping every header of the request on each hop except from https
+    /// to plain http, where the credentials would go out unencrypted.
+    pub async fn send(&self, client: &reqwest::Client, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        if !self.forward_auth {
+            return Ok(request.send().await?);
+        }
+        let max = self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS);
+        let mut request = request.build()?;
+        for _ in 0..=max {
+            let retry = request.try_clone();
+            let response = client.execute(request).await?;
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .filter(|_| response.status().is_redirection())
+                .and_then(|l| l.to_str().ok())
+                .and_then(|l| response.url().join(l).ok())
+                .filter(|l| matches!(l.scheme(), "http" | "https"));
+            let (Some(mut next), Some(location)) = (retry, location) else {
+                return Ok(response);
+            };
+            if next.url().scheme() == "https" && location.scheme() == "http" {
+                for name in [reqwest::header::AUTHORIZATION, reqwest::header::COOKIE, reqwest::header::PROXY_AUTHORIZATION] {
+                    next.headers_mut().remove(name);
+                }
+            }
+            *next.url_mut() = location;
+            request = next;
+        }
+        Err(anyhow!("Too many redirects (more than {})", max))
+    }
+}
+
+impl std::fmt::Debug for NetworkOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NetworkOptions")
+            .field("proxy", &self.proxy)
+            .field("ca_certs", &self.ca_certs.len())
+            .field("identity", &self.identity.is_some())
+            .field("insecure", &self.insecure)
+            .field("local_address", &self.local_address)
+            .field("resolve", &self.resolve)
+            .field("doh", &self.doh.is_some())
+            .field("max_redirects", &self.max_redirects)
+            .field("forward_auth", &self.forward_auth)
+            .finish()
+    }
+}
+
+/// The address of network interface `name` to connect from, IPv6 if `ipv6` else IPv4.
+#[cfg(unix)]
+pub fn interface_address(name: &str, ipv6: bool) -> Result<IpAddr> {
+    let addresses = nix::ifaddrs::getifaddrs().context("Failed to list network interfaces")?;
+    addresses
+        .filter(|ifaddr| ifaddr.interface_name == name)
+        .filter_map(|ifaddr| {
+            let addr = ifaddr.address?;
+            match (addr.as_sockaddr_in(), addr.as_sockaddr_in6()) {
+                (Some(v4), _) if !ipv6 => Some(IpAddr::V4(*std::net::SocketAddrV4::from(*v4).ip())),
+                (_, Some(v6)) if ipv6 => Some(IpAddr::V6(*std::net::SocketAddrV6::from(*v6).ip())),
+                _ => None,
+            }
+        })
+        // Link-local IPv6 addresses cannot reach mirrors
+        .find(|ip| !matches!(ip, IpAddr::V6(v6) if v6.segments()[0] & 0xffc0 == 0xfe80))
+        .ok_or_else(|| anyhow!("Interface {} has no {} address", name, if ipv6 { "IPv6" } else { "IPv4" }))
+}
+
+#[cfg(not(unix))]
+pub fn interface_address(name: &str, _ipv6: bool) -> Result<IpAddr> {
+    Err(anyhow!("Interface names are only supported on Unix; give the address of {} instead", name))
+}
+
+/// Load a CA certificate, PEM or DER.
+pub fn load_ca_cert(path: &Path) -> Result<reqwest::Certificate> {
+    let data = std::fs::read(path).context(format!("Failed to read CA certificate: {:?}", path))?;
+    reqwest::Certificate::from_pem(&data)
+        .or_else(|_| reqwest::Certificate::from_der(&data))
+        .context(format!("Invalid CA certificate: {:?}", path))
+}
+
+/// Load a client certificate and its PKCS#8 private key, both PEM.
+pub fn load_identity(cert: &Path, key: &Path) -> Result<reqwest::Identity> {
+    let cert_pem = std::fs::read(cert).context(format!("Failed to read client certificate: {:?}", cert))?;
+    let key_pem = std::fs::read(key).context(format!("Failed to read client key: {:?}", key))?;
+    reqwest::Identity::from_pkcs8_pem(&cert_pem, &key_pem).context("Invalid client certificate or key (expected PEM, PKCS#8 key)")
+}
+
+/// Route a client through `proxy` when set. The proxy URL is validated at startup,
+/// so an invalid value here is simply ignored.
+pub fn with_proxy(builder: reqwest::ClientBuilder, proxy: Option<&str>) -> reqwest::ClientBuilder {
+    match proxy.and_then(|p| reqwest::Proxy::all(p).ok()) {
+        Some(proxy) => builder.proxy(proxy),
+        None => builder,
+    }
+}
+
+/// Load a Netscape/Mozilla `cookies.txt` file (as exported by browsers, curl or wget).
+pub fn load_cookie_file(path: &Path) -> Result<reqwest::cookie::Jar> {
+    let content = std::fs::read_to_string(path).context(format!("Failed to read cookie file: {:?}", path))?;
+    let jar = reqwest::cookie::Jar::default();
+    for line in content.lines() {
+        // curl marks HttpOnly cookies with a prefix on an otherwise commented line
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 7 {
+            continue;
+        }
+        let (domain, path, secure, name, value) = (fields[0], fields[2], fields[3], fields[5], fields[6]);
+        let host = domain.trim_start_matches('.');
+        let scheme = if secure.eq_ignore_ascii_case("TRUE") { "https" } else { "http" };
+        let Ok(url) = Url::parse(&format!("{}://{}{}", scheme, host, path)) else {
+            continue;
+        };
+        let mut cookie = format!("{}={}; Path={}", name, value, path);
+        if domain.starts_with('.') {
+            cookie.push_str(&format!("; Domain={}", host));
+        }
+        jar.add_cookie_str(&cookie, &url);
+    }
+    Ok(jar)
+}
+
+/// Per-user directory for rdl's state (job database, daemon pid file and log):
+/// `$XDG_STATE_HOME/rdl`, `~/Library/Application Support/rdl` on macOS,
+/// `%LOCALAPPDATA%\rdl` on Windows, otherwise `~/.local/state/rdl`.
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("rdl"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("LOCALAPPDATA").filter(|d| !d.is_empty()) {
+            return Some(PathBuf::from(dir).join("rdl"));
+        }
+    }
+    let home = PathBuf::from(std::env::var_os("HOME").filter(|d| !d.is_empty())?);
+    if cfg!(target_os = "macos") {
+        return Some(home.join("Library").join("Application Support").join("rdl"));
+    }
+    Some(home.join(".local").join("state").join("rdl"))
+}
@@ -0,0 +1,132 @@
+//! `rdl watch`: keep running and download whatever shows up in a tasks file or a
+//! drop folder, aria2-style. Combine with `--daemon` for a long-running downloader.
+//!
+//! A watched tasks file is read again whenever it changes and only URLs not
+//! seen before are downloaded. In a watched folder every list file (`*.txt`) is
+//! downloaded as its own batch and then renamed to `<name>.txt.done`, so it is
+//! not picked up again after a restart; a list that cannot be parsed becomes
+//! `<name>.txt.failed` instead.
+
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use rdl_core::control::{wait_for_signal, Interrupted};
+use rdl_core::downloader::DownloadOptions;
+use rdl_core::metalink::is_metalink_path;
+use rdl_core::providers::DownloadItem;
+use rdl_core::tasks::{parse_tasks, read_tasks_file_as, InputFormat};
+
+use crate::commands::{run_items, FAILED_TASKS};
+
+/// Time for a file that just changed to be written completely
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+pub async fn run(target: &Path, format: InputFormat, options: DownloadOptions, daemon: bool) -> Result<()> {
+    let target = std::fs::canonicalize(target).with_context(|| format!("Cannot watch {:?}", target))?;
+    let (tx, mut changes) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok() {
+            let _ = tx.send(());
+        }
+    })
+    .context("Failed to start watching")?;
+    // Editors often replace a file instead of writing it, so a tasks file is watched through its directory
+    let dir = if target.is_dir() { target.as_path() } else { target.parent().unwrap_or(Path::new(".")) };
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {:?}", dir))?;
+    eprintln!("Watching {:?} for new downloads", target);
+
+    let mut seen = HashSet::new();
+    loop {
+        let batches = if target.is_dir() {
+            list_files(&target, format).await?
+        } else {
+            vec![(None, new_tasks(&target, format, &mut seen).await?)]
+        };
+        for (list, items) in batches {
+            if !items.is_empty() {
+                eprintln!("Queued {} new download(s)", items.len());
+                match run_items(items, options.clone(), daemon).await {
+                    Err(e) if e.is::<Interrupted>() => return Err(e),
+                    // Failures are reported and listed in failed.txt; keep watching
+                    Err(e) => eprintln!("{:#}", e),
+                    Ok(()) => {}
+                }
+            }
+            if let Some(list) = list {
+                let mut done = list.clone().into_os_string();
+                done.push(".done");
+                std::fs::rename(&list, &done).with_context(|| format!("Failed to rename {:?}", list))?;
+            }
+        }
+
+        #[cfg(unix)]
+        crate::systemd::notify(&format!("STATUS=Waiting for new downloads in {}", target.display()));
+        tokio::select! {
+            change = changes.recv() => {
+                if change.is_none() {
+                    return Ok(());
+                }
+            }
+            _ = wait_for_signal() => return Ok(()),
+        }
+        tokio::time::sleep(SETTLE_TIME).await;
+        while changes.try_recv().is_ok() {}
+    }
+}
+
+/// Tasks of the tasks file whose URL was not there the last time it was read.
+async fn new_tasks(path: &Path, format: InputFormat, seen: &mut HashSet<String>) -> Result<Vec<DownloadItem>> {
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        // Being replaced right now; the next change reads it again
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).context(format!("Failed to read {:?}", path)),
+    };
+    let format = match format {
+        InputFormat::Rdl if is_metalink_path(path) => InputFormat::Metalink,
+        format => format,
+    };
+    Ok(parse_tasks(&content, format)?
+        .into_iter()
+        .filter(|item| seen.insert(item.url.clone()))
+        .collect())
+}
+
+/// List files waiting in the drop folder, oldest first.
+async fn list_files(dir: &Path, format: InputFormat) -> Result<Vec<(Option<PathBuf>, Vec<DownloadItem>)>> {
+    let mut lists = vec![];
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let is_list = path.is_file()
+            && path.extension().is_some_and(|ext| ext == "txt")
+            && path.file_name().is_some_and(|name| name != FAILED_TASKS);
+        if is_list {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            lists.push((modified, path));
+        }
+    }
+    lists.sort();
+
+    let mut batches = vec![];
+    for (_, path) in lists {
+        match read_tasks_file_as(&path, format).await {
+            Ok(items) => batches.push((Some(path), items)),
+            // Renamed like a finished list so it is not read again; keep watching
+            Err(e) => {
+                eprintln!("Skipping {:?}: {:#}", path, e);
+                let mut failed = path.clone().into_os_string();
+                failed.push(".failed");
+                if let Err(e) = std::fs::rename(&path, &failed) {
+                    eprintln!("Failed to rename {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+    Ok(batches)
+}